@@ -36,6 +36,9 @@ fn place_setup<P: AsRef<Path>>(input_path: P) -> (TempDir, BuildCommand) {
     let options = BuildCommand {
         project: input,
         output,
+        watch: false,
+        profile: None,
+        asset_manifest: None,
     };
 
     (dir, options)
@@ -108,7 +108,47 @@ impl InMemoryFsInner {
         Ok(())
     }
 
+    /// Turns the entry at the given path and everything under it back into a
+    /// snapshot.
+    fn snapshot(&self, path: &Path) -> Option<VfsSnapshot> {
+        match self.entries.get(path)? {
+            Entry::File { contents } => Some(VfsSnapshot::file(contents.clone())),
+            Entry::Dir { children } => {
+                let children = children
+                    .iter()
+                    .filter_map(|child_path| {
+                        let name = child_path.file_name()?.to_string_lossy().into_owned();
+                        Some((name, self.snapshot(child_path)?))
+                    })
+                    .collect::<Vec<_>>();
+
+                Some(VfsSnapshot::dir(children))
+            }
+        }
+    }
+
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        let snapshot = match self.snapshot(from) {
+            Some(snapshot) => snapshot,
+            None => return not_found(from),
+        };
+
+        if let Some(Entry::Dir { .. }) = self.entries.get(to) {
+            return already_exists(to);
+        }
+
+        self.remove(from.to_path_buf());
+        self.remove(to.to_path_buf());
+        self.load_snapshot(to.to_path_buf(), snapshot)
+    }
+
     fn remove(&mut self, root_path: PathBuf) {
+        if let Some(parent_path) = root_path.parent() {
+            if let Some(Entry::Dir { children }) = self.entries.get_mut(parent_path) {
+                children.remove(&root_path);
+            }
+        }
+
         self.orphans.remove(&root_path);
 
         let mut to_remove = VecDeque::new();
@@ -170,6 +210,16 @@ impl VfsBackend for InMemoryFs {
         }
     }
 
+    fn create_dir(&mut self, path: &Path) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+
+        if inner.entries.contains_key(path) {
+            return already_exists(path);
+        }
+
+        inner.load_snapshot(path.to_path_buf(), VfsSnapshot::empty_dir())
+    }
+
     fn remove_file(&mut self, path: &Path) -> io::Result<()> {
         let mut inner = self.inner.lock().unwrap();
 
@@ -196,6 +246,11 @@ impl VfsBackend for InMemoryFs {
         }
     }
 
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.rename(from, to)
+    }
+
     fn metadata(&mut self, path: &Path) -> io::Result<Metadata> {
         let inner = self.inner.lock().unwrap();
 
@@ -247,3 +302,10 @@ fn not_found<T>(path: &Path) -> io::Result<T> {
         format!("path {} not found", path.display()),
     ))
 }
+
+fn already_exists<T>(path: &Path) -> io::Result<T> {
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("path {} already exists", path.display()),
+    ))
+}
@@ -71,9 +71,11 @@ pub trait VfsBackend: sealed::Sealed + Send + 'static {
     fn read(&mut self, path: &Path) -> io::Result<Vec<u8>>;
     fn write(&mut self, path: &Path, data: &[u8]) -> io::Result<()>;
     fn read_dir(&mut self, path: &Path) -> io::Result<ReadDir>;
+    fn create_dir(&mut self, path: &Path) -> io::Result<()>;
     fn metadata(&mut self, path: &Path) -> io::Result<Metadata>;
     fn remove_file(&mut self, path: &Path) -> io::Result<()>;
     fn remove_dir_all(&mut self, path: &Path) -> io::Result<()>;
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()>;
 
     fn event_receiver(&self) -> crossbeam_channel::Receiver<VfsEvent>;
     fn watch(&mut self, path: &Path) -> io::Result<()>;
@@ -163,6 +165,11 @@ impl VfsInner {
         Ok(dir)
     }
 
+    fn create_dir<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        self.backend.create_dir(path)
+    }
+
     fn remove_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
         let path = path.as_ref();
         let _ = self.backend.unwatch(path);
@@ -175,6 +182,13 @@ impl VfsInner {
         self.backend.remove_dir_all(path)
     }
 
+    fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, from: P, to: Q) -> io::Result<()> {
+        let from = from.as_ref();
+        let to = to.as_ref();
+        let _ = self.backend.unwatch(from);
+        self.backend.rename(from, to)
+    }
+
     fn metadata<P: AsRef<Path>>(&mut self, path: P) -> io::Result<Metadata> {
         let path = path.as_ref();
         self.backend.metadata(path)
@@ -264,6 +278,17 @@ impl Vfs {
         self.inner.lock().unwrap().read_dir(path)
     }
 
+    /// Create a directory. The parent of the directory must already exist.
+    ///
+    /// Roughly equivalent to [`std::fs::create_dir`][std::fs::create_dir].
+    ///
+    /// [std::fs::create_dir]: https://doc.rust-lang.org/stable/std/fs/fn.create_dir.html
+    #[inline]
+    pub fn create_dir<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        self.inner.lock().unwrap().create_dir(path)
+    }
+
     /// Remove a file.
     ///
     /// Roughly equivalent to [`std::fs::remove_file`][std::fs::remove_file].
@@ -286,6 +311,17 @@ impl Vfs {
         self.inner.lock().unwrap().remove_dir_all(path)
     }
 
+    /// Rename a file or directory, replacing the destination if it is a file
+    /// that already exists.
+    ///
+    /// Roughly equivalent to [`std::fs::rename`][std::fs::rename].
+    ///
+    /// [std::fs::rename]: https://doc.rust-lang.org/stable/std/fs/fn.rename.html
+    #[inline]
+    pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&self, from: P, to: Q) -> io::Result<()> {
+        self.inner.lock().unwrap().rename(from, to)
+    }
+
     /// Query metadata about the given path.
     ///
     /// Roughly equivalent to [`std::fs::metadata`][std::fs::metadata].
@@ -357,6 +393,17 @@ impl VfsLock<'_> {
         self.inner.read_dir(path)
     }
 
+    /// Create a directory. The parent of the directory must already exist.
+    ///
+    /// Roughly equivalent to [`std::fs::create_dir`][std::fs::create_dir].
+    ///
+    /// [std::fs::create_dir]: https://doc.rust-lang.org/stable/std/fs/fn.create_dir.html
+    #[inline]
+    pub fn create_dir<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        self.inner.create_dir(path)
+    }
+
     /// Remove a file.
     ///
     /// Roughly equivalent to [`std::fs::remove_file`][std::fs::remove_file].
@@ -379,6 +426,17 @@ impl VfsLock<'_> {
         self.inner.remove_dir_all(path)
     }
 
+    /// Rename a file or directory, replacing the destination if it is a file
+    /// that already exists.
+    ///
+    /// Roughly equivalent to [`std::fs::rename`][std::fs::rename].
+    ///
+    /// [std::fs::rename]: https://doc.rust-lang.org/stable/std/fs/fn.rename.html
+    #[inline]
+    pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&mut self, from: P, to: Q) -> io::Result<()> {
+        self.inner.rename(from, to)
+    }
+
     /// Query metadata about the given path.
     ///
     /// Roughly equivalent to [`std::fs::metadata`][std::fs::metadata].
@@ -35,6 +35,13 @@ impl VfsBackend for NoopBackend {
         ))
     }
 
+    fn create_dir(&mut self, _path: &Path) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "NoopBackend doesn't do anything",
+        ))
+    }
+
     fn remove_file(&mut self, _path: &Path) -> io::Result<()> {
         Err(io::Error::new(
             io::ErrorKind::Other,
@@ -49,6 +56,13 @@ impl VfsBackend for NoopBackend {
         ))
     }
 
+    fn rename(&mut self, _from: &Path, _to: &Path) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "NoopBackend doesn't do anything",
+        ))
+    }
+
     fn metadata(&mut self, _path: &Path) -> io::Result<Metadata> {
         Err(io::Error::new(
             io::ErrorKind::Other,
@@ -76,6 +76,10 @@ impl VfsBackend for StdBackend {
         })
     }
 
+    fn create_dir(&mut self, path: &Path) -> io::Result<()> {
+        fs_err::create_dir(path)
+    }
+
     fn remove_file(&mut self, path: &Path) -> io::Result<()> {
         fs_err::remove_file(path)
     }
@@ -84,6 +88,10 @@ impl VfsBackend for StdBackend {
         fs_err::remove_dir_all(path)
     }
 
+    fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        fs_err::rename(from, to)
+    }
+
     fn metadata(&mut self, path: &Path) -> io::Result<Metadata> {
         let inner = fs_err::metadata(path)?;
 
@@ -19,7 +19,13 @@ return {
 					unseenTags[tag] = true
 				end
 
-				local tagList = string.split(value, "\0")
+				-- Splitting an empty string gives back one empty tag, but an
+				-- empty value means there are no tags at all.
+				local tagList = {}
+				if value ~= "" then
+					tagList = string.split(value, "\0")
+				end
+
 				for _, tag in ipairs(tagList) do
 					unseenTags[tag] = nil
 					CollectionService:AddTag(instance, tag)
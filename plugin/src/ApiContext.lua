@@ -9,6 +9,7 @@ local Version = require(script.Parent.Version)
 local validateApiInfo = Types.ifEnabled(Types.ApiInfoResponse)
 local validateApiRead = Types.ifEnabled(Types.ApiReadResponse)
 local validateApiSubscribe = Types.ifEnabled(Types.ApiSubscribeResponse)
+local validateApiError = Types.ifEnabled(Types.ApiError)
 
 --[[
 	Returns a promise that will never resolve nor reject.
@@ -163,12 +164,17 @@ function ApiContext:write(patch)
 		local fixedUpdate = {
 			id = update.id,
 			changedName = update.changedName,
+			previousName = update.previousName,
 		}
 
 		if next(update.changedProperties) ~= nil then
 			fixedUpdate.changedProperties = update.changedProperties
 		end
 
+		if update.previousProperties ~= nil and next(update.previousProperties) ~= nil then
+			fixedUpdate.previousProperties = update.previousProperties
+		end
+
 		table.insert(updated, fixedUpdate)
 	end
 
@@ -190,12 +196,30 @@ function ApiContext:write(patch)
 	body = Http.jsonEncode(body)
 
 	return Http.post(url, body)
-		:andThen(rejectFailedRequests)
-		:andThen(Http.Response.json)
-		:andThen(function(body)
-			Log.info("Write response: {:?}", body)
+		:andThen(function(response)
+			-- The server answers with 409 Conflict when someone else changed
+			-- the values we expected to replace. Nothing was written, and the
+			-- response lists every value that didn't match.
+			if response.code == 409 then
+				local conflictBody = response:json()
+
+				return {
+					written = false,
+					conflicts = conflictBody.conflicts,
+				}
+			end
 
-			return body
+			return Promise.resolve(response)
+				:andThen(rejectFailedRequests)
+				:andThen(Http.Response.json)
+				:andThen(function(body)
+					Log.info("Write response: {:?}", body)
+
+					return {
+						written = true,
+						conflicts = {},
+					}
+				end)
 		end)
 end
 
@@ -218,6 +242,19 @@ function ApiContext:retrieveMessages()
 	end
 
 	return sendRequest()
+		:andThen(function(response)
+			-- The server only keeps a limited number of messages. If we fell
+			-- too far behind, it answers with 410 Gone and we need to read the
+			-- whole tree again.
+			if response.code == 410 then
+				local body = response:json()
+				assert(validateApiError(body))
+
+				return Promise.reject(body)
+			end
+
+			return response
+		end)
 		:andThen(rejectFailedRequests)
 		:andThen(Http.Response.json)
 		:andThen(function(body)
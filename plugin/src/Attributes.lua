@@ -0,0 +1,268 @@
+--[[
+	Reads the binary format that Roblox serializes attributes into, which the
+	Rojo server sends in the AttributesSerialize property, and applies the
+	attributes it contains to instances.
+
+	AttributesSerialize isn't in the reflection database, so setting it like
+	other properties would do nothing.
+]]
+
+local Attributes = {}
+
+Attributes.PROPERTY = "AttributesSerialize"
+
+--[[
+	Decodes a little-endian single precision float from four bytes.
+]]
+local function decodeFloat32(b1, b2, b3, b4)
+	local sign = b4 >= 128 and -1 or 1
+	local exponent = (b4 % 128) * 2 + math.floor(b3 / 128)
+	local mantissa = ((b3 % 128) * 256 + b2) * 256 + b1
+
+	if exponent == 0 then
+		return sign * math.ldexp(mantissa, -149)
+	elseif exponent == 255 then
+		return mantissa == 0 and sign * math.huge or 0 / 0
+	end
+
+	return sign * math.ldexp(mantissa + 0x800000, exponent - 150)
+end
+
+--[[
+	Decodes a little-endian double precision float from eight bytes.
+]]
+local function decodeFloat64(b1, b2, b3, b4, b5, b6, b7, b8)
+	local sign = b8 >= 128 and -1 or 1
+	local exponent = (b8 % 128) * 16 + math.floor(b7 / 16)
+	local mantissa = 0
+
+	for _, byte in ipairs({b7 % 16, b6, b5, b4, b3, b2, b1}) do
+		mantissa = mantissa * 256 + byte
+	end
+
+	if exponent == 0 then
+		return sign * math.ldexp(mantissa, -1074)
+	elseif exponent == 2047 then
+		return mantissa == 0 and sign * math.huge or 0 / 0
+	end
+
+	return sign * math.ldexp(mantissa + 2 ^ 52, exponent - 1075)
+end
+
+local Reader = {}
+Reader.__index = Reader
+
+function Reader.new(buffer)
+	return setmetatable({
+		buffer = buffer,
+		offset = 1,
+	}, Reader)
+end
+
+function Reader:bytes(count)
+	local last = self.offset + count - 1
+
+	if last > #self.buffer then
+		error("Attributes ended unexpectedly", 3)
+	end
+
+	local start = self.offset
+	self.offset = last + 1
+
+	return string.byte(self.buffer, start, last)
+end
+
+function Reader:u8()
+	return self:bytes(1)
+end
+
+function Reader:u32()
+	local b1, b2, b3, b4 = self:bytes(4)
+
+	return ((b4 * 256 + b3) * 256 + b2) * 256 + b1
+end
+
+function Reader:i32()
+	local value = self:u32()
+
+	if value >= 2 ^ 31 then
+		return value - 2 ^ 32
+	end
+
+	return value
+end
+
+function Reader:f32()
+	return decodeFloat32(self:bytes(4))
+end
+
+function Reader:f64()
+	return decodeFloat64(self:bytes(8))
+end
+
+function Reader:string()
+	local length = self:u32()
+	local start = self.offset
+
+	self:bytes(length)
+
+	return self.buffer:sub(start, start + length - 1)
+end
+
+function Reader:floats(count)
+	local values = {}
+
+	for i = 1, count do
+		values[i] = self:f32()
+	end
+
+	return unpack(values, 1, count)
+end
+
+local readers = {
+	[0x02] = function(reader)
+		return reader:string()
+	end,
+	[0x03] = function(reader)
+		return reader:u8() ~= 0
+	end,
+	[0x06] = function(reader)
+		return reader:f64()
+	end,
+	[0x09] = function(reader)
+		local scale = reader:f32()
+		local offset = reader:i32()
+
+		return UDim.new(scale, offset)
+	end,
+	[0x0A] = function(reader)
+		local xScale = reader:f32()
+		local xOffset = reader:i32()
+		local yScale = reader:f32()
+		local yOffset = reader:i32()
+
+		return UDim2.new(xScale, xOffset, yScale, yOffset)
+	end,
+	[0x0E] = function(reader)
+		return BrickColor.new(reader:u32())
+	end,
+	[0x0F] = function(reader)
+		return Color3.new(reader:floats(3))
+	end,
+	[0x10] = function(reader)
+		return Vector2.new(reader:floats(2))
+	end,
+	[0x11] = function(reader)
+		return Vector3.new(reader:floats(3))
+	end,
+	[0x14] = function(reader)
+		local x, y, z = reader:floats(3)
+		local rotationId = reader:u8()
+
+		-- Rojo always writes out the full rotation matrix, which is what a
+		-- rotation ID of zero means.
+		if rotationId ~= 0 then
+			error(("Unsupported CFrame rotation ID %d in attributes"):format(rotationId), 2)
+		end
+
+		return CFrame.new(x, y, z, reader:floats(9))
+	end,
+	[0x17] = function(reader)
+		local keypoints = {}
+
+		for i = 1, reader:u32() do
+			local envelope, time, value = reader:floats(3)
+			keypoints[i] = NumberSequenceKeypoint.new(time, value, envelope)
+		end
+
+		return NumberSequence.new(keypoints)
+	end,
+	[0x19] = function(reader)
+		local keypoints = {}
+
+		for i = 1, reader:u32() do
+			local _envelope, time, r, g, b = reader:floats(5)
+			keypoints[i] = ColorSequenceKeypoint.new(time, Color3.new(r, g, b))
+		end
+
+		return ColorSequence.new(keypoints)
+	end,
+	[0x1B] = function(reader)
+		return NumberRange.new(reader:floats(2))
+	end,
+	[0x1C] = function(reader)
+		return Rect.new(reader:floats(4))
+	end,
+}
+
+--[[
+	Decodes the value of an AttributesSerialize property into a table mapping
+	attribute names to values.
+]]
+function Attributes.decode(buffer)
+	local attributes = {}
+
+	-- Instances that never had attributes can have an empty value.
+	if buffer == "" then
+		return attributes
+	end
+
+	local reader = Reader.new(buffer)
+
+	for _ = 1, reader:u32() do
+		local name = reader:string()
+		local typeId = reader:u8()
+		local read = readers[typeId]
+
+		if read == nil then
+			error(("Unsupported type ID %d for attribute %s"):format(typeId, name), 2)
+		end
+
+		attributes[name] = read(reader)
+	end
+
+	return attributes
+end
+
+--[[
+	Returns whether the instance has exactly the given attributes.
+]]
+function Attributes.matches(instance, attributes)
+	local existing = instance:GetAttributes()
+
+	for name, value in pairs(existing) do
+		if attributes[name] ~= value then
+			return false
+		end
+	end
+
+	for name in pairs(attributes) do
+		if existing[name] == nil then
+			return false
+		end
+	end
+
+	return true
+end
+
+--[[
+	Sets the attributes of the instance to the ones in the given
+	AttributesSerialize value, removing any that it doesn't have.
+]]
+function Attributes.write(instance, buffer)
+	local attributes = Attributes.decode(buffer)
+
+	for name in pairs(instance:GetAttributes()) do
+		if attributes[name] == nil then
+			instance:SetAttribute(name, nil)
+		end
+	end
+
+	for name, value in pairs(attributes) do
+		instance:SetAttribute(name, value)
+	end
+
+	return true
+end
+
+return Attributes
@@ -0,0 +1,48 @@
+return function()
+	local Attributes = require(script.Parent.Attributes)
+
+	it("should decode no attributes", function()
+		expect(next(Attributes.decode(""))).to.equal(nil)
+		expect(next(Attributes.decode("\0\0\0\0"))).to.equal(nil)
+	end)
+
+	it("should decode strings, bools, and numbers", function()
+		local attributes = Attributes.decode(
+			"\3\0\0\0"
+			.. "\7\0\0\0Enabled\3\1"
+			.. "\5\0\0\0Label\2\2\0\0\0Hi"
+			.. "\5\0\0\0Speed\6\0\0\0\0\0\0\48\64"
+		)
+
+		expect(attributes.Enabled).to.equal(true)
+		expect(attributes.Label).to.equal("Hi")
+		expect(attributes.Speed).to.equal(16)
+	end)
+
+	it("should decode Vector3 values", function()
+		local attributes = Attributes.decode(
+			"\1\0\0\0"
+			.. "\6\0\0\0Offset\17\0\0\192\63\0\0\0\192\0\0\0\0"
+		)
+
+		expect(attributes.Offset).to.equal(Vector3.new(1.5, -2, 0))
+	end)
+
+	it("should throw on unknown types", function()
+		expect(function()
+			Attributes.decode("\1\0\0\0\3\0\0\0Bad\255")
+		end).to.throw()
+	end)
+
+	it("should replace existing attributes when written", function()
+		local folder = Instance.new("Folder")
+		folder:SetAttribute("Old", 1)
+
+		Attributes.write(folder, "\1\0\0\0\3\0\0\0New\3\1")
+
+		expect(folder:GetAttribute("Old")).to.equal(nil)
+		expect(folder:GetAttribute("New")).to.equal(true)
+		expect(Attributes.matches(folder, {New = true})).to.equal(true)
+		expect(Attributes.matches(folder, {})).to.equal(false)
+	end)
+end
@@ -9,7 +9,7 @@ local Log = require(script.Parent.Parent.Log)
 local InstanceMap = {}
 InstanceMap.__index = InstanceMap
 
-function InstanceMap.new(onInstanceChanged)
+function InstanceMap.new(onInstanceChanged, onChildAdded)
 	local self = {
 		-- A map from IDs to instances.
 		fromIds = {},
@@ -17,6 +17,11 @@ function InstanceMap.new(onInstanceChanged)
 		-- A map from instances to IDs.
 		fromInstances = {},
 
+		-- A map from IDs to the name and properties that the Rojo server last
+		-- told us each instance has. Changes sent back to the server say which
+		-- values they expect to replace using this.
+		syncedValues = {},
+
 		-- A set of all instances that updates should be paused for. This set
 		-- should generally be empty, and will be filled by pauseInstance
 		-- temporarily.
@@ -28,6 +33,10 @@ function InstanceMap.new(onInstanceChanged)
 		-- Callback that's invoked whenever an instance is changed and it was
 		-- not paused.
 		onInstanceChanged = onInstanceChanged,
+
+		-- Callback that's invoked whenever an instance we don't know about is
+		-- parented to an instance we know about, unless the parent was paused.
+		onChildAdded = onChildAdded,
 	}
 
 	return setmetatable(self, InstanceMap)
@@ -81,6 +90,7 @@ function InstanceMap:removeId(id)
 		self:__disconnectSignals(instance)
 		self.fromIds[id] = nil
 		self.fromInstances[instance] = nil
+		self.syncedValues[id] = nil
 	else
 		Log.warn("Attempted to remove nonexistant ID {}", id)
 	end
@@ -93,6 +103,7 @@ function InstanceMap:removeInstance(instance)
 	if id ~= nil then
 		self.fromInstances[instance] = nil
 		self.fromIds[id] = nil
+		self.syncedValues[id] = nil
 	else
 		Log.warn("Attempted to remove nonexistant instance {}", instance)
 	end
@@ -131,6 +142,33 @@ function InstanceMap:destroyId(id)
 	end
 end
 
+--[[
+	Returns the name and properties that the Rojo server last told us the
+	instance with the given ID has, creating an empty record if there isn't one.
+]]
+function InstanceMap:getSyncedValues(id)
+	local values = self.syncedValues[id]
+
+	if values == nil then
+		values = {
+			name = nil,
+			properties = {},
+		}
+
+		self.syncedValues[id] = values
+	end
+
+	return values
+end
+
+function InstanceMap:setSyncedName(id, name)
+	self:getSyncedValues(id).name = name
+end
+
+function InstanceMap:setSyncedProperty(id, propertyName, apiValue)
+	self:getSyncedValues(id).properties[propertyName] = apiValue
+end
+
 --[[
 	Pause updates for an instance momentarily and invoke a callback.
 
@@ -184,6 +222,19 @@ function InstanceMap:__connectSignals(instance)
 			self:__maybeFireInstanceChanged(instance, propertyName)
 		end)
 	end
+
+	if self.onChildAdded ~= nil then
+		local signals = self.instancesToSignal[instance]
+
+		if typeof(signals) ~= "table" then
+			signals = {signals}
+			self.instancesToSignal[instance] = signals
+		end
+
+		table.insert(signals, instance.ChildAdded:Connect(function(child)
+			self:__maybeFireChildAdded(instance, child)
+		end))
+	end
 end
 
 function InstanceMap:__maybeFireInstanceChanged(instance, propertyName)
@@ -200,13 +251,30 @@ function InstanceMap:__maybeFireInstanceChanged(instance, propertyName)
 	self.onInstanceChanged(instance, propertyName)
 end
 
+function InstanceMap:__maybeFireChildAdded(instance, child)
+	-- Instances created by the reconciler are tracked before they're parented,
+	-- so this only lets through instances that came from somewhere else.
+	if self.fromInstances[child] ~= nil then
+		return
+	end
+
+	Log.trace("{} was added to {}", child, instance:GetFullName())
+
+	if self.pausedUpdateInstances[instance] then
+		return
+	end
+
+	self.onChildAdded(instance, child)
+end
+
 function InstanceMap:__disconnectSignals(instance)
 	local signals = self.instancesToSignal[instance]
 
 	if signals ~= nil then
 		-- In most cases, we only have a single signal, so we avoid keeping
 		-- around the extra table. ValueBase objects force us to use multiple
-		-- signals to emulate the Instance.Changed event, however.
+		-- signals to emulate the Instance.Changed event, however, and watching
+		-- for new children takes another signal.
 		if typeof(signals) == "table" then
 			for _, signal in ipairs(signals) do
 				signal:Disconnect()
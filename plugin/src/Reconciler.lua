@@ -6,6 +6,7 @@
 local RbxDom = require(script.Parent.Parent.RbxDom)
 local t = require(script.Parent.Parent.t)
 
+local Attributes = require(script.Parent.Attributes)
 local Types = require(script.Parent.Types)
 local invariant = require(script.Parent.invariant)
 local getCanonicalProperty = require(script.Parent.getCanonicalProperty)
@@ -111,8 +112,11 @@ function Reconciler:applyPatch(patch)
 			-- addition.
 			--
 			-- This helps us make sure we only reify each instance once, and we
-			-- start from the top.
-			while patch.added[apiInstance.Parent] ~= nil do
+			-- start from the top. Ancestors we already have, like instances
+			-- adopted with adoptInstance, don't need to be created again.
+			while patch.added[apiInstance.Parent] ~= nil
+				and self.__instanceMap.fromIds[apiInstance.Parent] == nil
+			do
 				id = apiInstance.Parent
 				apiInstance = patch.added[id]
 			end
@@ -149,6 +153,7 @@ function Reconciler:applyPatch(patch)
 
 		if update.changedName ~= nil then
 			instance.Name = update.changedName
+			self.__instanceMap:setSyncedName(update.id, update.changedName)
 		end
 
 		if update.changedMetadata ~= nil then
@@ -159,11 +164,34 @@ function Reconciler:applyPatch(patch)
 			for propertyName, propertyValue in pairs(update.changedProperties) do
 				-- TODO: Gracefully handle this error instead?
 				assert(setCanonicalProperty(instance, propertyName, self:__decodeApiValue(propertyValue)))
+				self.__instanceMap:setSyncedProperty(update.id, propertyName, propertyValue)
 			end
 		end
 	end
 end
 
+--[[
+	Starts tracking an instance that already exists in the DataModel under an
+	ID from the Rojo server, like an instance the user added that the server
+	has now created files for. Patches adding that ID won't create it again.
+]]
+function Reconciler:adoptInstance(id, apiInstance, instance)
+	self.__instanceMap:insert(id, instance)
+	self:__recordSyncedValues(id, apiInstance)
+end
+
+--[[
+	Remembers the name and properties of an instance as the Rojo server sees
+	them.
+]]
+function Reconciler:__recordSyncedValues(id, apiInstance)
+	self.__instanceMap:setSyncedName(id, apiInstance.Name)
+
+	for propertyName, propertyValue in pairs(apiInstance.Properties) do
+		self.__instanceMap:setSyncedProperty(id, propertyName, propertyValue)
+	end
+end
+
 --[[
 	Transforms a value into one that can be sent over the network back to the
 	Rojo server.
@@ -256,6 +284,7 @@ function Reconciler:__reifyInstance(apiInstances, id, parentInstance)
 	end
 
 	self.__instanceMap:insert(id, instance)
+	self:__recordSyncedValues(id, apiInstance)
 
 	for _, childId in ipairs(apiInstance.Children) do
 		self:__reifyInstance(apiInstances, childId, instance)
@@ -283,6 +312,7 @@ function Reconciler:__hydrateInternal(apiInstances, id, instance, hydratePatch)
 	self.__instanceMap:insert(id, instance)
 
 	local apiInstance = apiInstances[id]
+	self:__recordSyncedValues(id, apiInstance)
 
 	local function markIdAdded(id)
 		local apiInstance = apiInstances[id]
@@ -301,14 +331,24 @@ function Reconciler:__hydrateInternal(apiInstances, id, instance, hydratePatch)
 	end
 
 	for propertyName, virtualValue in pairs(apiInstance.Properties) do
-		local success, existingValue = getCanonicalProperty(instance, propertyName)
+		if propertyName == Attributes.PROPERTY then
+			-- Attributes can't be read back in the format they're sent in, so
+			-- they're compared after decoding them instead.
+			local attributes = Attributes.decode(self:__decodeApiValue(virtualValue))
 
-		if success then
-			local decodedValue = self:__decodeApiValue(virtualValue)
-
-			if existingValue ~= decodedValue then
+			if not Attributes.matches(instance, attributes) then
 				changedProperties[propertyName] = virtualValue
 			end
+		else
+			local success, existingValue = getCanonicalProperty(instance, propertyName)
+
+			if success then
+				local decodedValue = self:__decodeApiValue(virtualValue)
+
+				if existingValue ~= decodedValue then
+					changedProperties[propertyName] = virtualValue
+				end
+			end
 		end
 	end
 
@@ -0,0 +1,46 @@
+return function()
+	local InstanceMap = require(script.Parent.InstanceMap)
+	local Reconciler = require(script.Parent.Reconciler)
+
+	local function folder(id, parent, name, children)
+		return {
+			Id = id,
+			Parent = parent,
+			Name = name,
+			ClassName = "Folder",
+			Properties = {},
+			Children = children,
+		}
+	end
+
+	it("should not create adopted instances again", function()
+		local instanceMap = InstanceMap.new()
+		local reconciler = Reconciler.new(instanceMap)
+
+		local root = Instance.new("Folder")
+		instanceMap:insert("ROOT", root)
+
+		local child = Instance.new("Folder")
+		child.Name = "Child"
+		child.Parent = root
+
+		local added = {
+			CHILD = folder("CHILD", "ROOT", "Child", {"GRANDCHILD"}),
+			GRANDCHILD = folder("GRANDCHILD", "CHILD", "Grandchild", {}),
+		}
+
+		reconciler:adoptInstance("CHILD", added.CHILD, child)
+		reconciler:applyPatch({
+			removed = {},
+			added = added,
+			updated = {},
+		})
+
+		expect(#root:GetChildren()).to.equal(1)
+		expect(instanceMap.fromIds.CHILD).to.equal(child)
+		expect(instanceMap.fromIds.GRANDCHILD.Parent).to.equal(child)
+
+		instanceMap:stop()
+		root:Destroy()
+	end)
+end
@@ -1,11 +1,14 @@
+local HttpService = game:GetService("HttpService")
 local StudioService = game:GetService("StudioService")
 
 local Log = require(script.Parent.Parent.Log)
 local Fmt = require(script.Parent.Parent.Fmt)
+local Promise = require(script.Parent.Parent.Promise)
 local t = require(script.Parent.Parent.t)
 
 local InstanceMap = require(script.Parent.InstanceMap)
 local Reconciler = require(script.Parent.Reconciler)
+local getCanonicalProperty = require(script.Parent.getCanonicalProperty)
 local strict = require(script.Parent.strict)
 
 local Status = strict("Session.Status", {
@@ -15,6 +18,11 @@ local Status = strict("Session.Status", {
 	Disconnected = "Disconnected",
 })
 
+-- Properties of instances added in Studio that we send to the Rojo server.
+-- encodeApiValue only knows about strings, so this sticks to the properties
+-- that files can hold.
+local ADDED_PROPERTIES = {"Source", "Value"}
+
 local function debugPatch(patch)
 	return Fmt.debugify(patch, function(patch, output)
 		output:writeLine("Patch {{")
@@ -57,7 +65,11 @@ function ServeSession.new(options)
 		self:__onInstanceChanged(instance, propertyName)
 	end
 
-	local instanceMap = InstanceMap.new(onInstanceChanged)
+	local function onChildAdded(parent, child)
+		self:__onChildAdded(parent, child)
+	end
+
+	local instanceMap = InstanceMap.new(onInstanceChanged, onChildAdded)
 	local reconciler = Reconciler.new(instanceMap)
 
 	local connections = {}
@@ -80,6 +92,13 @@ function ServeSession.new(options)
 		__twoWaySync = options.twoWaySync,
 		__reconciler = reconciler,
 		__instanceMap = instanceMap,
+		__rootInstanceId = nil,
+
+		-- Instances added in Studio that we've asked the Rojo server to
+		-- create, keyed by the ID we picked for each one. Once the server
+		-- tells us which of its instances they became, we track them under
+		-- the server's IDs instead of creating them again.
+		__pendingAdds = {},
 		__statusChangedCallback = nil,
 		__connections = connections,
 	}
@@ -112,6 +131,7 @@ function ServeSession:start()
 			self:__setStatus(Status.Connected)
 
 			local rootInstanceId = serverInfo.rootInstanceId
+			self.__rootInstanceId = rootInstanceId
 
 			return self:__initialSync(rootInstanceId)
 				:andThen(function()
@@ -171,14 +191,17 @@ function ServeSession:__onInstanceChanged(instance, propertyName)
 	end
 
 	local remove = nil
+	local syncedValues = self.__instanceMap:getSyncedValues(instanceId)
 
 	local update = {
 		id = instanceId,
 		changedProperties = {},
+		previousProperties = {},
 	}
 
 	if propertyName == "Name" then
 		update.changedName = instance.Name
+		update.previousName = syncedValues.name
 	elseif propertyName == "Parent" then
 		if instance.Parent == nil then
 			update = nil
@@ -196,6 +219,7 @@ function ServeSession:__onInstanceChanged(instance, propertyName)
 		end
 
 		update.changedProperties[propertyName] = encoded
+		update.previousProperties[propertyName] = syncedValues.properties[propertyName]
 	end
 
 	local patch = {
@@ -204,7 +228,148 @@ function ServeSession:__onInstanceChanged(instance, propertyName)
 		updated = {update},
 	}
 
+	-- Our change is what the server will have once it's written, so later
+	-- changes should expect to replace it. Waiting for the response to record
+	-- it would make a second change sent before then expect the old value and
+	-- get rejected.
+	local replacedName = syncedValues.name
+	local replacedProperties = {}
+
+	if update ~= nil then
+		if update.changedName ~= nil then
+			self.__instanceMap:setSyncedName(instanceId, update.changedName)
+		end
+
+		for changedName, value in pairs(update.changedProperties) do
+			replacedProperties[changedName] = syncedValues.properties[changedName]
+			self.__instanceMap:setSyncedProperty(instanceId, changedName, value)
+		end
+	end
+
 	self.__apiContext:write(patch)
+		:andThen(function(result)
+			if not result.written then
+				for _, conflict in ipairs(result.conflicts) do
+					Log.warn(
+						"Could not sync back {:?}.{}, it was changed by someone else. Expected {:?}, found {:?}",
+						instance,
+						conflict.field,
+						conflict.expected,
+						conflict.current
+					)
+
+					-- The server kept its own value, which is what the next
+					-- change has to replace instead.
+					if conflict.id == instanceId then
+						if conflict.field == "Name" then
+							if conflict.current ~= nil then
+								self.__instanceMap:setSyncedName(instanceId, conflict.current.Value)
+							end
+						elseif conflict.field ~= "ClassName" then
+							self.__instanceMap:setSyncedProperty(instanceId, conflict.field, conflict.current)
+						end
+					end
+				end
+			end
+		end)
+		:catch(function(err)
+			Log.warn("Could not sync back {:?}: {}", instance, tostring(err))
+
+			-- Nothing was written, so the server still has the values we
+			-- thought we replaced.
+			if update ~= nil then
+				if update.changedName ~= nil then
+					self.__instanceMap:setSyncedName(instanceId, replacedName)
+				end
+
+				for changedName in pairs(update.changedProperties) do
+					self.__instanceMap:setSyncedProperty(instanceId, changedName, replacedProperties[changedName])
+				end
+			end
+		end)
+end
+
+function ServeSession:__onChildAdded(parent, child)
+	if not self.__twoWaySync then
+		return
+	end
+
+	local parentId = self.__instanceMap.fromInstances[parent]
+
+	if parentId == nil then
+		return
+	end
+
+	local added = {}
+
+	local function addInstance(instance, parentClientId)
+		local clientId = HttpService:GenerateGUID(false):lower()
+		local properties = {}
+
+		for _, propertyName in ipairs(ADDED_PROPERTIES) do
+			local readOk, value = getCanonicalProperty(instance, propertyName)
+
+			if readOk then
+				local encodeOk, encoded = self.__reconciler:encodeApiValue(value)
+
+				if encodeOk then
+					properties[propertyName] = encoded
+				end
+			end
+		end
+
+		local apiInstance = {
+			Id = clientId,
+			Parent = parentClientId,
+			Name = instance.Name,
+			ClassName = instance.ClassName,
+			Properties = properties,
+			Children = {},
+		}
+
+		added[clientId] = apiInstance
+		self.__pendingAdds[clientId] = instance
+
+		for _, childInstance in ipairs(instance:GetChildren()) do
+			table.insert(apiInstance.Children, addInstance(childInstance, clientId))
+		end
+
+		return clientId
+	end
+
+	addInstance(child, parentId)
+
+	-- The server only creates files for instances under a parent that's
+	-- backed by a directory. If it can't, the instance stays in Studio
+	-- without being synced.
+	self.__apiContext:write({
+		removed = {},
+		added = added,
+		updated = {},
+	})
+		:catch(function(err)
+			Log.warn("Could not sync back {:?}: {}", child, tostring(err))
+		end)
+end
+
+--[[
+	Tracks the instances we added in Studio under the IDs the Rojo server gave
+	them, so that applying the message doesn't create them a second time.
+]]
+function ServeSession:__adoptPendingAdds(message)
+	if message.clientIds == nil then
+		return
+	end
+
+	for id, clientId in pairs(message.clientIds) do
+		local instance = self.__pendingAdds[clientId]
+		local apiInstance = message.added[id]
+
+		if instance ~= nil and apiInstance ~= nil then
+			self.__pendingAdds[clientId] = nil
+			self.__reconciler:adoptInstance(id, apiInstance, instance)
+		end
+	end
 end
 
 function ServeSession:__initialSync(rootInstanceId)
@@ -237,19 +402,46 @@ function ServeSession:__mainSyncLoop()
 	return self.__apiContext:retrieveMessages()
 		:andThen(function(messages)
 			for _, message in ipairs(messages) do
+				self:__adoptPendingAdds(message)
 				self.__reconciler:applyPatch(message)
 			end
 
 			if self.__status ~= Status.Disconnected then
 				return self:__mainSyncLoop()
 			end
+		end, function(err)
+			if typeof(err) == "table" and err.kind == "CursorTooOld" then
+				Log.info("Rojo server no longer has the changes we missed, reading the whole tree again...")
+
+				return self:__resync()
+					:andThen(function()
+						if self.__status ~= Status.Disconnected then
+							return self:__mainSyncLoop()
+						end
+					end)
+			end
+
+			return Promise.reject(err)
 		end)
 end
 
+--[[
+	Forgets every instance we know about and reads the whole tree from the
+	server again, pairing its instances up with the ones in the DataModel the
+	same way that connecting does.
+]]
+function ServeSession:__resync()
+	self.__instanceMap:stop()
+	self.__pendingAdds = {}
+
+	return self:__initialSync(self.__rootInstanceId)
+end
+
 function ServeSession:__stopInternal(err)
 	self:__setStatus(Status.Disconnected, err)
 	self.__apiContext:disconnect()
 	self.__instanceMap:stop()
+	self.__pendingAdds = {}
 
 	for _, connection in ipairs(self.__connections) do
 		connection:Disconnect()
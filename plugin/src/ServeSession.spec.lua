@@ -0,0 +1,54 @@
+return function()
+	local Promise = require(script.Parent.Parent.Promise)
+
+	local ServeSession = require(script.Parent.ServeSession)
+
+	-- An API context that remembers every write and never answers, like a
+	-- server that's still busy with the first change when the second is made.
+	local function slowApiContext()
+		local apiContext = {
+			writes = {},
+		}
+
+		function apiContext:write(patch)
+			table.insert(self.writes, patch)
+
+			return Promise.new(function() end)
+		end
+
+		function apiContext:disconnect()
+		end
+
+		return apiContext
+	end
+
+	it("should expect to replace a change that hasn't been answered yet", function()
+		local apiContext = slowApiContext()
+		local session = ServeSession.new({
+			apiContext = apiContext,
+			openScriptsExternally = false,
+			twoWaySync = true,
+		})
+
+		local value = Instance.new("StringValue")
+		value.Name = "Original"
+		session.__instanceMap:insert("VALUE", value)
+		session.__instanceMap:setSyncedName("VALUE", "Original")
+
+		value.Name = "First"
+		value.Name = "Second"
+
+		expect(#apiContext.writes).to.equal(2)
+
+		local firstUpdate = apiContext.writes[1].updated[1]
+		expect(firstUpdate.previousName).to.equal("Original")
+		expect(firstUpdate.changedName).to.equal("First")
+
+		local secondUpdate = apiContext.writes[2].updated[1]
+		expect(secondUpdate.previousName).to.equal("First")
+		expect(secondUpdate.changedName).to.equal("Second")
+
+		session:stop()
+		value:Destroy()
+	end)
+end
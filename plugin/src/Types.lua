@@ -36,6 +36,7 @@ local ApiSubscribeMessage = t.interface({
 	removed = t.array(RbxId),
 	added = t.map(RbxId, ApiInstance),
 	updated = t.array(ApiInstanceUpdate),
+	clientIds = t.optional(t.map(RbxId, RbxId)),
 })
 
 local ApiInfoResponse = t.interface({
@@ -62,6 +63,7 @@ local ApiError = t.interface({
 	kind = t.union(
 		t.literal("NotFound"),
 		t.literal("BadRequest"),
+		t.literal("CursorTooOld"),
 		t.literal("InternalError")
 	),
 	details = t.string,
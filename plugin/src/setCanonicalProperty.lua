@@ -1,9 +1,15 @@
 local RbxDom = require(script.Parent.Parent.RbxDom)
 
+local Attributes = require(script.Parent.Attributes)
+
 --[[
 	Attempts to set a property on the given instance.
 ]]
 local function setCanonicalProperty(instance, propertyName, value)
+	if propertyName == Attributes.PROPERTY then
+		return Attributes.write(instance, value)
+	end
+
 	local descriptor = RbxDom.findCanonicalPropertyDescriptor(instance.ClassName, propertyName)
 
 	-- We can skip unknown properties; they're not likely reflected to Lua.
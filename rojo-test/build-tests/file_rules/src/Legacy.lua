@@ -0,0 +1 @@
+return "still a module"
@@ -0,0 +1,14 @@
+-- Turns Markdown files into StringValue instances. Every other file is left
+-- for Rojo to handle.
+return function(path, contents)
+	if not path:match("%.md$") then
+		return nil
+	end
+
+	return {
+		ClassName = "StringValue",
+		Properties = {
+			Value = contents,
+		},
+	}
+end
@@ -0,0 +1 @@
+return "Hello from Lua!"
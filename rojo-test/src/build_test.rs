@@ -21,12 +21,16 @@ macro_rules! gen_build_tests {
 }
 
 gen_build_tests! {
+    assets,
+    attributes,
     client_in_folder,
     client_init,
     csv_bug_145,
     csv_bug_147,
     csv_in_folder,
+    data_files,
     deep_nesting,
+    file_rules,
     gitkeep,
     infer_service_name,
     infer_starter_player,
@@ -36,16 +40,23 @@ gen_build_tests! {
     json_as_lua,
     json_model_in_folder,
     json_model_legacy_name,
+    loc_json,
     module_in_folder,
     module_init,
+    project_env_variables,
+    project_middleware,
+    project_variables,
     rbxm_in_folder,
     rbxmx_in_folder,
+    rbxmx_multiple_roots,
     rbxmx_ref,
     script_meta_disabled,
     server_in_folder,
     server_init,
+    tags,
     txt,
     txt_in_folder,
+    user_plugins,
 }
 
 #[cfg(feature = "unstable_glob_ignore_paths")]
@@ -74,6 +85,8 @@ fn run_build_test(test_name: &str) {
             output_path.to_str().unwrap(),
         ])
         .env("RUST_LOG", "error")
+        // Read by the project_env_variables test as ${env:ROJO_TEST_GRAVITY}.
+        .env("ROJO_TEST_GRAVITY", "50")
         .current_dir(working_dir)
         .status()
         .expect("Couldn't start Rojo");
@@ -3,7 +3,9 @@ use std::collections::HashMap;
 use rbx_dom_weak::RbxId;
 use serde::Serialize;
 
-use librojo::web_api::{Instance, InstanceUpdate, ReadResponse, SubscribeResponse};
+use librojo::web_api::{
+    Instance, InstanceUpdate, ReadResponse, SubscribeResponse, WriteConflictResponse,
+};
 use rojo_insta_ext::RedactionMap;
 
 /// A convenience method to store all of the redactable data from a piece of
@@ -67,6 +69,14 @@ impl Internable<()> for SubscribeResponse<'_> {
     }
 }
 
+impl Internable<()> for WriteConflictResponse {
+    fn intern(&self, redactions: &mut RedactionMap, _extra: ()) {
+        for conflict in &self.conflicts {
+            redactions.intern(conflict.id);
+        }
+    }
+}
+
 fn intern_instance_updates(redactions: &mut RedactionMap, updates: &[InstanceUpdate]) {
     for update in updates {
         redactions.intern(update.id);
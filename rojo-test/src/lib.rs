@@ -4,4 +4,6 @@ mod build_test;
 mod internable;
 mod serve_test;
 mod serve_util;
+mod sourcemap_test;
+mod syncback_test;
 mod util;
@@ -1,8 +1,12 @@
-use std::fs;
+use std::{borrow::Cow, collections::HashMap, fs, path::Path, thread, time::Duration};
 
 use insta::assert_yaml_snapshot;
+use maplit::hashmap;
+use rbx_dom_weak::{RbxId, RbxValue};
 use tempfile::tempdir;
 
+use librojo::web_api::{Instance, InstanceUpdate, SocketMessage, WriteRequest};
+
 use crate::{internable::InternAndRedact, serve_util::run_serve_test};
 
 #[test]
@@ -186,3 +190,545 @@ fn move_folder_of_stuff() {
         );
     });
 }
+
+#[test]
+fn project_include() {
+    run_serve_test("project_include", |session, mut redactions| {
+        let info = session.get_api_rojo().unwrap();
+        let root_id = info.root_instance_id;
+
+        assert_yaml_snapshot!("project_include_info", redactions.redacted_yaml(info));
+
+        let read_response = session.get_api_read(root_id).unwrap();
+        assert_yaml_snapshot!(
+            "project_include_all",
+            read_response.intern_and_redact(&mut redactions, root_id)
+        );
+
+        fs::write(
+            session.path().join("shared.project.json"),
+            r#"{
+                "name": "shared",
+                "tree": {
+                    "$className": "Folder",
+                    "Greeting": {
+                        "$className": "StringValue",
+                        "$properties": {
+                            "Value": "Goodbye"
+                        }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let subscribe_response = session.get_api_subscribe(0).unwrap();
+        assert_yaml_snapshot!(
+            "project_include_subscribe",
+            subscribe_response.intern_and_redact(&mut redactions, ())
+        );
+
+        let read_response = session.get_api_read(root_id).unwrap();
+        assert_yaml_snapshot!(
+            "project_include_all-2",
+            read_response.intern_and_redact(&mut redactions, root_id)
+        );
+    });
+}
+
+#[test]
+fn remove_tags() {
+    run_serve_test("remove_tags", |session, mut redactions| {
+        let info = session.get_api_rojo().unwrap();
+        let root_id = info.root_instance_id;
+
+        assert_yaml_snapshot!("remove_tags_info", redactions.redacted_yaml(info));
+
+        let read_response = session.get_api_read(root_id).unwrap();
+        assert_yaml_snapshot!(
+            "remove_tags_all",
+            read_response.intern_and_redact(&mut redactions, root_id)
+        );
+
+        fs::write(session.path().join("src/Greeting.meta.json"), b"{}").unwrap();
+
+        let subscribe_response = session.get_api_subscribe(0).unwrap();
+        assert_yaml_snapshot!(
+            "remove_tags_subscribe",
+            subscribe_response.intern_and_redact(&mut redactions, ())
+        );
+    });
+}
+
+#[test]
+fn add_instances() {
+    run_serve_test("add_instances", |session, mut redactions| {
+        let info = session.get_api_rojo().unwrap();
+        let root_id = info.root_instance_id;
+        let session_id = info.session_id;
+
+        assert_yaml_snapshot!("add_instances_info", redactions.redacted_yaml(info));
+
+        let read_response = session.get_api_read(root_id).unwrap();
+        assert_yaml_snapshot!(
+            "add_instances_all",
+            read_response.intern_and_redact(&mut redactions, root_id)
+        );
+
+        let folder_id = RbxId::new();
+        let module_id = RbxId::new();
+        let script_id = RbxId::new();
+
+        let source = |value: &str| {
+            Cow::Owned(hashmap! {
+                "Source".to_owned() => RbxValue::String {
+                    value: value.to_owned(),
+                },
+            })
+        };
+
+        let added = hashmap! {
+            folder_id => Instance {
+                id: folder_id,
+                parent: Some(root_id),
+                name: Cow::Borrowed("Stuff"),
+                class_name: Cow::Borrowed("Folder"),
+                properties: Cow::Owned(HashMap::new()),
+                children: Cow::Owned(vec![module_id, script_id]),
+                metadata: None,
+            },
+            module_id => Instance {
+                id: module_id,
+                parent: Some(folder_id),
+                name: Cow::Borrowed("Util"),
+                class_name: Cow::Borrowed("ModuleScript"),
+                properties: source("return 1"),
+                children: Cow::Owned(Vec::new()),
+                metadata: None,
+            },
+            script_id => Instance {
+                id: script_id,
+                parent: Some(folder_id),
+                name: Cow::Borrowed("Main"),
+                class_name: Cow::Borrowed("Script"),
+                properties: source("print(\"Hello\")"),
+                children: Cow::Owned(Vec::new()),
+                metadata: None,
+            },
+        };
+
+        session
+            .post_api_write(&WriteRequest {
+                session_id,
+                removed: Vec::new(),
+                added,
+                updated: Vec::new(),
+            })
+            .unwrap();
+
+        // The client already has the instances it added, so the server tells
+        // it which of the new instances in the tree each one became.
+        let subscribe_response = session.get_api_subscribe(0).unwrap();
+        let client_names = hashmap! {
+            folder_id => "Stuff",
+            module_id => "Util",
+            script_id => "Main",
+        };
+
+        let mut paired = 0;
+        for message in &subscribe_response.messages {
+            for (id, client_id) in &message.client_ids {
+                assert_eq!(message.added[id].name, client_names[client_id]);
+                paired += 1;
+            }
+        }
+        assert_eq!(paired, client_names.len());
+
+        let stuff_path = session.path().join("src/Stuff");
+        assert_eq!(
+            fs::read_to_string(stuff_path.join("Util.lua")).unwrap(),
+            "return 1"
+        );
+        assert_eq!(
+            fs::read_to_string(stuff_path.join("Main.server.lua")).unwrap(),
+            "print(\"Hello\")"
+        );
+
+        // Writing the new files raises filesystem events, which should line up
+        // with the instances that were just added instead of adding them again.
+        thread::sleep(Duration::from_millis(300));
+
+        let read_response = session.get_api_read(root_id).unwrap();
+        assert_yaml_snapshot!(
+            "add_instances_all-2",
+            read_response.intern_and_redact(&mut redactions, root_id)
+        );
+    });
+}
+
+#[test]
+fn rename_instances() {
+    run_serve_test("rename_instances", |session, mut redactions| {
+        let info = session.get_api_rojo().unwrap();
+        let root_id = info.root_instance_id;
+        let session_id = info.session_id;
+
+        assert_yaml_snapshot!("rename_instances_info", redactions.redacted_yaml(info));
+
+        let read_response = session.get_api_read(root_id).unwrap();
+        let find_id = |name: &str| {
+            read_response
+                .instances
+                .values()
+                .find(|instance| instance.name == name)
+                .unwrap()
+                .id
+        };
+
+        let updated = vec![
+            InstanceUpdate {
+                id: find_id("Foo"),
+                changed_name: Some("Renamed".to_owned()),
+                changed_class_name: Some("Script".to_owned()),
+                changed_properties: HashMap::new(),
+                changed_metadata: None,
+                previous_name: None,
+                previous_class_name: None,
+                previous_properties: HashMap::new(),
+            },
+            InstanceUpdate {
+                id: find_id("Bar"),
+                changed_name: None,
+                changed_class_name: Some("LocalScript".to_owned()),
+                changed_properties: HashMap::new(),
+                changed_metadata: None,
+                previous_name: None,
+                previous_class_name: None,
+                previous_properties: HashMap::new(),
+            },
+        ];
+
+        assert_yaml_snapshot!(
+            "rename_instances_all",
+            read_response.intern_and_redact(&mut redactions, root_id)
+        );
+
+        session
+            .post_api_write(&WriteRequest {
+                session_id,
+                removed: Vec::new(),
+                added: HashMap::new(),
+                updated,
+            })
+            .unwrap();
+
+        session.get_api_subscribe(0).unwrap();
+
+        let src_path = session.path().join("src");
+        assert!(!src_path.join("Foo.lua").exists());
+        assert!(!src_path.join("Foo.meta.json").exists());
+        assert!(src_path.join("Renamed.server.lua").is_file());
+        assert!(src_path.join("Renamed.meta.json").is_file());
+        assert!(src_path.join("Bar/init.client.lua").is_file());
+
+        // Renaming files raises filesystem events, which should line up with
+        // the renamed instances instead of replacing them.
+        thread::sleep(Duration::from_millis(300));
+
+        let read_response = session.get_api_read(root_id).unwrap();
+        assert_yaml_snapshot!(
+            "rename_instances_all-2",
+            read_response.intern_and_redact(&mut redactions, root_id)
+        );
+    });
+}
+
+#[test]
+fn write_properties() {
+    run_serve_test("write_properties", |session, mut redactions| {
+        let info = session.get_api_rojo().unwrap();
+        let root_id = info.root_instance_id;
+        let session_id = info.session_id;
+
+        assert_yaml_snapshot!("write_properties_info", redactions.redacted_yaml(info));
+
+        let read_response = session.get_api_read(root_id).unwrap();
+        let find_id = |name: &str| {
+            read_response
+                .instances
+                .values()
+                .find(|instance| instance.name == name)
+                .unwrap()
+                .id
+        };
+
+        let updated = vec![
+            InstanceUpdate {
+                id: find_id("Count"),
+                changed_name: None,
+                changed_class_name: None,
+                changed_properties: hashmap! {
+                    "Value".to_owned() => Some(RbxValue::Int32 { value: 5 }),
+                },
+                changed_metadata: None,
+                previous_name: None,
+                previous_class_name: None,
+                previous_properties: HashMap::new(),
+            },
+            InstanceUpdate {
+                id: find_id("Hello"),
+                changed_name: None,
+                changed_class_name: None,
+                changed_properties: hashmap! {
+                    "Disabled".to_owned() => Some(RbxValue::Bool { value: true }),
+                },
+                changed_metadata: None,
+                previous_name: None,
+                previous_class_name: None,
+                previous_properties: HashMap::new(),
+            },
+        ];
+
+        assert_yaml_snapshot!(
+            "write_properties_all",
+            read_response.intern_and_redact(&mut redactions, root_id)
+        );
+
+        session
+            .post_api_write(&WriteRequest {
+                session_id,
+                removed: Vec::new(),
+                added: HashMap::new(),
+                updated,
+            })
+            .unwrap();
+
+        session.get_api_subscribe(0).unwrap();
+
+        let meta: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(session.path().join("src/Hello.meta.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(meta["properties"]["Disabled"]["Value"], true);
+
+        let project: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(session.path().join("default.project.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(project["tree"]["Count"]["$properties"]["Value"]["Value"], 5);
+        assert_eq!(project["tree"]["Scripts"]["$path"], "src");
+
+        // Writing the files raises filesystem events, which should agree with
+        // the properties that were just changed.
+        thread::sleep(Duration::from_millis(300));
+
+        let read_response = session.get_api_read(root_id).unwrap();
+        assert_yaml_snapshot!(
+            "write_properties_all-2",
+            read_response.intern_and_redact(&mut redactions, root_id)
+        );
+    });
+}
+
+#[test]
+fn write_conflict() {
+    run_serve_test("write_conflict", |session, mut redactions| {
+        let info = session.get_api_rojo().unwrap();
+        let root_id = info.root_instance_id;
+        let session_id = info.session_id;
+
+        assert_yaml_snapshot!("write_conflict_info", redactions.redacted_yaml(info));
+
+        let read_response = session.get_api_read(root_id).unwrap();
+        let script_id = read_response
+            .instances
+            .values()
+            .find(|instance| instance.name == "Main")
+            .unwrap()
+            .id;
+
+        assert_yaml_snapshot!(
+            "write_conflict_all",
+            read_response.intern_and_redact(&mut redactions, root_id)
+        );
+
+        // Someone else edits the script after this client last saw it.
+        let script_path = session.path().join("src/Main.server.lua");
+        fs::write(&script_path, "print(\"Edited on disk\")").unwrap();
+
+        let subscribe_response = session.get_api_subscribe(0).unwrap();
+        assert_yaml_snapshot!(
+            "write_conflict_subscribe",
+            subscribe_response.intern_and_redact(&mut redactions, ())
+        );
+
+        let source = |value: &str| RbxValue::String {
+            value: value.to_owned(),
+        };
+
+        let conflict_response = session
+            .post_api_write_conflict(&WriteRequest {
+                session_id,
+                removed: Vec::new(),
+                added: HashMap::new(),
+                updated: vec![InstanceUpdate {
+                    id: script_id,
+                    changed_name: None,
+                    changed_class_name: None,
+                    changed_properties: hashmap! {
+                        "Source".to_owned() => Some(source("print(\"Edited in Studio\")")),
+                    },
+                    changed_metadata: None,
+                    previous_name: None,
+                    previous_class_name: None,
+                    previous_properties: hashmap! {
+                        "Source".to_owned() => Some(source("print(\"Hello\")\n")),
+                    },
+                }],
+            })
+            .unwrap();
+
+        assert_yaml_snapshot!(
+            "write_conflict_response",
+            conflict_response.intern_and_redact(&mut redactions, ())
+        );
+
+        assert_eq!(
+            fs::read_to_string(&script_path).unwrap(),
+            "print(\"Edited on disk\")"
+        );
+    });
+}
+
+#[test]
+fn socket() {
+    run_serve_test("socket", |session, mut redactions| {
+        let info = session.get_api_rojo().unwrap();
+        let root_id = info.root_instance_id;
+        let session_id = info.session_id;
+
+        assert_yaml_snapshot!("socket_info", redactions.redacted_yaml(info));
+
+        let read_response = session.get_api_read(root_id).unwrap();
+        let script_id = read_response
+            .instances
+            .values()
+            .find(|instance| instance.name == "Main")
+            .unwrap()
+            .id;
+
+        assert_yaml_snapshot!(
+            "socket_all",
+            read_response.intern_and_redact(&mut redactions, root_id)
+        );
+
+        let mut socket = session.open_socket(0);
+
+        // Changes on disk are pushed over the socket without polling.
+        let script_path = session.path().join("src/Main.server.lua");
+        fs::write(&script_path, "print(\"Edited on disk\")").unwrap();
+
+        let message_cursor = match socket.receive() {
+            SocketMessage::Subscribe(response) => {
+                assert_yaml_snapshot!(
+                    "socket_subscribe",
+                    response.intern_and_redact(&mut redactions, ())
+                );
+
+                response.message_cursor
+            }
+            other => panic!("Expected a Subscribe message, got {:?}", other),
+        };
+
+        // Writes can be sent over the same connection.
+        socket.send(&WriteRequest {
+            session_id,
+            removed: Vec::new(),
+            added: HashMap::new(),
+            updated: vec![InstanceUpdate {
+                id: script_id,
+                changed_name: None,
+                changed_class_name: None,
+                changed_properties: hashmap! {
+                    "Source".to_owned() => Some(RbxValue::String {
+                        value: "print(\"Edited in Studio\")".to_owned(),
+                    }),
+                },
+                changed_metadata: None,
+                previous_name: None,
+                previous_class_name: None,
+                previous_properties: HashMap::new(),
+            }],
+        });
+
+        match socket.receive() {
+            SocketMessage::Write(response) => assert_eq!(response.session_id, session_id),
+            other => panic!("Expected a Write message, got {:?}", other),
+        }
+
+        // The change made by the write comes back over the socket too.
+        match socket.receive() {
+            SocketMessage::Subscribe(response) => {
+                assert!(response.message_cursor > message_cursor);
+            }
+            other => panic!("Expected a Subscribe message, got {:?}", other),
+        }
+
+        assert_eq!(
+            fs::read_to_string(&script_path).unwrap(),
+            "print(\"Edited in Studio\")"
+        );
+    });
+}
+
+#[test]
+fn errors() {
+    run_serve_test("errors", |session, mut redactions| {
+        let info = session.get_api_rojo().unwrap();
+        let root_id = info.root_instance_id;
+
+        assert_yaml_snapshot!("errors_info", redactions.redacted_yaml(info));
+
+        let read_response = session.get_api_read(root_id).unwrap();
+        assert_yaml_snapshot!(
+            "errors_all",
+            read_response.intern_and_redact(&mut redactions, root_id)
+        );
+
+        assert!(session.get_api_errors().unwrap().errors.is_empty());
+
+        let model_path = session.path().join("src/Model.model.json");
+        fs::write(&model_path, "{ \"ClassName\": ").unwrap();
+
+        // Clients are woken up when an error shows up, even though no
+        // instances changed.
+        let subscribe_response = session.get_api_subscribe(0).unwrap();
+        assert_eq!(subscribe_response.errors.len(), 1);
+
+        let errors = session.get_api_errors().unwrap().errors;
+        assert_eq!(errors, subscribe_response.errors);
+        assert_eq!(errors[0].path, Path::new("src/Model.model.json"));
+        assert!(
+            errors[0].message.contains("malformed .model.json file"),
+            "unexpected message: {}",
+            errors[0].message
+        );
+
+        fs::write(
+            &model_path,
+            r#"{ "ClassName": "StringValue", "Properties": { "Value": "Fixed" } }"#,
+        )
+        .unwrap();
+
+        let subscribe_response = session
+            .get_api_subscribe(subscribe_response.message_cursor)
+            .unwrap();
+        assert!(subscribe_response.errors.is_empty());
+        assert_yaml_snapshot!(
+            "errors_subscribe",
+            subscribe_response.intern_and_redact(&mut redactions, ())
+        );
+
+        assert!(session.get_api_errors().unwrap().errors.is_empty());
+    });
+}
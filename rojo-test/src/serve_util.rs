@@ -1,5 +1,7 @@
 use std::{
     fs,
+    io::{Read, Write},
+    net::TcpStream,
     path::{Path, PathBuf},
     process::Command,
     sync::atomic::{AtomicUsize, Ordering},
@@ -8,10 +10,14 @@ use std::{
 };
 
 use rbx_dom_weak::RbxId;
+use websocket_base::{dataframe::DataFrame, ws::Message, OwnedMessage};
 
 use tempfile::{tempdir, TempDir};
 
-use librojo::web_api::{ReadResponse, ServerInfoResponse, SubscribeResponse};
+use librojo::web_api::{
+    ErrorsResponse, ReadResponse, ServerInfoResponse, SocketMessage, SubscribeResponse,
+    WriteConflictResponse, WriteRequest, WriteResponse,
+};
 use rojo_insta_ext::RedactionMap;
 
 use crate::util::{
@@ -151,6 +157,12 @@ impl TestServeSession {
         Ok(serde_json::from_str(&body).expect("Server returned malformed response"))
     }
 
+    pub fn get_api_errors(&self) -> Result<ErrorsResponse, reqwest::Error> {
+        let url = format!("http://localhost:{}/api/errors", self.port);
+
+        reqwest::get(&url)?.json()
+    }
+
     pub fn get_api_subscribe(
         &self,
         cursor: u32,
@@ -159,6 +171,102 @@ impl TestServeSession {
 
         reqwest::get(&url)?.json()
     }
+
+    pub fn post_api_write(&self, request: &WriteRequest) -> Result<WriteResponse, reqwest::Error> {
+        let url = format!("http://localhost:{}/api/write", self.port);
+
+        reqwest::Client::new()
+            .post(&url)
+            .json(request)
+            .send()?
+            .error_for_status()?
+            .json()
+    }
+
+    /// Sends a write that is expected to be rejected because of conflicts.
+    pub fn post_api_write_conflict(
+        &self,
+        request: &WriteRequest,
+    ) -> Result<WriteConflictResponse, reqwest::Error> {
+        let url = format!("http://localhost:{}/api/write", self.port);
+
+        let mut response = reqwest::Client::new().post(&url).json(request).send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+
+        response.json()
+    }
+
+    /// Opens a WebSocket connection that streams every message past the given
+    /// cursor.
+    pub fn open_socket(&self, cursor: u32) -> TestSocket {
+        let mut stream =
+            TcpStream::connect(("localhost", self.port as u16)).expect("Couldn't connect to Rojo");
+
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+
+        write!(
+            stream,
+            "GET /api/subscribe/{} HTTP/1.1\r\n\
+             Host: localhost:{}\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+             Sec-WebSocket-Version: 13\r\n\r\n",
+            cursor, self.port
+        )
+        .unwrap();
+
+        // Read the handshake response one byte at a time so that none of the
+        // WebSocket frames following it get swallowed.
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            let mut byte = [0];
+            stream.read_exact(&mut byte).expect("Handshake failed");
+            head.push(byte[0]);
+        }
+
+        let head = String::from_utf8(head).unwrap();
+        assert!(
+            head.starts_with("HTTP/1.1 101"),
+            "Unexpected handshake response: {}",
+            head
+        );
+
+        TestSocket { stream }
+    }
+}
+
+/// A minimal blocking WebSocket client for talking to a `rojo serve` session.
+pub struct TestSocket {
+    stream: TcpStream,
+}
+
+impl TestSocket {
+    pub fn send(&mut self, request: &WriteRequest) {
+        let text = serde_json::to_string(request).unwrap();
+
+        OwnedMessage::Text(text)
+            .serialize(&mut self.stream, true)
+            .expect("Couldn't send WebSocket message");
+    }
+
+    /// Blocks until the next message from the server arrives.
+    pub fn receive(&mut self) -> SocketMessage<'static> {
+        loop {
+            let frame = DataFrame::read_dataframe(&mut self.stream, false)
+                .expect("Couldn't read WebSocket message");
+
+            match OwnedMessage::from_dataframes(vec![frame]).unwrap() {
+                OwnedMessage::Text(text) => {
+                    return serde_json::from_str(&text).expect("Server sent malformed message");
+                }
+                OwnedMessage::Ping(_) | OwnedMessage::Pong(_) => continue,
+                other => panic!("Unexpected WebSocket message: {:?}", other),
+            }
+        }
+    }
 }
 
 /// Probably-okay way to generate random enough port numbers for running the
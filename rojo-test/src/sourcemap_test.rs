@@ -0,0 +1,65 @@
+use std::{fs, path::Path, process::Command};
+
+use insta::assert_snapshot;
+use tempfile::tempdir;
+
+use crate::util::{get_rojo_path, get_sourcemap_tests_path, get_working_dir_path};
+
+macro_rules! gen_sourcemap_tests {
+    ( $($test_name: ident,)* ) => {
+        $(
+            paste::item! {
+                #[test]
+                fn [<sourcemap_ $test_name>]() {
+                    let _ = env_logger::try_init();
+
+                    run_sourcemap_test(stringify!($test_name));
+                }
+            }
+        )*
+    };
+}
+
+gen_sourcemap_tests! {
+    basic,
+}
+
+fn run_sourcemap_test(test_name: &str) {
+    let input_path = get_sourcemap_tests_path().join(test_name);
+
+    let output_dir = tempdir().expect("couldn't create temporary directory");
+    let output_path = output_dir.path().join("sourcemap.json");
+
+    let status = Command::new(get_rojo_path())
+        .args(&[
+            "sourcemap",
+            input_path.to_str().unwrap(),
+            "-o",
+            output_path.to_str().unwrap(),
+        ])
+        .env("RUST_LOG", "error")
+        .current_dir(get_working_dir_path())
+        .status()
+        .expect("Couldn't start Rojo");
+
+    assert!(status.success(), "Rojo did not exit successfully");
+
+    let contents = fs::read_to_string(&output_path).expect("Couldn't read output file");
+    let sourcemap: serde_json::Value =
+        serde_json::from_str(&contents).expect("Sourcemap was not valid JSON");
+
+    // Paths in the sourcemap use the platform's separator, so they're made to
+    // match before comparing them with the snapshot.
+    let pretty = serde_json::to_string_pretty(&sourcemap)
+        .unwrap()
+        .replace("\\\\", "/");
+
+    let mut settings = insta::Settings::new();
+
+    let snapshot_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("sourcemap-test-snapshots");
+    settings.set_snapshot_path(snapshot_path);
+
+    settings.bind(|| {
+        assert_snapshot!(test_name, pretty);
+    });
+}
@@ -0,0 +1,151 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::BufReader,
+    path::Path,
+    process::Command,
+};
+
+use rbx_dom_weak::{RbxInstance, RbxTree, RbxValue};
+use rbx_reflection::get_class_descriptor;
+use tempfile::tempdir;
+
+use crate::util::{get_rojo_path, get_syncback_tests_path, get_working_dir_path};
+
+macro_rules! gen_syncback_tests {
+    ( $($test_name: ident,)* ) => {
+        $(
+            paste::item! {
+                #[test]
+                fn [<syncback_ $test_name>]() {
+                    let _ = env_logger::try_init();
+
+                    run_syncback_test(stringify!($test_name));
+                }
+            }
+        )*
+    };
+}
+
+gen_syncback_tests! {
+    basic_place,
+    unrepresentable_names,
+}
+
+/// Converts the test's input place into a project with `rojo syncback`, builds
+/// that project with `rojo build`, and checks that the place that comes out
+/// matches the one that went in.
+fn run_syncback_test(test_name: &str) {
+    let input_path = get_syncback_tests_path()
+        .join(test_name)
+        .join("input.rbxlx");
+
+    let output_dir = tempdir().expect("couldn't create temporary directory");
+    let project_path = output_dir.path().join("project");
+    let output_path = output_dir.path().join("output.rbxlx");
+
+    run_rojo(&[
+        "syncback",
+        input_path.to_str().unwrap(),
+        "--output",
+        project_path.to_str().unwrap(),
+    ]);
+
+    run_rojo(&[
+        "build",
+        project_path.to_str().unwrap(),
+        "-o",
+        output_path.to_str().unwrap(),
+    ]);
+
+    let input = read_place(&input_path);
+    let output = read_place(&output_path);
+
+    assert_eq!(normalize_place(&output), normalize_place(&input));
+}
+
+fn run_rojo(args: &[&str]) {
+    let status = Command::new(get_rojo_path())
+        .args(args)
+        .env("RUST_LOG", "error")
+        .current_dir(get_working_dir_path())
+        .status()
+        .expect("Couldn't start Rojo");
+
+    assert!(status.success(), "Rojo did not exit successfully");
+}
+
+fn read_place(path: &Path) -> RbxTree {
+    let file = BufReader::new(File::open(path).expect("Couldn't open place file"));
+    let options = rbx_xml::DecodeOptions::new()
+        .property_behavior(rbx_xml::DecodePropertyBehavior::ReadUnknown);
+
+    rbx_xml::from_reader(file, options).expect("Couldn't decode place file")
+}
+
+/// A view of an instance that ignores properties set to their default value
+/// and the order of children, neither of which syncback preserves.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct NormalInstance {
+    name: String,
+    class_name: String,
+    properties: BTreeMap<String, String>,
+    children: BTreeSet<NormalInstance>,
+}
+
+fn normalize_place(tree: &RbxTree) -> BTreeSet<NormalInstance> {
+    let root = tree.get_instance(tree.get_root_id()).unwrap();
+
+    root.get_children_ids()
+        .iter()
+        .map(|&id| {
+            let mut service = normalize_instance(tree, tree.get_instance(id).unwrap());
+
+            // Project files can't describe Ref properties on services, like
+            // Workspace.CurrentCamera, so syncback leaves them out.
+            service
+                .properties
+                .retain(|_, value| !value.starts_with("Ref("));
+
+            service
+        })
+        .collect()
+}
+
+fn normalize_instance(tree: &RbxTree, instance: &RbxInstance) -> NormalInstance {
+    let descriptor = get_class_descriptor(&instance.class_name);
+
+    let properties = instance
+        .properties
+        .iter()
+        .filter(|(key, value)| {
+            let default = descriptor.and_then(|descriptor| descriptor.get_default_value(key));
+            default != Some(value)
+        })
+        .map(|(key, value)| {
+            // Referents are different between files, so only the name of the
+            // instance being pointed to is compared.
+            let value = match value {
+                RbxValue::Ref { value: Some(id) } => {
+                    format!("Ref({})", tree.get_instance(*id).unwrap().name)
+                }
+                _ => format!("{:?}", value),
+            };
+
+            (key.clone(), value)
+        })
+        .collect();
+
+    let children = instance
+        .get_children_ids()
+        .iter()
+        .map(|&id| normalize_instance(tree, tree.get_instance(id).unwrap()))
+        .collect();
+
+    NormalInstance {
+        name: instance.name.clone(),
+        class_name: instance.class_name.clone(),
+        properties,
+        children,
+    }
+}
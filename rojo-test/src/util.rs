@@ -36,6 +36,16 @@ pub fn get_serve_tests_path() -> PathBuf {
     manifest_dir.join("serve-tests")
 }
 
+pub fn get_sourcemap_tests_path() -> PathBuf {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    manifest_dir.join("sourcemap-tests")
+}
+
+pub fn get_syncback_tests_path() -> PathBuf {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    manifest_dir.join("syncback-tests")
+}
+
 /// Recursively walk a directory and copy each item to the equivalent location
 /// in another directory. Equivalent to `cp -r src/* dst`
 pub fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
@@ -1,5 +1,7 @@
 use std::{
-    fs,
+    collections::HashMap,
+    mem,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
 
@@ -10,11 +12,19 @@ use rbx_dom_weak::{RbxId, RbxValue};
 
 use crate::{
     error::ErrorDisplay,
+    error_registry::ErrorRegistry,
     message_queue::MessageQueue,
+    project::Project,
     snapshot::{
-        apply_patch_set, compute_patch_set, AppliedPatchSet, InstigatingSource, PatchSet, RojoTree,
+        apply_patch_set, compute_patch_set, find_update_conflicts, AppliedPatchSet,
+        InstanceSnapshot, InstanceWithMeta, InstigatingSource, PatchAdd, PatchSet, PatchUpdate,
+        RojoTree,
     },
-    snapshot_middleware::{snapshot_from_vfs, snapshot_project_node},
+    snapshot_middleware::{
+        find_init_file, rule_for_path, snapshot_from_vfs, snapshot_project_node, suffix_for_class,
+    },
+    syncback::{is_valid_file_name, syncback_snapshot, write_snapshot},
+    write_back::write_properties,
 };
 
 /// Owns the connection between Rojo's VFS and its DOM by holding onto another
@@ -48,6 +58,7 @@ impl ChangeProcessor {
         tree: Arc<Mutex<RojoTree>>,
         vfs: Arc<Vfs>,
         message_queue: Arc<MessageQueue<AppliedPatchSet>>,
+        errors: Arc<ErrorRegistry>,
         tree_mutation_receiver: Receiver<PatchSet>,
     ) -> Self {
         let (shutdown_sender, shutdown_receiver) = crossbeam_channel::bounded(1);
@@ -56,6 +67,7 @@ impl ChangeProcessor {
             tree,
             vfs,
             message_queue,
+            errors,
         };
 
         let job_thread = jod_thread::Builder::new()
@@ -109,6 +121,10 @@ struct JobThreadContext {
     /// Whenever changes are applied to the DOM, we should push those changes
     /// into this message queue to inform any connected clients.
     message_queue: Arc<MessageQueue<AppliedPatchSet>>,
+
+    /// Errors from snapshotting files are recorded here so that clients can
+    /// show them.
+    errors: Arc<ErrorRegistry>,
 }
 
 impl JobThreadContext {
@@ -122,7 +138,8 @@ impl JobThreadContext {
 
         // For a given VFS event, we might have many changes to different parts
         // of the tree. Calculate and apply all of these changes.
-        let applied_patches = {
+        let errors_before = self.errors.errors();
+        let mut applied_patches = {
             let mut tree = self.tree.lock().unwrap();
             let mut applied_patches = Vec::new();
 
@@ -151,7 +168,9 @@ impl JobThreadContext {
                     };
 
                     for id in affected_ids {
-                        if let Some(patch) = compute_and_apply_changes(&mut tree, &self.vfs, id) {
+                        if let Some(patch) =
+                            compute_and_apply_changes(&mut tree, &self.vfs, &self.errors, id)
+                        {
                             applied_patches.push(patch);
                         }
                     }
@@ -162,22 +181,80 @@ impl JobThreadContext {
             applied_patches
         };
 
+        // Clients find out about errors along with other changes, so when
+        // only errors changed they still need a message to wake them up.
+        if applied_patches.is_empty() && self.errors.errors() != errors_before {
+            applied_patches.push(AppliedPatchSet::new());
+        }
+
         // Notify anyone listening to the message queue about the changes we
         // just made.
         self.message_queue.push_messages(&applied_patches);
     }
 
-    fn handle_tree_event(&self, patch_set: PatchSet) {
+    fn handle_tree_event(&self, mut patch_set: PatchSet) {
         log::trace!("Applying PatchSet from client: {:#?}", patch_set);
 
         let applied_patch = {
             let mut tree = self.tree.lock().unwrap();
 
+            // The tree might have changed since the client's write was checked
+            // for conflicts, so updates are checked again before any of them
+            // are written to disk.
+            patch_set.updated_instances.retain(|update| {
+                let conflicts = find_update_conflicts(&tree, update);
+
+                for conflict in &conflicts {
+                    log::warn!(
+                        "Ignoring change to instance {}, its {} was changed by someone else.",
+                        conflict.id,
+                        conflict.field
+                    );
+                }
+
+                conflicts.is_empty()
+            });
+
+            // Instances added by the client are written to disk first. The
+            // instances we add to the tree are then snapshotted from those new
+            // files, which means that the VFS events caused by writing them
+            // will find matching instances instead of creating duplicates.
+            //
+            // The client already has the instances it added, so we remember
+            // which ID it picked for each one, in the same order that applying
+            // the patch will add them to the tree.
+            let mut client_ids = Vec::new();
+
+            for add in mem::take(&mut patch_set.added_instances) {
+                if let Some(instance) = self.write_added_instance(&tree, &add) {
+                    pair_client_ids(&instance, Some(&add.instance), &mut client_ids);
+
+                    patch_set.added_instances.push(PatchAdd {
+                        parent_id: add.parent_id,
+                        instance,
+                    });
+                }
+            }
+
             for &id in &patch_set.removed_instances {
                 if let Some(instance) = tree.get_instance(id) {
                     if let Some(instigating_source) = &instance.metadata().instigating_source {
                         match instigating_source {
-                            InstigatingSource::Path(path) => fs::remove_file(path).unwrap(),
+                            InstigatingSource::Path(path) => {
+                                let result = match self.vfs.metadata(path) {
+                                    Ok(meta) if meta.is_dir() => self.vfs.remove_dir_all(path),
+                                    Ok(_) => self.vfs.remove_file(path),
+                                    Err(err) => Err(err),
+                                };
+
+                                if let Err(err) = result {
+                                    log::error!(
+                                        "Cannot remove {}: {}",
+                                        path.display(),
+                                        ErrorDisplay(err)
+                                    );
+                                }
+                            }
                             InstigatingSource::ProjectNode(_, _, _, _) => {
                                 log::warn!(
                                     "Cannot remove instance {}, it's from a project file",
@@ -197,50 +274,64 @@ impl JobThreadContext {
                 }
             }
 
-            for update in &patch_set.updated_instances {
+            for update in &mut patch_set.updated_instances {
                 let id = update.id;
 
-                if let Some(instance) = tree.get_instance(id) {
-                    if update.changed_name.is_some() {
-                        log::warn!("Cannot rename instances yet.");
-                    }
+                if update.changed_name.is_some() || update.changed_class_name.is_some() {
+                    // The instance keeps its old name and ClassName if its
+                    // files weren't renamed, so that it still matches them.
+                    if let Err(message) = self.rename_instance(&mut tree, update) {
+                        log::warn!("Cannot rename instance {}, {}", id, message);
 
-                    if update.changed_class_name.is_some() {
-                        log::warn!("Cannot change ClassName yet.");
+                        update.changed_name = None;
+                        update.changed_class_name = None;
                     }
+                }
 
+                if let Some(instance) = tree.get_instance(id) {
                     if update.changed_metadata.is_some() {
                         log::warn!("Cannot change metadata yet.");
                     }
 
+                    let mut changed_properties = HashMap::new();
+                    let contents_file = self.contents_file(instance);
+
                     for (key, changed_value) in &update.changed_properties {
-                        if key == "Source" {
-                            if let Some(instigating_source) =
-                                &instance.metadata().instigating_source
-                            {
-                                match instigating_source {
-                                    InstigatingSource::Path(path) => {
-                                        if let Some(RbxValue::String { value }) = changed_value {
-                                            fs::write(path, value).unwrap();
-                                        } else {
-                                            log::warn!("Cannot change Source to non-string value.");
-                                        }
-                                    }
-                                    InstigatingSource::ProjectNode(_, _, _, _) => {
-                                        log::warn!(
-                                            "Cannot remove instance {}, it's from a project file",
-                                            id
+                        match &contents_file {
+                            Some((path, property)) if key == property => {
+                                if let Some(RbxValue::String { value }) = changed_value {
+                                    if let Err(err) = self.vfs.write(path, value) {
+                                        log::error!(
+                                            "Cannot write {}: {}",
+                                            path.display(),
+                                            ErrorDisplay(err)
                                         );
                                     }
+                                } else {
+                                    log::warn!("Cannot change {} to non-string value.", key);
                                 }
-                            } else {
+                            }
+                            _ if key == "Source" => {
                                 log::warn!(
-                                    "Cannot update instance {}, it is not an instigating source.",
+                                    "Cannot change Source of instance {}, it doesn't come from a script file.",
                                     id
                                 );
                             }
-                        } else {
-                            log::warn!("Cannot change properties besides BaseScript.Source.");
+                            _ => {
+                                changed_properties.insert(key.clone(), changed_value.clone());
+                            }
+                        }
+                    }
+
+                    if !changed_properties.is_empty() {
+                        if let Err(err) =
+                            write_properties(&self.vfs, &tree, id, &changed_properties)
+                        {
+                            log::warn!(
+                                "Cannot change properties of instance {}, {}",
+                                id,
+                                ErrorDisplay(err)
+                            );
                         }
                     }
                 } else {
@@ -248,14 +339,436 @@ impl JobThreadContext {
                 }
             }
 
-            apply_patch_set(&mut tree, patch_set)
+            let mut applied_patch = apply_patch_set(&mut tree, patch_set);
+
+            // Removals don't add anything, so the instances added to the tree
+            // line up with the client IDs collected above.
+            applied_patch.client_ids = applied_patch
+                .added
+                .iter()
+                .zip(client_ids)
+                .filter_map(|(&id, client_id)| Some((id, client_id?)))
+                .collect();
+
+            applied_patch
         };
 
         self.message_queue.push_messages(&[applied_patch]);
     }
+
+    /// Creates files for an instance that a client added to the tree, then
+    /// returns a snapshot of the instance read back from those files.
+    fn write_added_instance(&self, tree: &RojoTree, add: &PatchAdd) -> Option<InstanceSnapshot> {
+        let name = &add.instance.name;
+
+        let parent = match tree.get_instance(add.parent_id) {
+            Some(parent) => parent,
+            None => {
+                log::warn!(
+                    "Cannot add instance {}, its parent {} does not exist.",
+                    name,
+                    add.parent_id
+                );
+                return None;
+            }
+        };
+
+        let parent_path = match self.directory_for_instance(parent) {
+            Some(path) => path,
+            None => {
+                log::warn!(
+                    "Cannot add instance {}, its parent is not backed by a directory.",
+                    name
+                );
+                return None;
+            }
+        };
+
+        if !is_valid_file_name(name) {
+            log::warn!(
+                "Cannot add instance {}, its name can't be used as a file name.",
+                name
+            );
+            return None;
+        }
+
+        let files = match syncback_snapshot(&add.instance) {
+            Ok(files) => files,
+            Err(err) => {
+                log::error!("Cannot add instance {}: {}", name, ErrorDisplay(err));
+                return None;
+            }
+        };
+
+        // Existing files are never overwritten, since they might belong to
+        // another instance or hold changes that aren't in the tree yet.
+        for file_name in files.keys() {
+            match self
+                .vfs
+                .metadata(parent_path.join(file_name))
+                .with_not_found()
+            {
+                Ok(None) => {}
+                Ok(Some(_)) => {
+                    log::warn!(
+                        "Cannot add instance {}, {} already exists.",
+                        name,
+                        parent_path.join(file_name).display()
+                    );
+                    return None;
+                }
+                Err(err) => {
+                    log::error!("Cannot add instance {}: {}", name, ErrorDisplay(err));
+                    return None;
+                }
+            }
+        }
+
+        for (file_name, file) in &files {
+            if let Err(err) = write_snapshot(&self.vfs, &parent_path.join(file_name), file) {
+                log::error!("Cannot add instance {}: {}", name, ErrorDisplay(err));
+                return None;
+            }
+        }
+
+        // Syncback creates one file or directory for the instance itself,
+        // possibly alongside a .meta.json file.
+        let instance_path = files
+            .keys()
+            .find(|file_name| !file_name.ends_with(".meta.json"))
+            .map(|file_name| parent_path.join(file_name))?;
+
+        match snapshot_from_vfs(&parent.metadata().context, &self.vfs, &instance_path) {
+            Ok(Some(snapshot)) => Some(snapshot),
+            Ok(None) => {
+                log::error!(
+                    "Snapshot did not return an instance from path {}",
+                    instance_path.display()
+                );
+                log::error!("This may be a bug!");
+                None
+            }
+            Err(err) => {
+                log::error!("Snapshot error: {}", ErrorDisplay(err));
+                None
+            }
+        }
+    }
+
+    /// Renames the files backing an instance to match a new name or ClassName
+    /// picked by a client. The instance and its descendants are pointed at the
+    /// renamed files so that the VFS events caused by the rename line up with
+    /// the existing instances.
+    ///
+    /// If any of the files can't be renamed, the ones that were already
+    /// renamed are moved back and nothing about the instance changes.
+    fn rename_instance(&self, tree: &mut RojoTree, update: &PatchUpdate) -> Result<(), String> {
+        let id = update.id;
+
+        let instance = tree.get_instance(id).ok_or("it does not exist.")?;
+
+        let path = match &instance.metadata().instigating_source {
+            Some(InstigatingSource::Path(path)) => path.clone(),
+            Some(InstigatingSource::ProjectNode(_, _, _, _)) => {
+                return Err("it's from a project file.".to_owned());
+            }
+            None => return Err("it is not an instigating source.".to_owned()),
+        };
+
+        let renames = self.renames_for_instance(instance, &path, update)?;
+
+        for (from, to) in &renames {
+            match self.vfs.metadata(to).with_not_found() {
+                Ok(None) => {}
+                Ok(Some(_)) => return Err(format!("{} already exists.", to.display())),
+                Err(err) => {
+                    return Err(format!(
+                        "can't rename {}: {}",
+                        from.display(),
+                        ErrorDisplay(err)
+                    ))
+                }
+            }
+        }
+
+        rename_all(&self.vfs, &renames)?;
+
+        let ids: Vec<RbxId> = std::iter::once(id)
+            .chain(tree.descendants(id).map(|descendant| descendant.id()))
+            .collect();
+
+        for id in ids {
+            let mut metadata = tree.get_metadata(id).unwrap().clone();
+
+            if let Some(InstigatingSource::Path(path)) = &mut metadata.instigating_source {
+                *path = renamed_path(path, &renames);
+            }
+
+            for path in &mut metadata.relevant_paths {
+                *path = renamed_path(path, &renames);
+            }
+
+            tree.update_metadata(id, metadata);
+        }
+
+        Ok(())
+    }
+
+    /// Figures out which files need to be renamed, in order, so that the
+    /// instance created from the given path gets its new name and ClassName.
+    fn renames_for_instance(
+        &self,
+        instance: InstanceWithMeta<'_>,
+        path: &Path,
+        update: &PatchUpdate,
+    ) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+        let old_name = instance.name();
+        let new_name = update.changed_name.as_deref().unwrap_or(old_name);
+
+        let old_class_name = instance.class_name();
+        let new_class_name = update
+            .changed_class_name
+            .as_deref()
+            .unwrap_or(old_class_name);
+
+        if !is_valid_file_name(new_name) {
+            return Err(format!("{} can't be used as a file name.", new_name));
+        }
+
+        let parent_path = path.parent().ok_or("it has no parent directory.")?;
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or("its file name is not valid Unicode.")?;
+
+        let suffix = match file_name.get(..old_name.len()) {
+            Some(prefix) if prefix == old_name => &file_name[old_name.len()..],
+            _ => return Err("its file name doesn't match its name.".to_owned()),
+        };
+
+        if !suffix.is_empty() && !suffix.starts_with('.') {
+            return Err("its file name doesn't match its name.".to_owned());
+        }
+
+        let is_dir = self
+            .vfs
+            .metadata(path)
+            .map_err(|err| err.to_string())?
+            .is_dir();
+
+        // Instances made from file rules, like scripts, get their ClassName
+        // from how the name of their file ends. Instances backed by a
+        // directory get it from the init file inside of it instead.
+        let class_change = if old_class_name == new_class_name {
+            None
+        } else {
+            let context = &instance.metadata().context;
+            let not_from_rule = "only instances made from file rules can change ClassName.";
+
+            let class_path = if is_dir {
+                find_init_file(context, &self.vfs, path)
+                    .map_err(|err| err.to_string())?
+                    .ok_or(not_from_rule)?
+            } else {
+                path.to_path_buf()
+            };
+
+            let rule = rule_for_path(context, &class_path)
+                .filter(|rule| rule.class_name == old_class_name)
+                .ok_or(not_from_rule)?;
+
+            let new_suffix =
+                suffix_for_class(context, rule.suffix(), new_class_name).ok_or_else(|| {
+                    format!(
+                        "no file rule turns files ending in {} into {}.",
+                        rule.suffix(),
+                        new_class_name
+                    )
+                })?;
+
+            Some((class_path, new_suffix))
+        };
+
+        let mut renames = Vec::new();
+
+        if is_dir {
+            if let Some((init_path, new_suffix)) = class_change {
+                let new_init_path = path.join(format!("init{}", new_suffix));
+                renames.push((init_path, new_init_path));
+            }
+
+            if new_name != old_name {
+                renames.push((path.to_path_buf(), parent_path.join(new_name)));
+            }
+        } else {
+            let new_suffix = match class_change {
+                Some((_, new_suffix)) => new_suffix,
+                None => suffix,
+            };
+
+            renames.push((
+                path.to_path_buf(),
+                parent_path.join(format!("{}{}", new_name, new_suffix)),
+            ));
+
+            let meta_path = parent_path.join(format!("{}.meta.json", old_name));
+
+            if new_name != old_name
+                && self
+                    .vfs
+                    .metadata(&meta_path)
+                    .with_not_found()
+                    .map_err(|err| err.to_string())?
+                    .is_some()
+            {
+                renames.push((
+                    meta_path,
+                    parent_path.join(format!("{}.meta.json", new_name)),
+                ));
+            }
+        }
+
+        renames.retain(|(from, to)| from != to);
+
+        Ok(renames)
+    }
+
+    /// Finds the file holding the contents of an instance made from a file
+    /// rule, like the Source of a script, along with the property that holds
+    /// them. Instances backed by a directory keep their contents in the init
+    /// file inside of it.
+    fn contents_file(&self, instance: InstanceWithMeta<'_>) -> Option<(PathBuf, &'static str)> {
+        let path = match &instance.metadata().instigating_source {
+            Some(InstigatingSource::Path(path)) => path,
+            _ => return None,
+        };
+
+        let context = &instance.metadata().context;
+
+        let path = if self.vfs.metadata(path).ok()?.is_dir() {
+            find_init_file(context, &self.vfs, path).ok()??
+        } else {
+            path.clone()
+        };
+
+        let rule = rule_for_path(context, &path)?;
+
+        if rule.class_name != instance.class_name() {
+            return None;
+        }
+
+        Some((path, rule.content_property()?))
+    }
+
+    /// Finds the directory on disk that the children of the given instance
+    /// live in, if there is one.
+    fn directory_for_instance(&self, instance: InstanceWithMeta<'_>) -> Option<PathBuf> {
+        let path = match instance.metadata().instigating_source.as_ref()? {
+            // The root instance of a project is sourced from the project file
+            // itself, so its children live wherever its root node points.
+            InstigatingSource::Path(path) if Project::is_project_file(path) => {
+                let contents = self.vfs.read(path).ok()?;
+                let mut project = Project::load_from_slice(&contents, path).ok()?;
+
+                if let Some(profile) = &instance.metadata().context.profile {
+                    project.apply_profile(profile);
+                }
+
+                project.folder_location().join(project.tree.path.as_ref()?)
+            }
+            InstigatingSource::Path(path) => path.clone(),
+            InstigatingSource::ProjectNode(project_path, _, node, _) => {
+                project_path.parent()?.join(node.path.as_ref()?)
+            }
+        };
+
+        match self.vfs.metadata(&path) {
+            Ok(meta) if meta.is_dir() => Some(path),
+            _ => None,
+        }
+    }
+}
+
+/// Applies a list of renames to a path that might be inside of one of the
+/// renamed files or directories.
+fn renamed_path(path: &Path, renames: &[(PathBuf, PathBuf)]) -> PathBuf {
+    let mut path = path.to_path_buf();
+
+    for (from, to) in renames {
+        if let Ok(rest) = path.strip_prefix(from) {
+            path = if rest.as_os_str().is_empty() {
+                to.clone()
+            } else {
+                to.join(rest)
+            };
+        }
+    }
+
+    path
+}
+
+/// Renames files in order. If one of them can't be renamed, the files that
+/// were already renamed are moved back, so that an instance is never left
+/// split between its old and new files.
+fn rename_all(vfs: &Vfs, renames: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+    for (index, (from, to)) in renames.iter().enumerate() {
+        log::trace!("Renaming {} to {}", from.display(), to.display());
+
+        if let Err(err) = vfs.rename(from, to) {
+            for (from, to) in renames[..index].iter().rev() {
+                if let Err(err) = vfs.rename(to, from) {
+                    log::error!(
+                        "Cannot move {} back to {}: {}",
+                        to.display(),
+                        from.display(),
+                        ErrorDisplay(err)
+                    );
+                }
+            }
+
+            return Err(format!(
+                "can't rename {}: {}",
+                from.display(),
+                ErrorDisplay(err)
+            ));
+        }
+    }
+
+    Ok(())
 }
 
-fn compute_and_apply_changes(tree: &mut RojoTree, vfs: &Vfs, id: RbxId) -> Option<AppliedPatchSet> {
+/// Walks a snapshot read back from files written for a client's instance in
+/// the order that applying it will add instances to the tree, collecting the
+/// ID the client picked for each instance. Instances are matched up with the
+/// client's by name and ClassName, like hydrating the plugin does.
+fn pair_client_ids(
+    written: &InstanceSnapshot,
+    client: Option<&InstanceSnapshot>,
+    client_ids: &mut Vec<Option<RbxId>>,
+) {
+    client_ids.push(client.and_then(|client| client.snapshot_id));
+
+    let mut unpaired: Vec<_> = match client {
+        Some(client) => client.children.iter().collect(),
+        None => Vec::new(),
+    };
+
+    for child in &written.children {
+        let index = unpaired.iter().position(|client_child| {
+            client_child.name == child.name && client_child.class_name == child.class_name
+        });
+        let client_child = index.map(|index| unpaired.remove(index));
+
+        pair_client_ids(child, client_child, client_ids);
+    }
+}
+
+fn compute_and_apply_changes(
+    tree: &mut RojoTree,
+    vfs: &Vfs,
+    errors: &ErrorRegistry,
+    id: RbxId,
+) -> Option<AppliedPatchSet> {
     let metadata = tree
         .get_metadata(id)
         .expect("metadata missing for instance present in tree");
@@ -292,11 +805,14 @@ fn compute_and_apply_changes(tree: &mut RojoTree, vfs: &Vfs, id: RbxId) -> Optio
                         return None;
                     }
                     Err(err) => {
+                        errors.insert(path.as_path(), &err);
                         log::error!("Snapshot error: {}", ErrorDisplay(err));
                         return None;
                     }
                 };
 
+                errors.remove(path);
+
                 let patch_set = compute_patch_set(&snapshot, &tree, id);
                 apply_patch_set(tree, patch_set)
             }
@@ -307,12 +823,15 @@ fn compute_and_apply_changes(tree: &mut RojoTree, vfs: &Vfs, id: RbxId) -> Optio
                 // We associate deleting the instigating file for an
                 // instance with deleting that instance.
 
+                errors.remove(path);
+
                 let mut patch_set = PatchSet::new();
                 patch_set.removed_instances.push(id);
 
                 apply_patch_set(tree, patch_set)
             }
             Err(err) => {
+                errors.insert(path.as_path(), &err);
                 log::error!("Error processing filesystem change: {}", ErrorDisplay(err));
                 return None;
             }
@@ -323,6 +842,13 @@ fn compute_and_apply_changes(tree: &mut RojoTree, vfs: &Vfs, id: RbxId) -> Optio
             // there might be information associated with our instance from
             // the project file, we snapshot the entire project node again.
 
+            // Errors are reported for the path the node points to, falling
+            // back to the project file for nodes without one.
+            let error_path = match &project_node.path {
+                Some(path) => project_path.parent().unwrap().join(path),
+                None => project_path.clone(),
+            };
+
             let snapshot_result = snapshot_project_node(
                 &metadata.context,
                 &project_path,
@@ -335,16 +861,24 @@ fn compute_and_apply_changes(tree: &mut RojoTree, vfs: &Vfs, id: RbxId) -> Optio
             let snapshot = match snapshot_result {
                 Ok(Some(snapshot)) => snapshot,
                 Ok(None) => {
-                    log::error!("Snapshot did not return an instance from a project node.");
-                    log::error!("This is a bug!");
-                    return None;
+                    // The node was turned off with $enabled, which can happen
+                    // when a project that it includes changes.
+                    errors.remove(&error_path);
+
+                    let mut patch_set = PatchSet::new();
+                    patch_set.removed_instances.push(id);
+
+                    return Some(apply_patch_set(tree, patch_set));
                 }
                 Err(err) => {
+                    errors.insert(error_path, &err);
                     log::error!("{}", ErrorDisplay(err));
                     return None;
                 }
             };
 
+            errors.remove(&error_path);
+
             let patch_set = compute_patch_set(&snapshot, &tree, id);
             apply_patch_set(tree, patch_set)
         }
@@ -352,3 +886,325 @@ fn compute_and_apply_changes(tree: &mut RojoTree, vfs: &Vfs, id: RbxId) -> Optio
 
     Some(applied_patch_set)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use maplit::hashmap;
+    use memofs::{InMemoryFs, VfsSnapshot};
+    use rbx_dom_weak::RbxInstanceProperties;
+
+    use crate::{
+        snapshot::{InstanceContext, InstancePropertiesWithMeta},
+        snapshot_middleware::FileRule,
+    };
+
+    fn context_for(vfs: Vfs, path: &Path) -> JobThreadContext {
+        context_with(vfs, path, &InstanceContext::default())
+    }
+
+    fn context_with(vfs: Vfs, path: &Path, instance_context: &InstanceContext) -> JobThreadContext {
+        let mut tree = RojoTree::new(InstancePropertiesWithMeta {
+            properties: RbxInstanceProperties {
+                name: "ROOT".to_owned(),
+                class_name: "Folder".to_owned(),
+                properties: Default::default(),
+            },
+            metadata: Default::default(),
+        });
+
+        let snapshot = snapshot_from_vfs(instance_context, &vfs, path)
+            .unwrap()
+            .unwrap();
+
+        let patch_set = compute_patch_set(&snapshot, &tree, tree.get_root_id());
+        apply_patch_set(&mut tree, patch_set);
+
+        JobThreadContext {
+            tree: Arc::new(Mutex::new(tree)),
+            vfs: Arc::new(vfs),
+            message_queue: Arc::new(MessageQueue::new()),
+            errors: Arc::new(ErrorRegistry::new()),
+        }
+    }
+
+    fn luau_context() -> InstanceContext {
+        let project_path = Path::new("/root/default.project.json");
+        let mut context = InstanceContext::default();
+
+        context.add_file_rules(vec![
+            FileRule::from_project("*.luau", "ModuleScript", project_path).unwrap(),
+            FileRule::from_project("*.server.luau", "Script", project_path).unwrap(),
+        ]);
+
+        context
+    }
+
+    fn find_child(tree: &RojoTree, name: &str) -> RbxId {
+        let root_id = tree.get_root_id();
+
+        tree.get_instance(root_id)
+            .unwrap()
+            .children()
+            .iter()
+            .copied()
+            .find(|&id| tree.get_instance(id).unwrap().name() == name)
+            .unwrap()
+    }
+
+    fn update_for(id: RbxId) -> PatchUpdate {
+        PatchUpdate {
+            id,
+            changed_name: None,
+            changed_class_name: None,
+            changed_properties: HashMap::new(),
+            changed_metadata: None,
+            previous_name: None,
+            previous_class_name: None,
+            previous_properties: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn class_change_follows_file_rules() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/root",
+            VfsSnapshot::dir(hashmap! {
+                "Foo.luau" => VfsSnapshot::file("return 'foo'"),
+                "Bar" => VfsSnapshot::dir(hashmap! {
+                    "init.luau" => VfsSnapshot::file("return 'bar'"),
+                }),
+            }),
+        )
+        .unwrap();
+
+        let context = context_with(Vfs::new(imfs), Path::new("/root"), &luau_context());
+        let mut tree = context.tree.lock().unwrap();
+
+        // There's no rule for LocalScripts ending in .luau.
+        let update = PatchUpdate {
+            changed_class_name: Some("LocalScript".to_owned()),
+            ..update_for(find_child(&tree, "Foo"))
+        };
+
+        match context.renames_for_instance(
+            tree.get_instance(update.id).unwrap(),
+            Path::new("/root/Foo.luau"),
+            &update,
+        ) {
+            Err(message) => assert!(message.contains("LocalScript"), "{}", message),
+            other => panic!("expected an error, got {:?}", other),
+        }
+
+        for name in &["Foo", "Bar"] {
+            let id = find_child(&tree, name);
+            assert_eq!(tree.get_instance(id).unwrap().class_name(), "ModuleScript");
+
+            let update = PatchUpdate {
+                changed_class_name: Some("Script".to_owned()),
+                ..update_for(id)
+            };
+
+            context.rename_instance(&mut tree, &update).unwrap();
+        }
+
+        assert_eq!(
+            &*context.vfs.read("/root/Foo.server.luau").unwrap(),
+            b"return 'foo'"
+        );
+        assert_eq!(
+            &*context.vfs.read("/root/Bar/init.server.luau").unwrap(),
+            b"return 'bar'"
+        );
+    }
+
+    #[test]
+    fn source_is_written_to_init_file() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/root",
+            VfsSnapshot::dir(hashmap! {
+                "Bar" => VfsSnapshot::dir(hashmap! {
+                    "init.luau" => VfsSnapshot::file("return 'bar'"),
+                }),
+            }),
+        )
+        .unwrap();
+
+        let context = context_with(Vfs::new(imfs), Path::new("/root"), &luau_context());
+        let bar_id = find_child(&context.tree.lock().unwrap(), "Bar");
+
+        let mut patch_set = PatchSet::new();
+        patch_set.updated_instances.push(PatchUpdate {
+            changed_properties: hashmap! {
+                "Source".to_owned() => Some(RbxValue::String {
+                    value: "return 'baz'".to_owned(),
+                }),
+            },
+            ..update_for(bar_id)
+        });
+
+        context.handle_tree_event(patch_set);
+
+        assert_eq!(
+            &*context.vfs.read("/root/Bar/init.luau").unwrap(),
+            b"return 'baz'"
+        );
+    }
+
+    #[test]
+    fn rename_goes_through_vfs() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/root",
+            VfsSnapshot::dir(hashmap! {
+                "Foo.lua" => VfsSnapshot::file("return 'foo'"),
+                "Foo.meta.json" => VfsSnapshot::file(r#"{ "ignoreUnknownInstances": true }"#),
+            }),
+        )
+        .unwrap();
+
+        let context = context_for(Vfs::new(imfs.clone()), Path::new("/root"));
+        let mut tree = context.tree.lock().unwrap();
+
+        let root_id = tree.get_root_id();
+        let foo_id = tree.get_instance(root_id).unwrap().children()[0];
+
+        let update = PatchUpdate {
+            id: foo_id,
+            changed_name: Some("Bar".to_owned()),
+            changed_class_name: None,
+            changed_properties: HashMap::new(),
+            changed_metadata: None,
+            previous_name: None,
+            previous_class_name: None,
+            previous_properties: HashMap::new(),
+        };
+
+        context.rename_instance(&mut tree, &update).unwrap();
+
+        let mut files: Vec<_> = context
+            .vfs
+            .read_dir("/root")
+            .unwrap()
+            .map(|entry| entry.unwrap().path().to_path_buf())
+            .collect();
+        files.sort();
+
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from("/root/Bar.lua"),
+                PathBuf::from("/root/Bar.meta.json"),
+            ]
+        );
+
+        assert_eq!(
+            &*context.vfs.read("/root/Bar.lua").unwrap(),
+            b"return 'foo'"
+        );
+
+        let metadata = tree.get_metadata(foo_id).unwrap();
+        assert_eq!(
+            metadata.instigating_source,
+            Some(InstigatingSource::Path(PathBuf::from("/root/Bar.lua")))
+        );
+    }
+
+    #[test]
+    fn failed_rename_keeps_name() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/root",
+            VfsSnapshot::dir(hashmap! {
+                "Foo.lua" => VfsSnapshot::file("return 'foo'"),
+                "Bar.lua" => VfsSnapshot::file("return 'bar'"),
+            }),
+        )
+        .unwrap();
+
+        let context = context_for(Vfs::new(imfs), Path::new("/root"));
+        let foo_id = find_child(&context.tree.lock().unwrap(), "Foo");
+
+        context.handle_tree_event(PatchSet {
+            updated_instances: vec![PatchUpdate {
+                changed_name: Some("Bar".to_owned()),
+                ..update_for(foo_id)
+            }],
+            ..PatchSet::new()
+        });
+
+        let tree = context.tree.lock().unwrap();
+        assert_eq!(tree.get_instance(foo_id).unwrap().name(), "Foo");
+        assert_eq!(
+            &*context.vfs.read("/root/Foo.lua").unwrap(),
+            b"return 'foo'"
+        );
+        assert_eq!(
+            &*context.vfs.read("/root/Bar.lua").unwrap(),
+            b"return 'bar'"
+        );
+    }
+
+    #[test]
+    fn rename_all_moves_files_back() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/root",
+            VfsSnapshot::dir(hashmap! {
+                "Foo.lua" => VfsSnapshot::file("return 'foo'"),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+        let renames = vec![
+            (
+                PathBuf::from("/root/Foo.lua"),
+                PathBuf::from("/root/Bar.lua"),
+            ),
+            (
+                PathBuf::from("/root/Foo.meta.json"),
+                PathBuf::from("/root/Bar.meta.json"),
+            ),
+        ];
+
+        assert!(rename_all(&vfs, &renames).is_err());
+        assert_eq!(&*vfs.read("/root/Foo.lua").unwrap(), b"return 'foo'");
+        assert!(vfs
+            .metadata("/root/Bar.lua")
+            .with_not_found()
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn remove_goes_through_vfs() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/root",
+            VfsSnapshot::dir(hashmap! {
+                "Foo.lua" => VfsSnapshot::file("return 'foo'"),
+                "Bar" => VfsSnapshot::dir(hashmap! {
+                    "init.lua" => VfsSnapshot::file("return 'bar'"),
+                }),
+            }),
+        )
+        .unwrap();
+
+        let context = context_for(Vfs::new(imfs), Path::new("/root"));
+        let removed_instances = {
+            let tree = context.tree.lock().unwrap();
+            vec![find_child(&tree, "Foo"), find_child(&tree, "Bar")]
+        };
+
+        context.handle_tree_event(PatchSet {
+            removed_instances,
+            ..PatchSet::new()
+        });
+
+        assert_eq!(context.vfs.read_dir("/root").unwrap().count(), 0);
+    }
+}
@@ -1,13 +1,21 @@
 use std::{
+    collections::BTreeMap,
     fs::File,
     io::{BufWriter, Write},
+    path::Path,
 };
 
 use memofs::Vfs;
+use rbx_dom_weak::RbxValue;
 use thiserror::Error;
 use tokio::runtime::Runtime;
 
-use crate::{cli::BuildCommand, serve_session::ServeSession, snapshot::RojoTree};
+use crate::{
+    cli::BuildCommand,
+    serve_session::ServeSession,
+    snapshot::{InstigatingSource, RojoTree},
+    snapshot_middleware::{asset_content_property, relative_asset_path},
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum OutputKind {
@@ -44,24 +52,33 @@ pub fn build(options: BuildCommand) -> Result<(), anyhow::Error> {
 
     let vfs = Vfs::new_default();
 
-    let session = ServeSession::new(vfs, &options.absolute_project())?;
+    let session = ServeSession::new(vfs, options.absolute_project(), options.profile.as_deref())?;
     let mut cursor = session.message_queue().cursor();
 
     {
         let tree = session.tree();
         write_model(&tree, &options)?;
+        write_asset_manifest(&tree, session.root_dir(), &options)?;
     }
 
     if options.watch {
         let mut rt = Runtime::new().unwrap();
 
         loop {
-            let receiver = session.message_queue().subscribe(cursor);
-            let (new_cursor, _patch_set) = rt.block_on(receiver).unwrap();
-            cursor = new_cursor;
+            match session.message_queue().subscribe(cursor) {
+                Ok(receiver) => {
+                    let (new_cursor, _patch_set) = rt.block_on(receiver).unwrap();
+                    cursor = new_cursor;
+                }
+
+                // Everything is written from the whole tree, so it doesn't
+                // matter which messages were missed while writing.
+                Err(_) => cursor = session.message_queue().cursor(),
+            }
 
             let tree = session.tree();
             write_model(&tree, &options)?;
+            write_asset_manifest(&tree, session.root_dir(), &options)?;
         }
     }
 
@@ -69,7 +86,7 @@ pub fn build(options: BuildCommand) -> Result<(), anyhow::Error> {
 }
 
 fn write_model(tree: &RojoTree, options: &BuildCommand) -> Result<(), anyhow::Error> {
-    let output_kind = detect_output_kind(&options).ok_or(Error::UnknownOutputKind)?;
+    let output_kind = detect_output_kind(options).ok_or(Error::UnknownOutputKind)?;
     log::debug!("Hoping to generate file of type {:?}", output_kind);
 
     let root_id = tree.get_root_id();
@@ -120,3 +137,43 @@ fn write_model(tree: &RojoTree, options: &BuildCommand) -> Result<(), anyhow::Er
 
     Ok(())
 }
+
+/// Writes the content ID of every instance made from an image or audio file,
+/// keyed by the path of the file relative to the project, if an asset manifest
+/// was asked for.
+fn write_asset_manifest(
+    tree: &RojoTree,
+    project_dir: &Path,
+    options: &BuildCommand,
+) -> Result<(), anyhow::Error> {
+    let manifest_path = match &options.asset_manifest {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+
+    let mut manifest = BTreeMap::new();
+
+    for instance in tree.descendants(tree.get_root_id()) {
+        let path = match &instance.metadata().instigating_source {
+            Some(InstigatingSource::Path(path)) => path,
+            _ => continue,
+        };
+
+        let property = match asset_content_property(path, instance.class_name()) {
+            Some(property) => property,
+            None => continue,
+        };
+
+        if let Some(RbxValue::Content { value }) = instance.properties().get(property) {
+            manifest.insert(relative_asset_path(Some(project_dir), path), value.clone());
+        }
+    }
+
+    let mut file = BufWriter::new(File::create(manifest_path)?);
+    serde_json::to_writer_pretty(&mut file, &manifest)?;
+    file.flush()?;
+
+    log::info!("Wrote asset manifest to {}", manifest_path.display());
+
+    Ok(())
+}
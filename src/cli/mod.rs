@@ -5,6 +5,8 @@ mod doc;
 mod init;
 mod plugin;
 mod serve;
+mod sourcemap;
+mod syncback;
 mod upload;
 
 use std::{
@@ -24,6 +26,8 @@ pub use self::doc::*;
 pub use self::init::*;
 pub use self::plugin::*;
 pub use self::serve::*;
+pub use self::sourcemap::*;
+pub use self::syncback::*;
 pub use self::upload::*;
 
 /// Command line options that Rojo accepts, defined using the structopt crate.
@@ -111,6 +115,12 @@ pub enum Subcommand {
     /// Generates a place or model file out of the project and uploads it to Roblox.
     Upload(UploadCommand),
 
+    /// Generates a sourcemap describing which files each instance came from.
+    Sourcemap(SourcemapCommand),
+
+    /// Converts a place file into a new Rojo project.
+    Syncback(SyncbackCommand),
+
     /// Open Rojo's documentation in your browser.
     Doc,
 
@@ -190,6 +200,10 @@ pub struct ServeCommand {
     /// it has none.
     #[structopt(long)]
     pub port: Option<u16>,
+
+    /// The project profile to apply, if any.
+    #[structopt(long)]
+    pub profile: Option<String>,
 }
 
 impl ServeCommand {
@@ -212,6 +226,15 @@ pub struct BuildCommand {
     /// Whether to automatically rebuild when any input files change.
     #[structopt(long)]
     pub watch: bool,
+
+    /// The project profile to apply, if any.
+    #[structopt(long)]
+    pub profile: Option<String>,
+
+    /// Where to write a JSON file mapping each image and audio file in the
+    /// project to the content ID it was given, if anywhere.
+    #[structopt(long)]
+    pub asset_manifest: Option<PathBuf>,
 }
 
 impl BuildCommand {
@@ -234,6 +257,10 @@ pub struct UploadCommand {
     /// Asset ID to upload to.
     #[structopt(long = "asset_id")]
     pub asset_id: u64,
+
+    /// The project profile to apply, if any.
+    #[structopt(long)]
+    pub profile: Option<String>,
 }
 
 impl UploadCommand {
@@ -242,6 +269,51 @@ impl UploadCommand {
     }
 }
 
+/// Generate a sourcemap from a Rojo project, mapping each instance in the tree
+/// to the files that it was created from.
+#[derive(Debug, StructOpt)]
+pub struct SourcemapCommand {
+    /// Path to the project to generate a sourcemap for. Defaults to the current
+    /// directory.
+    #[structopt(default_value = "")]
+    pub project: PathBuf,
+
+    /// Where to output the sourcemap. If not specified, the sourcemap will be
+    /// printed to stdout.
+    #[structopt(long, short)]
+    pub output: Option<PathBuf>,
+
+    /// Whether to automatically regenerate the sourcemap when any input files
+    /// change.
+    #[structopt(long)]
+    pub watch: bool,
+}
+
+impl SourcemapCommand {
+    pub fn absolute_project(&self) -> Cow<'_, Path> {
+        resolve_path(&self.project)
+    }
+}
+
+/// Convert an existing place file into a Rojo project, writing out each
+/// instance as a file.
+#[derive(Debug, StructOpt)]
+pub struct SyncbackCommand {
+    /// Path to the place file to convert. Must end in .rbxl or .rbxlx.
+    pub input: PathBuf,
+
+    /// Path to the folder to create the project in. Defaults to the current
+    /// directory.
+    #[structopt(long, short, default_value = "")]
+    pub output: PathBuf,
+}
+
+impl SyncbackCommand {
+    pub fn absolute_output(&self) -> Cow<'_, Path> {
+        resolve_path(&self.output)
+    }
+}
+
 /// The kind of asset to upload to the website. Affects what endpoints Rojo uses
 /// and changes how the asset is built.
 #[derive(Debug, Clone, Copy)]
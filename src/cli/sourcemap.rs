@@ -0,0 +1,128 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use memofs::Vfs;
+use rbx_dom_weak::RbxId;
+use serde::Serialize;
+use tokio::runtime::Runtime;
+
+use crate::{cli::SourcemapCommand, serve_session::ServeSession, snapshot::RojoTree};
+
+/// A node in the sourcemap, describing one instance and the files that it was
+/// created from.
+///
+/// The structure of the sourcemap mirrors the structure of the instance tree,
+/// so the path to any instance in the DataModel is given by the names of its
+/// ancestors.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SourcemapNode<'a> {
+    name: &'a str,
+    class_name: &'a str,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    file_paths: Vec<PathBuf>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<SourcemapNode<'a>>,
+}
+
+pub fn sourcemap(options: SourcemapCommand) -> Result<(), anyhow::Error> {
+    log::trace!("Constructing in-memory filesystem");
+
+    let vfs = Vfs::new_default();
+
+    let session = ServeSession::new(vfs, options.absolute_project(), None)?;
+    let mut cursor = session.message_queue().cursor();
+
+    write_sourcemap(&session, &options)?;
+
+    if options.watch {
+        let mut rt = Runtime::new().unwrap();
+
+        loop {
+            match session.message_queue().subscribe(cursor) {
+                Ok(receiver) => {
+                    let (new_cursor, _patch_set) = rt.block_on(receiver)?;
+                    cursor = new_cursor;
+                }
+
+                // Everything is written from the whole tree, so it doesn't
+                // matter which messages were missed while writing.
+                Err(_) => cursor = session.message_queue().cursor(),
+            }
+
+            write_sourcemap(&session, &options)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_sourcemap(
+    session: &ServeSession,
+    options: &SourcemapCommand,
+) -> Result<(), anyhow::Error> {
+    let tree = session.tree();
+    let root_node =
+        recurse_create_node(&tree, tree.get_root_id(), session.root_dir(), session.vfs());
+
+    match &options.output {
+        Some(output_path) => {
+            log::trace!("Opening output file for write");
+            let mut file = BufWriter::new(File::create(output_path)?);
+            serde_json::to_writer(&mut file, &root_node)?;
+            file.flush()?;
+
+            log::info!("Created sourcemap at {}", output_path.display());
+        }
+        None => {
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            serde_json::to_writer(&mut stdout, &root_node)?;
+            writeln!(stdout)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn recurse_create_node<'a>(
+    tree: &'a RojoTree,
+    id: RbxId,
+    project_dir: &Path,
+    vfs: &Vfs,
+) -> SourcemapNode<'a> {
+    let instance = tree.get_instance(id).expect("instance did not exist");
+
+    let children = instance
+        .children()
+        .iter()
+        .map(|&child_id| recurse_create_node(tree, child_id, project_dir, vfs))
+        .collect();
+
+    // Relevant paths include files that could affect this instance if they
+    // existed, like adjacent meta files. Only files that actually exist are
+    // useful to consumers of the sourcemap.
+    let file_paths = instance
+        .metadata()
+        .relevant_paths
+        .iter()
+        .filter(|path| {
+            vfs.metadata(path)
+                .map(|meta| meta.is_file())
+                .unwrap_or(false)
+        })
+        .map(|path| path.strip_prefix(project_dir).unwrap_or(path).to_path_buf())
+        .collect();
+
+    SourcemapNode {
+        name: instance.name(),
+        class_name: instance.class_name(),
+        file_paths,
+        children,
+    }
+}
@@ -0,0 +1,99 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::BufReader,
+};
+
+use memofs::{IoResultExt, Vfs, VfsSnapshot};
+use rbx_dom_weak::{RbxInstanceProperties, RbxTree};
+use thiserror::Error;
+
+use crate::{
+    cli::SyncbackCommand,
+    syncback::{syncback_place, write_snapshot},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InputKind {
+    Rbxlx,
+    Rbxl,
+}
+
+fn detect_input_kind(options: &SyncbackCommand) -> Option<InputKind> {
+    let extension = options.input.extension()?.to_str()?;
+
+    match extension {
+        "rbxlx" => Some(InputKind::Rbxlx),
+        "rbxl" => Some(InputKind::Rbxl),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Error)]
+enum Error {
+    #[error("Could not detect what kind of file to convert. Expected input file to end in .rbxl or .rbxlx.")]
+    UnknownInputKind,
+
+    #[error("A file or folder named {name} already exists in the output folder")]
+    AlreadyExists { name: String },
+}
+
+fn xml_decode_config() -> rbx_xml::DecodeOptions {
+    rbx_xml::DecodeOptions::new().property_behavior(rbx_xml::DecodePropertyBehavior::ReadUnknown)
+}
+
+pub fn syncback(options: SyncbackCommand) -> Result<(), anyhow::Error> {
+    let input_kind = detect_input_kind(&options).ok_or(Error::UnknownInputKind)?;
+    log::debug!("Reading input file of type {:?}", input_kind);
+
+    let file = BufReader::new(File::open(&options.input)?);
+
+    let tree = match input_kind {
+        InputKind::Rbxlx => rbx_xml::from_reader(file, xml_decode_config())?,
+        InputKind::Rbxl => {
+            let mut tree = RbxTree::new(RbxInstanceProperties {
+                name: "DataModel".to_owned(),
+                class_name: "DataModel".to_owned(),
+                properties: HashMap::new(),
+            });
+
+            let root_id = tree.get_root_id();
+            rbx_binary::decode(&mut tree, root_id, file)?;
+
+            tree
+        }
+    };
+
+    let project_name = options
+        .input
+        .file_stem()
+        .and_then(|name| name.to_str())
+        .unwrap_or("new-project");
+
+    let snapshot = syncback_place(&tree, project_name)?;
+
+    let base_path = options.absolute_output();
+    fs::create_dir_all(&base_path)?;
+
+    let vfs = Vfs::new_default();
+
+    if let VfsSnapshot::Dir { children } = snapshot {
+        for name in children.keys() {
+            if vfs
+                .metadata(base_path.join(name))
+                .with_not_found()?
+                .is_some()
+            {
+                return Err(Error::AlreadyExists { name: name.clone() }.into());
+            }
+        }
+
+        for (name, child) in &children {
+            write_snapshot(&vfs, &base_path.join(name), child)?;
+        }
+    }
+
+    log::info!("Converted {} into a Rojo project", project_name);
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Keeps track of the latest error that happened while turning each path into
+/// instances, so that they can be shown to users somewhere other than the
+/// terminal.
+///
+/// Errors are keyed by the path that was being snapshotted and stick around
+/// until that path is snapshotted successfully or removed.
+#[derive(Debug, Default)]
+pub struct ErrorRegistry {
+    errors: Mutex<BTreeMap<PathBuf, String>>,
+}
+
+impl ErrorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an error for the given path, replacing any earlier error for
+    /// it.
+    pub fn insert(&self, path: impl Into<PathBuf>, error: &dyn Error) {
+        let mut message = error.to_string();
+
+        let mut current_err = error;
+        while let Some(source) = current_err.source() {
+            message.push_str(": ");
+            message.push_str(&source.to_string());
+            current_err = source;
+        }
+
+        self.errors.lock().unwrap().insert(path.into(), message);
+    }
+
+    /// Forgets about any error for the given path.
+    pub fn remove(&self, path: &Path) {
+        self.errors.lock().unwrap().remove(path);
+    }
+
+    /// Returns every error that's currently recorded, ordered by path.
+    pub fn errors(&self) -> Vec<(PathBuf, String)> {
+        self.errors
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(path, message)| (path.clone(), message.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io;
+
+    use crate::snapshot_middleware::SnapshotError;
+
+    #[test]
+    fn error_chain() {
+        let registry = ErrorRegistry::new();
+        let error = SnapshotError::malformed_json(
+            serde_json::from_str::<u32>("nope").unwrap_err(),
+            "/foo.json",
+        );
+
+        registry.insert("/foo.json", &error);
+
+        assert_eq!(
+            registry.errors(),
+            vec![(
+                PathBuf::from("/foo.json"),
+                "malformed JSON at path /foo.json: expected ident at line 1 column 2".to_owned()
+            )]
+        );
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let registry = ErrorRegistry::new();
+        let error = io::Error::new(io::ErrorKind::Other, "oh no");
+
+        registry.insert("/foo.lua", &error);
+        registry.insert("/bar.lua", &error);
+        registry.insert("/foo.lua", &error);
+
+        assert_eq!(
+            registry.errors(),
+            vec![
+                (PathBuf::from("/bar.lua"), "oh no".to_owned()),
+                (PathBuf::from("/foo.lua"), "oh no".to_owned()),
+            ]
+        );
+
+        registry.remove(Path::new("/bar.lua"));
+        registry.remove(Path::new("/foo.lua"));
+        assert!(registry.errors().is_empty());
+    }
+}
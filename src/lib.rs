@@ -10,17 +10,21 @@ mod tree_view;
 mod auth_cookie;
 mod change_processor;
 mod error;
+mod error_registry;
 mod glob;
 mod lua_ast;
 mod message_queue;
 mod multimap;
 mod path_serializer;
 mod project;
+mod project_variables;
 mod serve_session;
 mod session_id;
 mod snapshot;
 mod snapshot_middleware;
+mod syncback;
 mod web;
+mod write_back;
 
 pub use project::*;
 pub use session_id::SessionId;
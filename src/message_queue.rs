@@ -1,77 +1,176 @@
 use std::{
+    collections::VecDeque,
     mem,
     sync::{Mutex, RwLock},
 };
 
 use futures::sync::oneshot;
+use thiserror::Error;
+
+/// Messages that can be merged with the message that came right before them.
+pub trait Coalesce: Sized {
+    /// Merges `next` into `self` if the result would mean the same thing as
+    /// receiving both messages in order, otherwise gives `next` back.
+    fn coalesce(&mut self, next: Self) -> Result<(), Self>;
+}
+
+/// Returned when subscribing with a cursor whose messages have already been
+/// dropped from the queue's history. Subscribers need to catch up some other
+/// way, like reading the whole tree again.
+#[derive(Debug, Error)]
+#[error("Message cursor {cursor} is too old, the oldest message kept is {oldest_cursor}")]
+pub struct CursorTooOld {
+    pub cursor: u32,
+    pub oldest_cursor: u32,
+}
 
 struct Listener<T> {
     sender: oneshot::Sender<(u32, Vec<T>)>,
     cursor: u32,
 }
 
-fn fire_listener_if_ready<T: Clone>(
-    messages: &[T],
-    listener: Listener<T>,
-) -> Result<(), Listener<T>> {
-    let current_cursor = messages.len() as u32;
+struct History<T> {
+    messages: VecDeque<T>,
+
+    /// The cursor of the first message in `messages`, which moves forward as
+    /// old messages are dropped.
+    first_cursor: u32,
+}
+
+impl<T: Clone + Coalesce> History<T> {
+    fn cursor(&self) -> u32 {
+        self.first_cursor + self.messages.len() as u32
+    }
+
+    fn fire_listener_if_ready(
+        &self,
+        listener: Listener<T>,
+        coalesce: bool,
+    ) -> Result<(), Listener<T>> {
+        let current_cursor = self.cursor();
+
+        if listener.cursor < current_cursor {
+            let start = (listener.cursor - self.first_cursor) as usize;
+            let new_messages = self.messages.iter().skip(start).cloned();
+
+            let new_messages = if coalesce {
+                coalesce_messages(new_messages)
+            } else {
+                new_messages.collect()
+            };
+
+            let _ = listener.sender.send((current_cursor, new_messages));
+            Ok(())
+        } else {
+            Err(listener)
+        }
+    }
+}
+
+fn coalesce_messages<T: Coalesce>(messages: impl Iterator<Item = T>) -> Vec<T> {
+    let mut coalesced: Vec<T> = Vec::new();
 
-    if listener.cursor < current_cursor {
-        let new_messages = messages[(listener.cursor as usize)..].to_vec();
-        let _ = listener.sender.send((current_cursor, new_messages));
-        Ok(())
-    } else {
-        Err(listener)
+    for message in messages {
+        let message = match coalesced.last_mut() {
+            Some(last) => match last.coalesce(message) {
+                Ok(()) => continue,
+                Err(message) => message,
+            },
+            None => message,
+        };
+
+        coalesced.push(message);
     }
+
+    coalesced
 }
 
 /// A message queue with persistent history that can be subscribed to.
 ///
+/// The history can be limited to a number of messages, after which the oldest
+/// messages are dropped and subscribing with their cursors fails with
+/// `CursorTooOld`.
+///
 /// Definitely non-optimal. This would ideally be a lockless mpmc queue.
-#[derive(Default)]
 pub struct MessageQueue<T> {
-    messages: RwLock<Vec<T>>,
+    history: RwLock<History<T>>,
     message_listeners: Mutex<Vec<Listener<T>>>,
+    history_limit: Option<usize>,
+    coalesce: bool,
 }
 
-impl<T: Clone> MessageQueue<T> {
+impl<T: Clone + Coalesce> MessageQueue<T> {
     pub fn new() -> MessageQueue<T> {
         MessageQueue {
-            messages: RwLock::new(Vec::new()),
+            history: RwLock::new(History {
+                messages: VecDeque::new(),
+                first_cursor: 0,
+            }),
             message_listeners: Mutex::new(Vec::new()),
+            history_limit: None,
+            coalesce: false,
         }
     }
 
+    /// Keep at most `limit` messages around for subscribers that are behind.
+    pub fn with_history_limit(mut self, limit: usize) -> Self {
+        self.history_limit = Some(limit);
+        self
+    }
+
+    /// Merge consecutive messages before handing them to subscribers when they
+    /// can be merged.
+    pub fn with_coalescing(mut self) -> Self {
+        self.coalesce = true;
+        self
+    }
+
     pub fn push_messages(&self, new_messages: &[T]) {
         let mut message_listeners = self.message_listeners.lock().unwrap();
-        let mut messages = self.messages.write().unwrap();
-        messages.extend_from_slice(new_messages);
+        let mut history = self.history.write().unwrap();
+        history.messages.extend(new_messages.iter().cloned());
 
         let mut remaining_listeners = Vec::new();
 
         for listener in message_listeners.drain(..) {
-            match fire_listener_if_ready(&messages, listener) {
+            match history.fire_listener_if_ready(listener, self.coalesce) {
                 Ok(_) => {}
                 Err(listener) => remaining_listeners.push(listener),
             }
         }
 
+        // Messages are only dropped once every waiting listener has gotten
+        // them, so nobody who was already subscribed can miss a message.
+        if let Some(limit) = self.history_limit {
+            while history.messages.len() > limit {
+                history.messages.pop_front();
+                history.first_cursor += 1;
+            }
+        }
+
         // Without this annotation, Rust gets confused since the first argument
         // is a MutexGuard, but the second is a Vec.
         mem::replace::<Vec<_>>(&mut message_listeners, remaining_listeners);
     }
 
     /// Subscribe to any messages occurring after the given message cursor.
-    pub fn subscribe(&self, cursor: u32) -> oneshot::Receiver<(u32, Vec<T>)> {
+    pub fn subscribe(&self, cursor: u32) -> Result<oneshot::Receiver<(u32, Vec<T>)>, CursorTooOld> {
         let (sender, receiver) = oneshot::channel();
 
         let listener = {
             let listener = Listener { sender, cursor };
 
-            let messages = self.messages.read().unwrap();
+            let history = self.history.read().unwrap();
+
+            if cursor < history.first_cursor {
+                return Err(CursorTooOld {
+                    cursor,
+                    oldest_cursor: history.first_cursor,
+                });
+            }
 
-            match fire_listener_if_ready(&messages, listener) {
-                Ok(_) => return receiver,
+            match history.fire_listener_if_ready(listener, self.coalesce) {
+                Ok(_) => return Ok(receiver),
                 Err(listener) => listener,
             }
         };
@@ -79,7 +178,7 @@ impl<T: Clone> MessageQueue<T> {
         let mut message_listeners = self.message_listeners.lock().unwrap();
         message_listeners.push(listener);
 
-        receiver
+        Ok(receiver)
     }
 
     /// Subscribe to any messages being pushed into the queue.
@@ -88,15 +187,105 @@ impl<T: Clone> MessageQueue<T> {
     /// instead.
     #[cfg(test)]
     pub fn subscribe_any(&self) -> oneshot::Receiver<(u32, Vec<T>)> {
-        let cursor = {
-            let messages = self.messages.read().unwrap();
-            messages.len() as u32
-        };
-
-        self.subscribe(cursor)
+        self.subscribe(self.cursor())
+            .expect("the current cursor is never too old")
     }
 
     pub fn cursor(&self) -> u32 {
-        self.messages.read().unwrap().len() as u32
+        self.history.read().unwrap().cursor()
+    }
+}
+
+impl<T: Clone + Coalesce> Default for MessageQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use futures::Future;
+
+    /// A message that merges with the previous one when both have the same
+    /// key, keeping the newer value.
+    #[derive(Debug, Clone, PartialEq)]
+    struct Message(&'static str, u32);
+
+    impl Coalesce for Message {
+        fn coalesce(&mut self, next: Self) -> Result<(), Self> {
+            if self.0 == next.0 {
+                self.1 = next.1;
+                Ok(())
+            } else {
+                Err(next)
+            }
+        }
+    }
+
+    fn messages(values: &[(&'static str, u32)]) -> Vec<Message> {
+        values
+            .iter()
+            .map(|&(key, value)| Message(key, value))
+            .collect()
+    }
+
+    #[test]
+    fn subscribe_returns_history() {
+        let queue = MessageQueue::new();
+        queue.push_messages(&messages(&[("a", 1), ("b", 2)]));
+
+        let (cursor, received) = queue.subscribe(0).unwrap().wait().unwrap();
+
+        assert_eq!(cursor, 2);
+        assert_eq!(received, messages(&[("a", 1), ("b", 2)]));
+    }
+
+    #[test]
+    fn history_limit() {
+        let queue = MessageQueue::new().with_history_limit(2);
+        queue.push_messages(&messages(&[("a", 1), ("b", 2), ("c", 3)]));
+
+        assert_eq!(queue.cursor(), 3);
+
+        let err = queue.subscribe(0).unwrap_err();
+        assert_eq!(err.cursor, 0);
+        assert_eq!(err.oldest_cursor, 1);
+
+        let (cursor, received) = queue.subscribe(1).unwrap().wait().unwrap();
+
+        assert_eq!(cursor, 3);
+        assert_eq!(received, messages(&[("b", 2), ("c", 3)]));
+    }
+
+    #[test]
+    fn history_limit_waiting_listener() {
+        let queue = MessageQueue::new().with_history_limit(1);
+        let receiver = queue.subscribe_any();
+
+        queue.push_messages(&messages(&[("a", 1), ("b", 2), ("c", 3)]));
+
+        let (cursor, received) = receiver.wait().unwrap();
+
+        assert_eq!(cursor, 3);
+        assert_eq!(received, messages(&[("a", 1), ("b", 2), ("c", 3)]));
+    }
+
+    #[test]
+    fn coalescing() {
+        let queue = MessageQueue::new().with_coalescing();
+        queue.push_messages(&messages(&[
+            ("a", 1),
+            ("a", 2),
+            ("b", 3),
+            ("a", 4),
+            ("a", 5),
+        ]));
+
+        let (cursor, received) = queue.subscribe(0).unwrap().wait().unwrap();
+
+        assert_eq!(cursor, 5);
+        assert_eq!(received, messages(&[("a", 2), ("b", 3), ("a", 5)]));
     }
 }
@@ -1,6 +1,6 @@
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
-    fs, io,
+    fs, io, mem,
     path::{Path, PathBuf},
 };
 
@@ -8,7 +8,7 @@ use rbx_dom_weak::UnresolvedRbxValue;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::glob::Glob;
+use crate::{glob::Glob, project_variables::substitute_project};
 
 static PROJECT_FILENAME: &str = "default.project.json";
 
@@ -64,6 +64,47 @@ pub struct Project {
     #[cfg_attr(not(feature = "unstable_glob_ignore_paths"), serde(skip))]
     pub glob_ignore_paths: Vec<Glob>,
 
+    /// Values that can be used in `$path`, `$include`, string values in
+    /// `$properties`, and `servePort` by writing `${NAME}`. Environment
+    /// variables can be used the same way with `${env:NAME}`.
+    ///
+    /// Variables can be strings, numbers, or booleans. A string that's nothing
+    /// but a reference to a variable becomes that variable's value, so numbers
+    /// and booleans can be used for properties that need them. Environment
+    /// variables referenced that way become numbers or booleans if they read
+    /// as one.
+    ///
+    /// References are only substituted in projects that have this field, even
+    /// if it's empty.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, serde_json::Value>,
+
+    /// Named sets of changes to `tree` that can be picked when building,
+    /// serving, or uploading the project with `--profile`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// A list of Lua files, relative to the folder the project file is in, that
+    /// define user plugins. Plugins can turn files into instances before any of
+    /// Rojo's built-in rules are applied.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub plugins: Vec<PathBuf>,
+
+    /// Rules for turning files into instances based on how their names end,
+    /// like `"*.luau": "ModuleScript"`. The instance is named after the part of
+    /// the file name matched by `*`, and holds the contents of the file. When
+    /// more than one rule matches a file, the longest one is used.
+    ///
+    /// These rules apply to this project and projects nested inside of it.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub file_rules: BTreeMap<String, String>,
+
+    /// Changes how Rojo's middleware, like `lua` or `json`, are used to turn
+    /// the files in this project into instances. Projects nested inside of
+    /// this one start out with the same changes.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub middleware: BTreeMap<String, MiddlewareOptions>,
+
     /// The path to the file that this project came from. Relative paths in the
     /// project should be considered relative to the parent of this field, also
     /// given by `Project::folder_location`.
@@ -113,7 +154,7 @@ impl Project {
         contents: &[u8],
         project_file_location: &Path,
     ) -> Result<Self, serde_json::Error> {
-        let mut project: Self = serde_json::from_slice(&contents)?;
+        let mut project = Self::parse(contents)?;
         project.file_location = project_file_location.to_path_buf();
         project.check_compatibility();
         Ok(project)
@@ -130,13 +171,12 @@ impl Project {
     }
 
     fn load_exact(project_file_location: &Path) -> Result<Self, Error> {
-        let contents = fs::read_to_string(project_file_location)?;
+        let contents = fs::read(project_file_location)?;
 
-        let mut project: Project =
-            serde_json::from_str(&contents).map_err(|source| Error::Json {
-                source,
-                path: project_file_location.to_owned(),
-            })?;
+        let mut project = Self::parse(&contents).map_err(|source| Error::Json {
+            source,
+            path: project_file_location.to_owned(),
+        })?;
 
         project.file_location = project_file_location.to_path_buf();
         project.check_compatibility();
@@ -144,6 +184,14 @@ impl Project {
         Ok(project)
     }
 
+    /// Deserializes a project, substituting variables into it first.
+    fn parse(contents: &[u8]) -> Result<Self, serde_json::Error> {
+        let mut value: serde_json::Value = serde_json::from_slice(contents)?;
+        substitute_project(&mut value).map_err(serde::de::Error::custom)?;
+
+        serde_json::from_value(value)
+    }
+
     /// Checks if there are any compatibility issues with this project file and
     /// warns the user if there are any.
     fn check_compatibility(&self) {
@@ -153,6 +201,48 @@ impl Project {
     pub fn folder_location(&self) -> &Path {
         self.file_location.parent().unwrap()
     }
+
+    /// Layers the profile with the given name on top of this project's tree.
+    /// Projects that don't define the profile are left alone, which lets
+    /// profiles be shared by projects nested inside of each other.
+    pub fn apply_profile(&mut self, name: &str) {
+        if let Some(profile) = self.profiles.get(name) {
+            let tree = mem::take(&mut self.tree);
+            self.tree = profile.tree.clone().with_base(tree);
+        }
+    }
+}
+
+/// Changes to one of the middleware used to snapshot a project.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct MiddlewareOptions {
+    /// If set to `false`, the middleware won't be used at all.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+
+    /// Middleware with higher priorities get to handle files first. Rojo's
+    /// built-in middleware have priorities between 0 and 100.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+
+    /// Globs matched against file names to decide which files the middleware
+    /// is tried on, replacing the ones it uses by default. Middleware still
+    /// skip files they don't know how to read, so this is mostly useful for
+    /// narrowing down which files a middleware handles.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<Vec<String>>,
+}
+
+/// A named set of changes to a project's tree, like including test code only
+/// in development builds.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct Profile {
+    /// Layered on top of the project's tree the same way a node is layered on
+    /// top of the project it pulls in with `$include`. Nodes can be turned
+    /// off or back on with `$enabled`.
+    pub tree: ProjectNode,
 }
 
 /// Describes an instance and its descendants in a project.
@@ -181,6 +271,12 @@ pub struct ProjectNode {
     )]
     pub properties: HashMap<String, UnresolvedRbxValue>,
 
+    /// CollectionService tags that will be given to the resulting instance.
+    /// These are added to any tags the instance has from `$path` or
+    /// `$include`.
+    #[serde(rename = "$tags", default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
     /// Defines the behavior when Rojo encounters unknown instances in Roblox
     /// Studio during live sync. `$ignoreUnknownInstances` should be considered
     /// a large hammer and used with care.
@@ -211,9 +307,79 @@ pub struct ProjectNode {
         skip_serializing_if = "Option::is_none"
     )]
     pub path: Option<PathBuf>,
+
+    /// Defines that this node should start out as the tree of another project
+    /// file, given relative to the folder this project is in. Everything else
+    /// set on this node is layered on top of that tree: `$className`, `$path`
+    /// and `$ignoreUnknownInstances` replace the included values,
+    /// `$properties` are merged, `$tags` are added to the included ones, and
+    /// children with the same name as an included child are merged into it
+    /// the same way.
+    ///
+    /// Only the `tree` of the included project is used.
+    #[serde(
+        rename = "$include",
+        serialize_with = "crate::path_serializer::serialize_option_absolute",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub include: Option<PathBuf>,
+
+    /// If set to `false`, this node and its descendants are left out of the
+    /// project. This is mostly useful in profiles, which can turn nodes off or
+    /// back on. The root of a project can't be turned off.
+    #[serde(rename = "$enabled", skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
 }
 
 impl ProjectNode {
+    /// Layers this node on top of `base`, which is usually the tree of a
+    /// project pulled in with `$include`.
+    pub fn with_base(self, base: ProjectNode) -> ProjectNode {
+        let mut properties = base.properties;
+        properties.extend(self.properties);
+
+        let mut tags = base.tags;
+        tags.extend(self.tags);
+
+        let mut children = base.children;
+        for (name, child) in self.children {
+            let child = match children.remove(&name) {
+                Some(base_child) => child.with_base(base_child),
+                None => child,
+            };
+
+            children.insert(name, child);
+        }
+
+        ProjectNode {
+            class_name: self.class_name.or(base.class_name),
+            children,
+            properties,
+            tags,
+            ignore_unknown_instances: self
+                .ignore_unknown_instances
+                .or(base.ignore_unknown_instances),
+            path: self.path.or(base.path),
+            include: self.include.or(base.include),
+            enabled: self.enabled.or(base.enabled),
+        }
+    }
+
+    /// Makes every relative `$path` and `$include` in this node and its
+    /// descendants relative to the given folder instead, so that the node can
+    /// be used from a project in a different folder.
+    pub fn rebase_paths(&mut self, folder: &Path) {
+        for path in self.path.iter_mut().chain(self.include.iter_mut()) {
+            if path.is_relative() {
+                *path = folder.join(&*path);
+            }
+        }
+
+        for child in self.children.values_mut() {
+            child.rebase_paths(folder);
+        }
+    }
+
     fn validate_reserved_names(&self) {
         for (name, child) in &self.children {
             if name.starts_with('$') {
@@ -0,0 +1,474 @@
+//! Substitutes variables into project files before they're deserialized.
+//!
+//! Projects can define values in their `variables` field and refer to them
+//! with `${NAME}` in `$path`, `$include`, string values in `$properties`, and
+//! `servePort`, including in the trees of profiles. Environment variables are
+//! available as `${env:NAME}`, both in those places and in the values of
+//! `variables` themselves. `$${` produces a literal `${`.
+//!
+//! A string that's nothing but one reference becomes the referenced value
+//! as-is. Environment variables are always text, so ones that read as a JSON
+//! number or as `true` or `false` become numbers and booleans in that case.
+//!
+//! Projects without a `variables` field are left as they are, since they were
+//! free to use text like `${NAME}` before variables existed.
+
+use std::{collections::HashMap, convert::TryFrom, env};
+
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum VariableError {
+    #[error("unknown variable {name} in {text:?}")]
+    UnknownVariable { name: String, text: String },
+
+    #[error("environment variable {name} is not set, but is used in {text:?}")]
+    MissingEnvironmentVariable { name: String, text: String },
+
+    #[error("unterminated variable reference in {text:?}")]
+    Unterminated { text: String },
+
+    #[error("variable {name} must be a string, number, or boolean")]
+    InvalidValue { name: String },
+
+    #[error("servePort must be a port number, but it was {value}")]
+    InvalidServePort { value: Value },
+}
+
+/// Substitutes variables into the raw JSON of a project file.
+pub fn substitute_project(project: &mut Value) -> Result<(), VariableError> {
+    let project = match project.as_object_mut() {
+        Some(project) => project,
+        None => return Ok(()),
+    };
+
+    let variables = match project.get_mut("variables") {
+        Some(Value::Object(variables)) => resolve_variables(variables)?,
+        _ => return Ok(()),
+    };
+
+    if let Some(port) = project.get_mut("servePort") {
+        substitute_serve_port(&variables, port)?;
+    }
+
+    if let Some(tree) = project.get_mut("tree") {
+        substitute_node(&variables, tree)?;
+    }
+
+    if let Some(Value::Object(profiles)) = project.get_mut("profiles") {
+        for profile in profiles.values_mut() {
+            if let Some(tree) = profile.get_mut("tree") {
+                substitute_node(&variables, tree)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Substitutes environment variables into the values of `variables`, then
+/// turns them into the values that other references are replaced with.
+fn resolve_variables(
+    variables: &mut Map<String, Value>,
+) -> Result<HashMap<String, Value>, VariableError> {
+    let empty = HashMap::new();
+
+    variables
+        .iter_mut()
+        .map(|(name, value)| {
+            match value {
+                Value::String(_) => substitute_value(&empty, value)?,
+                Value::Number(_) | Value::Bool(_) => {}
+                _ => return Err(VariableError::InvalidValue { name: name.clone() }),
+            }
+
+            Ok((name.clone(), value.clone()))
+        })
+        .collect()
+}
+
+fn substitute_serve_port(
+    variables: &HashMap<String, Value>,
+    port: &mut Value,
+) -> Result<(), VariableError> {
+    if !port.is_string() {
+        return Ok(());
+    }
+
+    substitute_value(variables, port)?;
+
+    let number = match port {
+        Value::String(text) => text.parse().ok(),
+        Value::Number(number) => number
+            .as_u64()
+            .and_then(|number| u16::try_from(number).ok()),
+        _ => None,
+    };
+
+    match number {
+        Some(number) => {
+            *port = Value::from(number);
+            Ok(())
+        }
+        None => Err(VariableError::InvalidServePort {
+            value: port.clone(),
+        }),
+    }
+}
+
+fn substitute_node(
+    variables: &HashMap<String, Value>,
+    node: &mut Value,
+) -> Result<(), VariableError> {
+    let node = match node.as_object_mut() {
+        Some(node) => node,
+        None => return Ok(()),
+    };
+
+    for (key, value) in node.iter_mut() {
+        match key.as_str() {
+            "$path" | "$include" => {
+                if let Value::String(text) = value {
+                    *text = substitute_str(variables, text)?;
+                }
+            }
+            "$properties" => {
+                if let Value::Object(properties) = value {
+                    for property in properties.values_mut() {
+                        substitute_property(variables, property)?;
+                    }
+                }
+            }
+            _ if !key.starts_with('$') => substitute_node(variables, value)?,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Properties can either be given as a bare value or as an object with an
+/// explicit `Type` and `Value`, so both shapes are handled here.
+fn substitute_property(
+    variables: &HashMap<String, Value>,
+    property: &mut Value,
+) -> Result<(), VariableError> {
+    match property {
+        Value::String(_) => substitute_value(variables, property),
+        Value::Object(explicit) => match explicit.get_mut("Value") {
+            Some(value) if value.is_string() => substitute_value(variables, value),
+            _ => Ok(()),
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Substitutes variables into a JSON string value.
+///
+/// Strings made up of exactly one reference to a variable are replaced with
+/// the variable's value as-is, which lets variables hold numbers and booleans
+/// for properties like `Workspace.Gravity`.
+fn substitute_value(
+    variables: &HashMap<String, Value>,
+    value: &mut Value,
+) -> Result<(), VariableError> {
+    let text = match value {
+        Value::String(text) => text,
+        _ => return Ok(()),
+    };
+
+    if let Some(name) = whole_reference(text) {
+        if let Some(env_name) = env_name(name) {
+            *value = env_value(env_name, text)?;
+            return Ok(());
+        }
+
+        if let Some(variable) = variables.get(name) {
+            *value = variable.clone();
+            return Ok(());
+        }
+    }
+
+    *text = substitute_str(variables, text)?;
+    Ok(())
+}
+
+/// If the text is nothing but a single `${NAME}` reference, returns the name.
+fn whole_reference(text: &str) -> Option<&str> {
+    if !text.starts_with("${") || !text.ends_with('}') || text.len() < 3 {
+        return None;
+    }
+
+    let name = &text[2..text.len() - 1];
+
+    if name.contains('}') {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+/// Returns the name of the environment variable an `env:NAME` reference
+/// refers to.
+fn env_name(name: &str) -> Option<&str> {
+    let mut parts = name.splitn(2, ':');
+
+    match (parts.next(), parts.next()) {
+        (Some("env"), Some(env_name)) => Some(env_name),
+        _ => None,
+    }
+}
+
+/// Reads an environment variable that makes up a whole value, turning it into
+/// a number or boolean if it reads as one.
+fn env_value(name: &str, text: &str) -> Result<Value, VariableError> {
+    let value = read_env(name, text)?;
+
+    match value.as_str() {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => {}
+    }
+
+    match serde_json::from_str::<serde_json::Number>(&value) {
+        Ok(number) => Ok(Value::Number(number)),
+        Err(_) => Ok(Value::String(value)),
+    }
+}
+
+fn read_env(name: &str, text: &str) -> Result<String, VariableError> {
+    env::var(name).map_err(|_| VariableError::MissingEnvironmentVariable {
+        name: name.to_owned(),
+        text: text.to_owned(),
+    })
+}
+
+/// Replaces every variable reference in the given text.
+fn substitute_str(variables: &HashMap<String, Value>, text: &str) -> Result<String, VariableError> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            output.push_str(&rest[..start - 1]);
+            output.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        output.push_str(&rest[..start]);
+
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| VariableError::Unterminated {
+                text: text.to_owned(),
+            })?;
+
+        let name = &rest[start + 2..start + end];
+        output.push_str(&lookup(variables, name, text)?);
+
+        rest = &rest[start + end + 1..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn lookup(
+    variables: &HashMap<String, Value>,
+    name: &str,
+    text: &str,
+) -> Result<String, VariableError> {
+    if let Some(env_name) = env_name(name) {
+        return read_env(env_name, text);
+    }
+
+    match variables.get(name) {
+        Some(Value::String(value)) => Ok(value.clone()),
+        Some(value) => Ok(value.to_string()),
+        None => Err(VariableError::UnknownVariable {
+            name: name.to_owned(),
+            text: text.to_owned(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::json;
+
+    fn substituted(mut project: Value) -> Value {
+        substitute_project(&mut project).unwrap();
+        project
+    }
+
+    #[test]
+    fn paths_and_properties() {
+        let project = substituted(json!({
+            "name": "variables",
+            "variables": {
+                "ASSETS": "staging-assets",
+                "GRAVITY": 50,
+            },
+            "tree": {
+                "$className": "DataModel",
+                "Workspace": {
+                    "$properties": {
+                        "Gravity": "${GRAVITY}",
+                    },
+                },
+                "ReplicatedStorage": {
+                    "Assets": {
+                        "$path": "${ASSETS}/models",
+                    },
+                    "Label": {
+                        "$className": "StringValue",
+                        "$properties": {
+                            "Value": {
+                                "Type": "String",
+                                "Value": "Gravity is ${GRAVITY} in $${ASSETS}",
+                            },
+                        },
+                    },
+                },
+            },
+        }));
+
+        let tree = &project["tree"];
+        assert_eq!(tree["Workspace"]["$properties"]["Gravity"], json!(50));
+        assert_eq!(
+            tree["ReplicatedStorage"]["Assets"]["$path"],
+            json!("staging-assets/models")
+        );
+        assert_eq!(
+            tree["ReplicatedStorage"]["Label"]["$properties"]["Value"]["Value"],
+            json!("Gravity is 50 in ${ASSETS}")
+        );
+    }
+
+    #[test]
+    fn unknown_variable() {
+        let mut project = json!({
+            "name": "unknown",
+            "variables": {},
+            "tree": {
+                "$path": "${MISSING}/src",
+            },
+        });
+
+        match substitute_project(&mut project) {
+            Err(VariableError::UnknownVariable { name, .. }) => assert_eq!(name, "MISSING"),
+            other => panic!("expected unknown variable error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn project_without_variables() {
+        let original = json!({
+            "name": "no-variables",
+            "tree": {
+                "$className": "DataModel",
+                "ReplicatedStorage": {
+                    "$path": "${not-a-variable}/src",
+                    "Label": {
+                        "$className": "StringValue",
+                        "$properties": {
+                            "Value": "Costs ${5}",
+                        },
+                    },
+                },
+            },
+        });
+
+        assert_eq!(substituted(original.clone()), original);
+    }
+
+    #[test]
+    fn environment_variables() {
+        env::set_var("ROJO_TEST_SERVE_PORT", "34873");
+        env::set_var("ROJO_TEST_ASSET_ROOT", "/assets");
+
+        let project = substituted(json!({
+            "name": "environment",
+            "servePort": "${env:ROJO_TEST_SERVE_PORT}",
+            "variables": {
+                "MODELS": "${env:ROJO_TEST_ASSET_ROOT}/models",
+            },
+            "tree": {
+                "$path": "${MODELS}",
+            },
+        }));
+
+        assert_eq!(project["servePort"], json!(34873));
+        assert_eq!(project["tree"]["$path"], json!("/assets/models"));
+    }
+
+    #[test]
+    fn typed_environment_variables() {
+        env::set_var("ROJO_TEST_GRAVITY", "50");
+        env::set_var("ROJO_TEST_ENABLED", "true");
+        env::set_var("ROJO_TEST_LABEL", "50 studs");
+
+        let project = substituted(json!({
+            "name": "typed-environment",
+            "variables": {
+                "GRAVITY": "${env:ROJO_TEST_GRAVITY}",
+            },
+            "tree": {
+                "$className": "DataModel",
+                "Workspace": {
+                    "$properties": {
+                        "Gravity": "${GRAVITY}",
+                    },
+                },
+                "ReplicatedStorage": {
+                    "Enabled": {
+                        "$className": "BoolValue",
+                        "$properties": {
+                            "Value": "${env:ROJO_TEST_ENABLED}",
+                        },
+                    },
+                    "Label": {
+                        "$className": "StringValue",
+                        "$properties": {
+                            "Value": "${env:ROJO_TEST_LABEL}",
+                        },
+                    },
+                    "Assets": {
+                        "$path": "${GRAVITY}/assets",
+                    },
+                },
+            },
+        }));
+
+        let tree = &project["tree"];
+        assert_eq!(tree["Workspace"]["$properties"]["Gravity"], json!(50));
+
+        let storage = &tree["ReplicatedStorage"];
+        assert_eq!(storage["Enabled"]["$properties"]["Value"], json!(true));
+        assert_eq!(storage["Label"]["$properties"]["Value"], json!("50 studs"));
+        assert_eq!(storage["Assets"]["$path"], json!("50/assets"));
+    }
+
+    #[test]
+    fn invalid_serve_port() {
+        let mut project = json!({
+            "name": "port",
+            "servePort": "${PORT}",
+            "variables": {
+                "PORT": "not a port",
+            },
+            "tree": {},
+        });
+
+        match substitute_project(&mut project) {
+            Err(VariableError::InvalidServePort { value }) => {
+                assert_eq!(value, json!("not a port"));
+            }
+            other => panic!("expected invalid port error, got {:?}", other),
+        }
+    }
+}
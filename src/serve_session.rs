@@ -12,6 +12,7 @@ use thiserror::Error;
 
 use crate::{
     change_processor::ChangeProcessor,
+    error_registry::ErrorRegistry,
     message_queue::MessageQueue,
     project::{Project, ProjectError},
     session_id::SessionId,
@@ -22,6 +23,10 @@ use crate::{
     snapshot_middleware::{snapshot_from_vfs, SnapshotError},
 };
 
+/// How many messages a serve session keeps around for clients that fell
+/// behind. Clients further behind than this need to read the whole tree again.
+const MESSAGE_HISTORY_LIMIT: usize = 1000;
+
 /// Contains all of the state for a Rojo serve session.
 ///
 /// Nothing here is specific to any Rojo interface. Though the primary way to
@@ -79,6 +84,10 @@ pub struct ServeSession {
     /// to be applied.
     message_queue: Arc<MessageQueue<AppliedPatchSet>>,
 
+    /// Errors from files that couldn't be turned into instances since the
+    /// session started, so that they can be shown to users.
+    errors: Arc<ErrorRegistry>,
+
     /// A channel to send mutation requests on. These will be handled by the
     /// ChangeProcessor and trigger changes in the tree.
     tree_mutation_sender: Sender<PatchSet>,
@@ -91,7 +100,14 @@ impl ServeSession {
     /// The project file is expected to be loaded out-of-band since it's
     /// currently loaded from the filesystem directly instead of through the
     /// in-memory filesystem layer.
-    pub fn new<P: AsRef<Path>>(vfs: Vfs, start_path: P) -> Result<Self, ServeSessionError> {
+    ///
+    /// If a profile is given, it's applied to every project that defines it,
+    /// and the root project must be one of them.
+    pub fn new<P: AsRef<Path>>(
+        vfs: Vfs,
+        start_path: P,
+        profile: Option<&str>,
+    ) -> Result<Self, ServeSessionError> {
         let start_path = start_path.as_ref();
         let start_time = Instant::now();
 
@@ -116,6 +132,17 @@ impl ServeSession {
 
         let mut instance_context = InstanceContext::default();
 
+        if let Some(profile) = profile {
+            if !root_project.profiles.contains_key(profile) {
+                return Err(ServeSessionError::UnknownProfile {
+                    profile: profile.to_owned(),
+                    path: root_project.file_location.clone(),
+                });
+            }
+
+            instance_context.profile = Some(profile.to_owned());
+        }
+
         let rules = root_project
             .glob_ignore_paths
             .iter()
@@ -137,10 +164,13 @@ impl ServeSession {
         apply_patch_set(&mut tree, patch_set);
 
         let session_id = SessionId::new();
-        let message_queue = MessageQueue::new();
+        let message_queue = MessageQueue::new()
+            .with_history_limit(MESSAGE_HISTORY_LIMIT)
+            .with_coalescing();
 
         let tree = Arc::new(Mutex::new(tree));
         let message_queue = Arc::new(message_queue);
+        let errors = Arc::new(ErrorRegistry::new());
         let vfs = Arc::new(vfs);
 
         let (tree_mutation_sender, tree_mutation_receiver) = crossbeam_channel::unbounded();
@@ -150,6 +180,7 @@ impl ServeSession {
             Arc::clone(&tree),
             Arc::clone(&vfs),
             Arc::clone(&message_queue),
+            Arc::clone(&errors),
             tree_mutation_receiver,
         );
 
@@ -160,15 +191,12 @@ impl ServeSession {
             root_project,
             tree,
             message_queue,
+            errors,
             tree_mutation_sender,
             vfs,
         })
     }
 
-    pub fn tree_handle(&self) -> Arc<Mutex<RojoTree>> {
-        Arc::clone(&self.tree)
-    }
-
     pub fn tree(&self) -> MutexGuard<'_, RojoTree> {
         self.tree.lock().unwrap()
     }
@@ -177,7 +205,6 @@ impl ServeSession {
         self.tree_mutation_sender.clone()
     }
 
-    #[allow(unused)]
     pub fn vfs(&self) -> &Vfs {
         &self.vfs
     }
@@ -186,6 +213,10 @@ impl ServeSession {
         &self.message_queue
     }
 
+    pub fn errors(&self) -> &ErrorRegistry {
+        &self.errors
+    }
+
     pub fn session_id(&self) -> SessionId {
         self.session_id
     }
@@ -194,6 +225,12 @@ impl ServeSession {
         &self.root_project.name
     }
 
+    /// The folder containing the root project file. Relative paths in the
+    /// project are resolved against this folder.
+    pub fn root_dir(&self) -> &Path {
+        self.root_project.folder_location()
+    }
+
     pub fn project_port(&self) -> Option<u16> {
         self.root_project.serve_port
     }
@@ -216,6 +253,9 @@ pub enum ServeSessionError {
     )]
     NoProjectFound { path: PathBuf },
 
+    #[error("The project at path {} has no profile named {profile}", .path.display())]
+    UnknownProfile { profile: String, path: PathBuf },
+
     #[error(transparent)]
     Project {
         #[from]
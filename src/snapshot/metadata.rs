@@ -6,7 +6,12 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 
-use crate::{glob::Glob, path_serializer, project::ProjectNode};
+use crate::{
+    glob::Glob,
+    path_serializer,
+    project::ProjectNode,
+    snapshot_middleware::{FileRule, MiddlewareRegistry, UserPlugin},
+};
 
 /// Rojo-specific metadata that can be associated with an instance or a snapshot
 /// of an instance.
@@ -103,6 +108,31 @@ impl Default for InstanceMetadata {
 pub struct InstanceContext {
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub path_ignore_rules: Arc<Vec<PathIgnoreRule>>,
+
+    /// Rules, defined in project files, for turning files into instances based
+    /// on how their names end.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub file_rules: Arc<Vec<FileRule>>,
+
+    /// The user plugins, defined in project files, that should get a chance to
+    /// snapshot files before Rojo's built-in middleware.
+    #[serde(skip)]
+    pub user_plugins: Arc<Vec<Arc<UserPlugin>>>,
+
+    /// The profile picked with `--profile`, which is applied to every project
+    /// that defines it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+
+    /// The middleware used to turn paths into instances, which projects can
+    /// change for the files inside of them.
+    #[serde(skip)]
+    pub middleware: Arc<MiddlewareRegistry>,
+
+    /// The folder of the outermost project, which the content URLs of image
+    /// and audio files are relative to.
+    #[serde(skip)]
+    pub asset_root: Option<Arc<Path>>,
 }
 
 impl InstanceContext {
@@ -123,12 +153,55 @@ impl InstanceContext {
         let rules = Arc::make_mut(&mut self.path_ignore_rules);
         rules.extend(new_rules);
     }
+
+    /// Extend the list of file rules in the context with the given rules,
+    /// replacing any existing rules with the same pattern.
+    pub fn add_file_rules<I>(&mut self, new_rules: I)
+    where
+        I: IntoIterator<Item = FileRule>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let new_rules = new_rules.into_iter();
+
+        if new_rules.len() == 0 {
+            return;
+        }
+
+        let rules = Arc::make_mut(&mut self.file_rules);
+
+        for rule in new_rules {
+            rules.retain(|existing| existing.pattern != rule.pattern);
+            rules.push(rule);
+        }
+    }
+
+    /// Extend the list of user plugins in the context with the given plugins.
+    /// Plugins added later run after the existing ones.
+    pub fn add_user_plugins<I>(&mut self, new_plugins: I)
+    where
+        I: IntoIterator<Item = Arc<UserPlugin>>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let new_plugins = new_plugins.into_iter();
+
+        if new_plugins.len() == 0 {
+            return;
+        }
+
+        let plugins = Arc::make_mut(&mut self.user_plugins);
+        plugins.extend(new_plugins);
+    }
 }
 
 impl Default for InstanceContext {
     fn default() -> Self {
         InstanceContext {
             path_ignore_rules: Arc::new(Vec::new()),
+            file_rules: Arc::new(Vec::new()),
+            user_plugins: Arc::new(Vec::new()),
+            profile: None,
+            middleware: MiddlewareRegistry::shared_default(),
+            asset_root: None,
         }
     }
 }
@@ -162,7 +235,7 @@ pub enum InstigatingSource {
     ProjectNode(
         #[serde(serialize_with = "path_serializer::serialize_absolute")] PathBuf,
         String,
-        ProjectNode,
+        Box<ProjectNode>,
         Option<String>,
     ),
 }
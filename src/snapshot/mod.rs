@@ -53,6 +53,7 @@ mod metadata;
 mod patch;
 mod patch_apply;
 mod patch_compute;
+mod patch_conflict;
 mod tree;
 
 pub use instance_snapshot::InstanceSnapshot;
@@ -60,6 +61,7 @@ pub use metadata::*;
 pub use patch::*;
 pub use patch_apply::apply_patch_set;
 pub use patch_compute::compute_patch_set;
+pub use patch_conflict::{find_conflicts, find_update_conflicts};
 pub use tree::*;
 
 #[cfg(test)]
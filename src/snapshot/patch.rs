@@ -5,13 +5,15 @@ use std::collections::HashMap;
 use rbx_dom_weak::{RbxId, RbxValue};
 use serde::{Deserialize, Serialize};
 
+use crate::message_queue::Coalesce;
+
 use super::{InstanceMetadata, InstanceSnapshot};
 
 /// A set of different kinds of patches that can be applied to an RbxTree.
 ///
-/// These patches shouldn't be persisted: there's no mechanism in place to make
-/// sure that another patch wasn't applied before this one that could cause a
-/// conflict!
+/// These patches shouldn't be persisted: only updates that carry previous
+/// values can be checked for conflicts with patches that were applied before
+/// them, using `find_conflicts`.
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PatchSet {
     pub removed_instances: Vec<RbxId>,
@@ -49,6 +51,40 @@ pub struct PatchUpdate {
 
     /// Changed Rojo-specific metadata, if any of it changed.
     pub changed_metadata: Option<InstanceMetadata>,
+
+    /// The name that the author of this patch expects the instance to have
+    /// before the patch is applied, if it knows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_name: Option<String>,
+
+    /// The ClassName that the author of this patch expects the instance to
+    /// have before the patch is applied, if it knows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_class_name: Option<String>,
+
+    /// The values that the author of this patch expects properties to have
+    /// before the patch is applied. Properties that aren't listed here aren't
+    /// checked. A value of `None` means that the property is expected to be
+    /// missing.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub previous_properties: HashMap<String, Option<RbxValue>>,
+}
+
+/// Describes a field of an instance whose value did not match the value that a
+/// `PatchUpdate` expected it to have.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PatchConflict {
+    pub id: RbxId,
+
+    /// The name of the field, which is either `Name`, `ClassName`, or the name
+    /// of a property.
+    pub field: String,
+
+    /// The value the patch expected the field to have.
+    pub expected: Option<RbxValue>,
+
+    /// The value the field actually has.
+    pub current: Option<RbxValue>,
 }
 
 /// Applied patch sets have the same rough shape as PatchSet, but are
@@ -58,14 +94,18 @@ pub struct PatchUpdate {
 /// Applied patch sets are generated by applying a patch to a tree, and are
 /// suitable for sending over the network to a synchronized tree like the Rojo
 /// Studio plugin.
-///
-// TODO: Introduce machinery to detect conflicts, like keeping previous +
-// current values in all fields.
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AppliedPatchSet {
     pub removed: Vec<RbxId>,
     pub added: Vec<RbxId>,
     pub updated: Vec<AppliedPatchUpdate>,
+
+    /// For added instances that a client asked for, maps the ID each one got
+    /// in the tree to the ID the client picked for it. The client already has
+    /// those instances and can use this to match them up instead of creating
+    /// them a second time.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub client_ids: HashMap<RbxId, RbxId>,
 }
 
 impl AppliedPatchSet {
@@ -74,19 +114,55 @@ impl AppliedPatchSet {
             removed: Vec::new(),
             added: Vec::new(),
             updated: Vec::new(),
+            client_ids: HashMap::new(),
         }
     }
 }
 
+/// Consecutive patch sets that only update the same instances, like when a
+/// script is saved over and over, are merged into one, so subscribers that fell
+/// behind only see where each instance ended up.
+impl Coalesce for AppliedPatchSet {
+    fn coalesce(&mut self, next: Self) -> Result<(), Self> {
+        let only_updates = |patch: &Self| patch.added.is_empty() && patch.removed.is_empty();
+
+        let same_instances = next.updated.iter().all(|next_update| {
+            self.updated
+                .iter()
+                .any(|update| update.id == next_update.id)
+        });
+
+        if !only_updates(self) || !only_updates(&next) || !same_instances {
+            return Err(next);
+        }
+
+        for next_update in next.updated {
+            let update = self
+                .updated
+                .iter_mut()
+                .find(|update| update.id == next_update.id)
+                .unwrap();
+
+            update.merge(next_update);
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppliedPatchUpdate {
     pub id: RbxId,
-
-    // TODO: Store previous values in order to detect application conflicts
     pub changed_name: Option<String>,
     pub changed_class_name: Option<String>,
     pub changed_properties: HashMap<String, Option<RbxValue>>,
     pub changed_metadata: Option<InstanceMetadata>,
+
+    /// The values that changed fields had before the patch was applied, which
+    /// lets a synchronized tree tell whether it was up to date.
+    pub previous_name: Option<String>,
+    pub previous_class_name: Option<String>,
+    pub previous_properties: HashMap<String, Option<RbxValue>>,
 }
 
 impl AppliedPatchUpdate {
@@ -97,6 +173,92 @@ impl AppliedPatchUpdate {
             changed_class_name: None,
             changed_properties: HashMap::new(),
             changed_metadata: None,
+            previous_name: None,
+            previous_class_name: None,
+            previous_properties: HashMap::new(),
+        }
+    }
+
+    /// Folds a later update to the same instance into this one. Changed values
+    /// come from the later update, while previous values are kept from the
+    /// earliest update that changed each field.
+    fn merge(&mut self, next: AppliedPatchUpdate) {
+        if next.changed_name.is_some() {
+            self.changed_name = next.changed_name;
+        }
+
+        if next.changed_class_name.is_some() {
+            self.changed_class_name = next.changed_class_name;
+        }
+
+        if next.changed_metadata.is_some() {
+            self.changed_metadata = next.changed_metadata;
+        }
+
+        self.changed_properties.extend(next.changed_properties);
+
+        if self.previous_name.is_none() {
+            self.previous_name = next.previous_name;
+        }
+
+        if self.previous_class_name.is_none() {
+            self.previous_class_name = next.previous_class_name;
         }
+
+        for (key, value) in next.previous_properties {
+            self.previous_properties.entry(key).or_insert(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use maplit::hashmap;
+
+    fn source(value: &str) -> Option<RbxValue> {
+        Some(RbxValue::String {
+            value: value.to_owned(),
+        })
+    }
+
+    fn source_update(id: RbxId, previous: &str, changed: &str) -> AppliedPatchSet {
+        let mut update = AppliedPatchUpdate::new(id);
+        update.changed_properties = hashmap! { "Source".to_owned() => source(changed) };
+        update.previous_properties = hashmap! { "Source".to_owned() => source(previous) };
+
+        AppliedPatchSet {
+            updated: vec![update],
+            ..AppliedPatchSet::new()
+        }
+    }
+
+    #[test]
+    fn coalesce_updates_to_same_instance() {
+        let id = RbxId::new();
+
+        let mut patch = source_update(id, "one", "two");
+        patch.coalesce(source_update(id, "two", "three")).unwrap();
+
+        assert_eq!(patch.updated.len(), 1);
+
+        let update = &patch.updated[0];
+        assert_eq!(update.changed_properties["Source"], source("three"));
+        assert_eq!(update.previous_properties["Source"], source("one"));
+    }
+
+    #[test]
+    fn coalesce_keeps_other_patches() {
+        let id = RbxId::new();
+
+        let mut patch = source_update(id, "one", "two");
+        assert!(patch
+            .coalesce(source_update(RbxId::new(), "two", "three"))
+            .is_err());
+
+        let mut added = AppliedPatchSet::new();
+        added.added.push(RbxId::new());
+        assert!(patch.coalesce(added).is_err());
     }
 }
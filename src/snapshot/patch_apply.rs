@@ -1,6 +1,6 @@
 //! Defines the algorithm for applying generated patches.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, mem};
 
 use rbx_dom_weak::{RbxId, RbxInstanceProperties, RbxValue};
 
@@ -175,16 +175,23 @@ fn apply_update_child(context: &mut PatchApplyContext, tree: &mut RojoTree, patc
     };
 
     if let Some(name) = patch.changed_name {
-        *instance.name_mut() = name.clone();
+        let previous_name = mem::replace(instance.name_mut(), name.clone());
+        applied_patch.previous_name = Some(previous_name);
         applied_patch.changed_name = Some(name);
     }
 
     if let Some(class_name) = patch.changed_class_name {
-        *instance.class_name_mut() = class_name.clone();
+        let previous_class_name = mem::replace(instance.class_name_mut(), class_name.clone());
+        applied_patch.previous_class_name = Some(previous_class_name);
         applied_patch.changed_class_name = Some(class_name);
     }
 
     for (key, property_entry) in patch.changed_properties {
+        let previous_value = instance.properties().get(&key).cloned();
+        applied_patch
+            .previous_properties
+            .insert(key.clone(), previous_value);
+
         match property_entry {
             // Ref values need to be potentially rewritten from snapshot IDs to
             // instance IDs if they referred to an instance that was created as
@@ -312,6 +319,9 @@ mod test {
                 "Baz".to_owned() => Some(RbxValue::Int32 { value: 10 }),
             },
             changed_metadata: None,
+            previous_name: None,
+            previous_class_name: None,
+            previous_properties: HashMap::new(),
         };
 
         let patch_set = PatchSet {
@@ -147,6 +147,9 @@ fn compute_property_patches(
         changed_class_name,
         changed_properties,
         changed_metadata,
+        previous_name: None,
+        previous_class_name: None,
+        previous_properties: HashMap::new(),
     });
 }
 
@@ -275,6 +278,9 @@ mod test {
                     }),
                 },
                 changed_metadata: None,
+                previous_name: None,
+                previous_class_name: None,
+                previous_properties: HashMap::new(),
             }],
             added_instances: Vec::new(),
             removed_instances: Vec::new(),
@@ -0,0 +1,211 @@
+//! Defines how updates are checked against the tree to find changes that were
+//! made after the author of the update last saw the instance.
+
+use rbx_dom_weak::RbxValue;
+
+use super::{
+    patch::{PatchConflict, PatchSet, PatchUpdate},
+    RojoTree,
+};
+
+/// Compares the previous values that updates in the given `PatchSet` expect
+/// against the current contents of the tree, returning every field that does
+/// not match.
+pub fn find_conflicts(tree: &RojoTree, patch_set: &PatchSet) -> Vec<PatchConflict> {
+    patch_set
+        .updated_instances
+        .iter()
+        .flat_map(|update| find_update_conflicts(tree, update))
+        .collect()
+}
+
+/// Finds conflicts for a single `PatchUpdate`. Updates for instances that no
+/// longer exist have no conflicts, since there's nothing for them to clobber.
+pub fn find_update_conflicts(tree: &RojoTree, update: &PatchUpdate) -> Vec<PatchConflict> {
+    let instance = match tree.get_instance(update.id) {
+        Some(instance) => instance,
+        None => return Vec::new(),
+    };
+
+    let mut conflicts = Vec::new();
+
+    let mut check = |field: &str, expected: Option<&RbxValue>, current: Option<&RbxValue>| {
+        if expected != current {
+            conflicts.push(PatchConflict {
+                id: update.id,
+                field: field.to_owned(),
+                expected: expected.cloned(),
+                current: current.cloned(),
+            });
+        }
+    };
+
+    if let Some(expected) = &update.previous_name {
+        check(
+            "Name",
+            Some(&string_value(expected)),
+            Some(&string_value(instance.name())),
+        );
+    }
+
+    if let Some(expected) = &update.previous_class_name {
+        check(
+            "ClassName",
+            Some(&string_value(expected)),
+            Some(&string_value(instance.class_name())),
+        );
+    }
+
+    let mut keys: Vec<&String> = update.previous_properties.keys().collect();
+    keys.sort();
+
+    for key in keys {
+        check(
+            key,
+            update.previous_properties[key].as_ref(),
+            instance.properties().get(key),
+        );
+    }
+
+    conflicts
+}
+
+fn string_value(value: &str) -> RbxValue {
+    RbxValue::String {
+        value: value.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use maplit::hashmap;
+    use rbx_dom_weak::RbxInstanceProperties;
+
+    use crate::snapshot::InstancePropertiesWithMeta;
+
+    fn source(value: &str) -> RbxValue {
+        RbxValue::String {
+            value: value.to_owned(),
+        }
+    }
+
+    #[test]
+    fn matching_previous_values() {
+        let tree = RojoTree::new(InstancePropertiesWithMeta {
+            properties: RbxInstanceProperties {
+                name: "Main".to_owned(),
+                class_name: "Script".to_owned(),
+                properties: hashmap! {
+                    "Source".to_owned() => source("print(1)"),
+                },
+            },
+            metadata: Default::default(),
+        });
+
+        let patch_set = PatchSet {
+            updated_instances: vec![PatchUpdate {
+                id: tree.get_root_id(),
+                changed_name: Some("Other".to_owned()),
+                changed_class_name: None,
+                changed_properties: hashmap! {
+                    "Source".to_owned() => Some(source("print(2)")),
+                    "Disabled".to_owned() => Some(RbxValue::Bool { value: true }),
+                },
+                changed_metadata: None,
+                previous_name: Some("Main".to_owned()),
+                previous_class_name: Some("Script".to_owned()),
+                previous_properties: hashmap! {
+                    "Source".to_owned() => Some(source("print(1)")),
+                    "Disabled".to_owned() => None,
+                },
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(find_conflicts(&tree, &patch_set), Vec::new());
+    }
+
+    #[test]
+    fn changed_previous_values() {
+        let tree = RojoTree::new(InstancePropertiesWithMeta {
+            properties: RbxInstanceProperties {
+                name: "Main".to_owned(),
+                class_name: "Script".to_owned(),
+                properties: hashmap! {
+                    "Source".to_owned() => source("print(\"edited\")"),
+                },
+            },
+            metadata: Default::default(),
+        });
+
+        let root_id = tree.get_root_id();
+
+        let patch_set = PatchSet {
+            updated_instances: vec![PatchUpdate {
+                id: root_id,
+                changed_name: None,
+                changed_class_name: None,
+                changed_properties: hashmap! {
+                    "Source".to_owned() => Some(source("print(2)")),
+                },
+                changed_metadata: None,
+                previous_name: Some("Old".to_owned()),
+                previous_class_name: None,
+                previous_properties: hashmap! {
+                    "Source".to_owned() => Some(source("print(1)")),
+                },
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            find_conflicts(&tree, &patch_set),
+            vec![
+                PatchConflict {
+                    id: root_id,
+                    field: "Name".to_owned(),
+                    expected: Some(source("Old")),
+                    current: Some(source("Main")),
+                },
+                PatchConflict {
+                    id: root_id,
+                    field: "Source".to_owned(),
+                    expected: Some(source("print(1)")),
+                    current: Some(source("print(\"edited\")")),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unchecked_update() {
+        let tree = RojoTree::new(InstancePropertiesWithMeta {
+            properties: RbxInstanceProperties {
+                name: "Main".to_owned(),
+                class_name: "Script".to_owned(),
+                properties: HashMap::new(),
+            },
+            metadata: Default::default(),
+        });
+
+        let patch_set = PatchSet {
+            updated_instances: vec![PatchUpdate {
+                id: tree.get_root_id(),
+                changed_name: Some("Other".to_owned()),
+                changed_class_name: None,
+                changed_properties: HashMap::new(),
+                changed_metadata: None,
+                previous_name: None,
+                previous_class_name: None,
+                previous_properties: HashMap::new(),
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(find_conflicts(&tree, &patch_set), Vec::new());
+    }
+}
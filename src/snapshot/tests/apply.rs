@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use insta::assert_yaml_snapshot;
 use maplit::hashmap;
 use rbx_dom_weak::{RbxInstanceProperties, RbxValue};
@@ -23,6 +25,9 @@ fn set_name_and_class_name() {
             changed_class_name: Some("Folder".to_owned()),
             changed_properties: Default::default(),
             changed_metadata: None,
+            previous_name: None,
+            previous_class_name: None,
+            previous_properties: HashMap::new(),
         }],
         ..Default::default()
     };
@@ -54,6 +59,9 @@ fn add_property() {
                 }),
             },
             changed_metadata: None,
+            previous_name: None,
+            previous_class_name: None,
+            previous_properties: HashMap::new(),
         }],
         ..Default::default()
     };
@@ -98,6 +106,9 @@ fn remove_property() {
                 "Foo".to_owned() => None,
             },
             changed_metadata: None,
+            previous_name: None,
+            previous_class_name: None,
+            previous_properties: HashMap::new(),
         }],
         ..Default::default()
     };
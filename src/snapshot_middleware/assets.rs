@@ -0,0 +1,267 @@
+//! Turns image and audio files into instances that show or play them, like
+//! Decals and Sounds.
+//!
+//! Rojo can't upload assets, so the content property of each instance points
+//! at its file with an `rbxasset://rojo/` URL, relative to the folder of the
+//! outermost project. Studio loads these URLs from the `content/rojo` folder of
+//! its install. Assets that have been uploaded can set their content ID in
+//! their `.meta.json` file instead.
+
+use std::path::{Component, Path};
+
+use maplit::hashmap;
+use memofs::Vfs;
+use rbx_dom_weak::RbxValue;
+
+use crate::snapshot::{InstanceContext, InstanceMetadata, InstanceSnapshot};
+
+use super::{
+    error::SnapshotError,
+    meta_file::{apply_read_metadata, AdjacentMetadata},
+    middleware::{SnapshotInstanceResult, SnapshotMiddleware},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AssetKind {
+    Image,
+    Audio,
+}
+
+impl AssetKind {
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "png" | "jpg" | "jpeg" => Some(AssetKind::Image),
+            "ogg" | "mp3" => Some(AssetKind::Audio),
+            _ => None,
+        }
+    }
+
+    /// The class that assets of this kind turn into unless their `.meta.json`
+    /// file picks another one.
+    fn default_class(self) -> &'static str {
+        match self {
+            AssetKind::Image => "Decal",
+            AssetKind::Audio => "Sound",
+        }
+    }
+
+    /// Returns the property of the given class that holds assets of this kind,
+    /// if the class can hold them.
+    fn content_property(self, class_name: &str) -> Option<&'static str> {
+        match (self, class_name) {
+            (AssetKind::Image, "Decal") | (AssetKind::Image, "Texture") => Some("Texture"),
+            (AssetKind::Image, "ImageLabel") | (AssetKind::Image, "ImageButton") => Some("Image"),
+            (AssetKind::Audio, "Sound") => Some("SoundId"),
+            _ => None,
+        }
+    }
+}
+
+pub struct SnapshotAsset;
+
+impl SnapshotMiddleware for SnapshotAsset {
+    fn from_vfs(context: &InstanceContext, vfs: &Vfs, path: &Path) -> SnapshotInstanceResult {
+        let meta = vfs.metadata(path)?;
+
+        if meta.is_dir() {
+            return Ok(None);
+        }
+
+        let kind = match AssetKind::from_path(path) {
+            Some(kind) => kind,
+            None => return Ok(None),
+        };
+
+        let instance_name = path
+            .file_stem()
+            .ok_or_else(|| SnapshotError::file_name_missing(path))?
+            .to_str()
+            .ok_or_else(|| SnapshotError::file_name_bad_unicode(path))?;
+
+        let mut meta = AdjacentMetadata::read_adjacent(vfs, path, instance_name)?;
+        let class_name = meta
+            .as_mut()
+            .and_then(|meta| meta.class_name.take())
+            .unwrap_or_else(|| kind.default_class().to_owned());
+
+        let property = kind
+            .content_property(&class_name)
+            .ok_or_else(|| SnapshotError::unsupported_asset_class(&class_name, path))?;
+
+        let url = format!(
+            "rbxasset://rojo/{}",
+            relative_asset_path(context.asset_root.as_deref(), path)
+        );
+
+        let mut snapshot = InstanceSnapshot::new()
+            .name(instance_name)
+            .class_name(class_name)
+            .properties(hashmap! {
+                property.to_owned() => RbxValue::Content { value: url },
+            })
+            .metadata(
+                InstanceMetadata::new()
+                    .instigating_source(path)
+                    .relevant_paths(vec![path.to_path_buf()])
+                    .context(context),
+            );
+
+        apply_read_metadata(path, meta, &mut snapshot)?;
+
+        Ok(Some(snapshot))
+    }
+}
+
+/// Returns the property that holds the content of an instance of the given
+/// class made from the asset file at `path`, if the file is an asset.
+pub fn asset_content_property(path: &Path, class_name: &str) -> Option<&'static str> {
+    AssetKind::from_path(path)?.content_property(class_name)
+}
+
+/// Returns the path of an asset relative to `root`, separated by forward
+/// slashes no matter the platform, which is how assets are named in content
+/// URLs and in the asset manifest written by `rojo build`.
+pub fn relative_asset_path(root: Option<&Path>, path: &Path) -> String {
+    let relative = root
+        .and_then(|root| path.strip_prefix(root).ok())
+        .unwrap_or(path);
+
+    relative
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use memofs::{InMemoryFs, VfsSnapshot};
+
+    fn context_with_root(root: &str) -> InstanceContext {
+        InstanceContext {
+            asset_root: Some(Arc::from(Path::new(root))),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn image_from_vfs() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/game/ui/icon.png", VfsSnapshot::file(&[0x89, 0x50][..]))
+            .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let instance_snapshot = SnapshotAsset::from_vfs(
+            &context_with_root("/game"),
+            &vfs,
+            Path::new("/game/ui/icon.png"),
+        )
+        .unwrap()
+        .unwrap();
+
+        insta::assert_yaml_snapshot!(instance_snapshot);
+    }
+
+    #[test]
+    fn image_with_class_from_meta() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/game/icon.png", VfsSnapshot::file(&[0x89, 0x50][..]))
+            .unwrap();
+        imfs.load_snapshot(
+            "/game/icon.meta.json",
+            VfsSnapshot::file(r#"{ "className": "ImageLabel" }"#),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let instance_snapshot = SnapshotAsset::from_vfs(
+            &context_with_root("/game"),
+            &vfs,
+            Path::new("/game/icon.png"),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(instance_snapshot.class_name, "ImageLabel");
+        assert_eq!(
+            instance_snapshot.properties.get("Image"),
+            Some(&RbxValue::Content {
+                value: "rbxasset://rojo/icon.png".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn sound_with_uploaded_id() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/game/boom.ogg", VfsSnapshot::file(&[0x4f, 0x67][..]))
+            .unwrap();
+        imfs.load_snapshot(
+            "/game/boom.meta.json",
+            VfsSnapshot::file(
+                r#"
+                    {
+                      "properties": {
+                        "SoundId": "rbxassetid://1234"
+                      }
+                    }
+                "#,
+            ),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let instance_snapshot = SnapshotAsset::from_vfs(
+            &context_with_root("/game"),
+            &vfs,
+            Path::new("/game/boom.ogg"),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(instance_snapshot.class_name, "Sound");
+        assert_eq!(
+            instance_snapshot.properties.get("SoundId"),
+            Some(&RbxValue::Content {
+                value: "rbxassetid://1234".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn unsupported_class() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/game/boom.mp3", VfsSnapshot::file(&[0x49, 0x44][..]))
+            .unwrap();
+        imfs.load_snapshot(
+            "/game/boom.meta.json",
+            VfsSnapshot::file(r#"{ "className": "Decal" }"#),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let result = SnapshotAsset::from_vfs(
+            &context_with_root("/game"),
+            &vfs,
+            Path::new("/game/boom.mp3"),
+        );
+
+        match result {
+            Err(SnapshotError::UnsupportedAssetClass { class_name, .. }) => {
+                assert_eq!(class_name, "Decal");
+            }
+            other => panic!("expected unsupported asset class error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,416 @@
+//! Turns the `attributes` of meta files and JSON models into the binary format
+//! that Roblox stores instance attributes in, which lives in the
+//! `AttributesSerialize` property.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryInto,
+    path::Path,
+};
+
+use rbx_dom_weak::{AmbiguousRbxValue, RbxValue, UnresolvedRbxValue};
+
+use super::error::SnapshotError;
+
+/// The property that Roblox serializes attributes into.
+pub const ATTRIBUTES_PROPERTY: &str = "AttributesSerialize";
+
+/// Resolves and encodes the given attributes into a value for the
+/// `AttributesSerialize` property.
+///
+/// Values without a type become the type that Studio would use for them:
+/// numbers become doubles, strings stay strings, and arrays of two or three
+/// numbers become a Vector2 or Vector3. Other types, like Color3 or UDim, need
+/// to be given with an explicit `Type`.
+pub fn encode_attributes<'a>(
+    attributes: impl IntoIterator<Item = (&'a String, &'a UnresolvedRbxValue)>,
+    path: &Path,
+) -> Result<RbxValue, SnapshotError> {
+    let mut entries = BTreeMap::new();
+    add_entries(&mut entries, attributes, path)?;
+
+    Ok(encode_entries(&entries))
+}
+
+/// Adds the given attributes to the ones already in the `AttributesSerialize`
+/// property of `properties`, like ones from a model file. Attributes that are
+/// already there are replaced.
+pub fn add_attributes<'a>(
+    properties: &mut HashMap<String, RbxValue>,
+    attributes: impl IntoIterator<Item = (&'a String, &'a UnresolvedRbxValue)>,
+    path: &Path,
+) -> Result<(), SnapshotError> {
+    let mut entries = match properties.get(ATTRIBUTES_PROPERTY) {
+        Some(RbxValue::BinaryString { value }) => decode_entries(value).unwrap_or_else(|| {
+            log::warn!(
+                "Existing attributes of the instance for {} couldn't be read and were replaced",
+                path.display()
+            );
+            BTreeMap::new()
+        }),
+        _ => BTreeMap::new(),
+    };
+
+    add_entries(&mut entries, attributes, path)?;
+    properties.insert(ATTRIBUTES_PROPERTY.to_owned(), encode_entries(&entries));
+
+    Ok(())
+}
+
+/// Encodes attributes into the bytes of their values, keyed by name.
+fn add_entries<'a>(
+    entries: &mut BTreeMap<String, Vec<u8>>,
+    attributes: impl IntoIterator<Item = (&'a String, &'a UnresolvedRbxValue)>,
+    path: &Path,
+) -> Result<(), SnapshotError> {
+    for (name, value) in attributes {
+        let mut output = Vec::new();
+        write_value(&mut output, &resolve(value)).map_err(|type_name| {
+            SnapshotError::unsupported_attribute_type(name, type_name, path)
+        })?;
+
+        entries.insert(name.clone(), output);
+    }
+
+    Ok(())
+}
+
+/// Attributes are sorted by name so that the same attributes always encode to
+/// the same bytes.
+fn encode_entries(entries: &BTreeMap<String, Vec<u8>>) -> RbxValue {
+    let mut output = Vec::new();
+    write_u32(&mut output, entries.len() as u32);
+
+    for (name, value) in entries {
+        write_string(&mut output, name);
+        output.extend_from_slice(value);
+    }
+
+    RbxValue::BinaryString { value: output }
+}
+
+/// Splits encoded attributes back into the bytes of each value, keyed by
+/// name. Gives back `None` if the attributes hold a type we don't know the
+/// size of.
+fn decode_entries(input: &[u8]) -> Option<BTreeMap<String, Vec<u8>>> {
+    let mut reader = Reader { input };
+    let mut entries = BTreeMap::new();
+
+    for _ in 0..reader.read_u32()? {
+        let name_len = reader.read_u32()?;
+        let name = String::from_utf8(reader.read(name_len as usize)?.to_vec()).ok()?;
+
+        let start = reader.input;
+        let type_id = reader.read(1)?[0];
+
+        match type_id {
+            0x02 => {
+                let len = reader.read_u32()?;
+                reader.read(len as usize)?;
+            }
+            0x03 => {
+                reader.read(1)?;
+            }
+            0x06 | 0x09 | 0x10 | 0x1B => {
+                reader.read(8)?;
+            }
+            0x0A | 0x1C => {
+                reader.read(16)?;
+            }
+            0x0E => {
+                reader.read(4)?;
+            }
+            0x0F | 0x11 => {
+                reader.read(12)?;
+            }
+            0x14 => {
+                reader.read(12)?;
+
+                if reader.read(1)?[0] == 0 {
+                    reader.read(36)?;
+                }
+            }
+            0x17 => {
+                let count = reader.read_u32()?;
+                reader.read(count as usize * 12)?;
+            }
+            0x19 => {
+                let count = reader.read_u32()?;
+                reader.read(count as usize * 20)?;
+            }
+            _ => return None,
+        }
+
+        let value_len = start.len() - reader.input.len();
+        entries.insert(name, start[..value_len].to_vec());
+    }
+
+    Some(entries)
+}
+
+struct Reader<'a> {
+    input: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn read(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.input.len() < len {
+            return None;
+        }
+
+        let (bytes, rest) = self.input.split_at(len);
+        self.input = rest;
+        Some(bytes)
+    }
+
+    fn read_u32(&mut self) -> Option<u32> {
+        let bytes = self.read(4)?;
+        Some(u32::from_le_bytes(bytes.try_into().ok()?))
+    }
+}
+
+fn resolve(value: &UnresolvedRbxValue) -> RbxValue {
+    match value {
+        UnresolvedRbxValue::Concrete(value) => value.clone(),
+        UnresolvedRbxValue::Ambiguous(value) => match value {
+            AmbiguousRbxValue::String(value) => RbxValue::String {
+                value: value.clone(),
+            },
+            AmbiguousRbxValue::Float1(value) => RbxValue::Float64 { value: *value },
+            AmbiguousRbxValue::Float2(x, y) => RbxValue::Vector2 {
+                value: [*x as f32, *y as f32],
+            },
+            AmbiguousRbxValue::Float3(x, y, z) => RbxValue::Vector3 {
+                value: [*x as f32, *y as f32, *z as f32],
+            },
+        },
+    }
+}
+
+/// Writes the type ID and contents of a single attribute value. Values that
+/// attributes can't hold give back the name of their type.
+fn write_value(output: &mut Vec<u8>, value: &RbxValue) -> Result<(), String> {
+    match value {
+        RbxValue::String { value } => {
+            output.push(0x02);
+            write_string(output, value);
+        }
+        RbxValue::Bool { value } => {
+            output.push(0x03);
+            output.push(*value as u8);
+        }
+        RbxValue::Float32 { value } => write_double(output, f64::from(*value)),
+        RbxValue::Float64 { value } => write_double(output, *value),
+        RbxValue::Int32 { value } => write_double(output, f64::from(*value)),
+        RbxValue::Int64 { value } => write_double(output, *value as f64),
+        RbxValue::UDim { value } => {
+            output.push(0x09);
+            write_udim(output, value.0, value.1);
+        }
+        RbxValue::UDim2 { value } => {
+            output.push(0x0A);
+            write_udim(output, value.0, value.1);
+            write_udim(output, value.2, value.3);
+        }
+        RbxValue::BrickColor { value } => {
+            output.push(0x0E);
+            write_u32(output, *value as u32);
+        }
+        RbxValue::Color3 { value } => {
+            output.push(0x0F);
+            write_floats(output, value);
+        }
+        RbxValue::Vector2 { value } => {
+            output.push(0x10);
+            write_floats(output, value);
+        }
+        RbxValue::Vector3 { value } => {
+            output.push(0x11);
+            write_floats(output, value);
+        }
+        RbxValue::CFrame { value } => {
+            // The position comes first, followed by a rotation ID of zero to
+            // say that the full rotation matrix follows.
+            output.push(0x14);
+            write_floats(output, &value[..3]);
+            output.push(0);
+            write_floats(output, &value[3..]);
+        }
+        RbxValue::NumberSequence { value } => {
+            output.push(0x17);
+            write_u32(output, value.keypoints.len() as u32);
+
+            for keypoint in &value.keypoints {
+                write_floats(output, &[keypoint.envelope, keypoint.time, keypoint.value]);
+            }
+        }
+        RbxValue::ColorSequence { value } => {
+            output.push(0x19);
+            write_u32(output, value.keypoints.len() as u32);
+
+            for keypoint in &value.keypoints {
+                // ColorSequence keypoints have an envelope in the format, but
+                // it's always zero.
+                write_floats(output, &[0.0, keypoint.time]);
+                write_floats(output, &keypoint.color);
+            }
+        }
+        RbxValue::NumberRange { value } => {
+            output.push(0x1B);
+            write_floats(output, &[value.0, value.1]);
+        }
+        RbxValue::Rect { value } => {
+            output.push(0x1C);
+            write_floats(
+                output,
+                &[value.min.0, value.min.1, value.max.0, value.max.1],
+            );
+        }
+        other => return Err(format!("{:?}", other.get_type())),
+    }
+
+    Ok(())
+}
+
+fn write_double(output: &mut Vec<u8>, value: f64) {
+    output.push(0x06);
+    output.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_udim(output: &mut Vec<u8>, scale: f32, offset: i32) {
+    output.extend_from_slice(&scale.to_le_bytes());
+    output.extend_from_slice(&offset.to_le_bytes());
+}
+
+fn write_floats(output: &mut Vec<u8>, values: &[f32]) {
+    for value in values {
+        output.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn write_u32(output: &mut Vec<u8>, value: u32) {
+    output.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_string(output: &mut Vec<u8>, value: &str) {
+    write_u32(output, value.len() as u32);
+    output.extend_from_slice(value.as_bytes());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    fn encode(json: &str) -> Result<Vec<u8>, SnapshotError> {
+        let attributes: HashMap<String, UnresolvedRbxValue> = serde_json::from_str(json).unwrap();
+
+        match encode_attributes(&attributes, Path::new("/foo.meta.json"))? {
+            RbxValue::BinaryString { value } => Ok(value),
+            other => panic!("expected a BinaryString, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(encode("{}").unwrap(), vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn inferred_types() {
+        let encoded = encode(
+            r#"{
+                "Speed": 16,
+                "Label": "Hi",
+                "Enabled": true
+            }"#,
+        )
+        .unwrap();
+
+        let mut expected = vec![3, 0, 0, 0];
+
+        expected.extend_from_slice(&[7, 0, 0, 0]);
+        expected.extend_from_slice(b"Enabled");
+        expected.extend_from_slice(&[0x03, 1]);
+
+        expected.extend_from_slice(&[5, 0, 0, 0]);
+        expected.extend_from_slice(b"Label");
+        expected.extend_from_slice(&[0x02, 2, 0, 0, 0]);
+        expected.extend_from_slice(b"Hi");
+
+        expected.extend_from_slice(&[5, 0, 0, 0]);
+        expected.extend_from_slice(b"Speed");
+        expected.push(0x06);
+        expected.extend_from_slice(&16.0f64.to_le_bytes());
+
+        assert_eq!(encoded, expected);
+    }
+
+    #[test]
+    fn explicit_type() {
+        let encoded = encode(
+            r#"{
+                "Tint": {
+                    "Type": "Color3",
+                    "Value": [1, 0.5, 0]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let mut expected = vec![1, 0, 0, 0, 4, 0, 0, 0];
+        expected.extend_from_slice(b"Tint");
+        expected.push(0x0F);
+        expected.extend_from_slice(&1.0f32.to_le_bytes());
+        expected.extend_from_slice(&0.5f32.to_le_bytes());
+        expected.extend_from_slice(&0.0f32.to_le_bytes());
+
+        assert_eq!(encoded, expected);
+    }
+
+    #[test]
+    fn added_to_existing() {
+        let existing: HashMap<String, UnresolvedRbxValue> =
+            serde_json::from_str(r#"{ "Speed": 16, "Label": "Hi" }"#).unwrap();
+        let added: HashMap<String, UnresolvedRbxValue> =
+            serde_json::from_str(r#"{ "Speed": 20, "Enabled": true }"#).unwrap();
+
+        let mut properties = HashMap::new();
+        properties.insert(
+            ATTRIBUTES_PROPERTY.to_owned(),
+            encode_attributes(&existing, Path::new("/foo.rbxmx")).unwrap(),
+        );
+
+        add_attributes(&mut properties, &added, Path::new("/foo.meta.json")).unwrap();
+
+        assert_eq!(
+            properties[ATTRIBUTES_PROPERTY],
+            encode(r#"{ "Speed": 20, "Label": "Hi", "Enabled": true }"#)
+                .map(|value| RbxValue::BinaryString { value })
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn unsupported_type() {
+        let result = encode(
+            r#"{
+                "Target": {
+                    "Type": "Ref",
+                    "Value": null
+                }
+            }"#,
+        );
+
+        match result {
+            Err(SnapshotError::UnsupportedAttributeType {
+                name, type_name, ..
+            }) => {
+                assert_eq!(name, "Target");
+                assert_eq!(type_name, "Ref");
+            }
+            other => panic!("expected unsupported attribute error, got {:?}", other),
+        }
+    }
+}
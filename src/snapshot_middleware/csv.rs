@@ -1,15 +1,20 @@
-use std::{collections::BTreeMap, path::Path};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
 
 use maplit::hashmap;
-use memofs::{IoResultExt, Vfs};
+use memofs::Vfs;
 use rbx_dom_weak::RbxValue;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 use crate::snapshot::{InstanceContext, InstanceMetadata, InstanceSnapshot};
 
 use super::{
     error::SnapshotError,
-    meta_file::AdjacentMetadata,
+    meta_file::{apply_read_metadata, AdjacentMetadata},
     middleware::{SnapshotInstanceResult, SnapshotMiddleware},
     util::match_file_name,
 };
@@ -28,13 +33,27 @@ impl SnapshotMiddleware for SnapshotCsv {
             Some(name) => name,
             None => return Ok(None),
         };
-
-        let meta_path = path.with_file_name(format!("{}.meta.json", instance_name));
         let contents = vfs.read(path)?;
 
-        let table_contents = convert_localization_csv(&contents)
+        let (table_contents, problems) = convert_and_validate_localization_csv(&contents)
             .map_err(|source| SnapshotError::malformed_l10n_csv(source, path))?;
 
+        let mut meta = AdjacentMetadata::read_adjacent(vfs, path, instance_name)?;
+        let strict = meta
+            .as_mut()
+            .and_then(|meta| meta.strict.take())
+            .unwrap_or(false);
+
+        if !problems.is_empty() {
+            if strict {
+                return Err(SnapshotError::invalid_l10n_csv(problems, path));
+            }
+
+            for problem in &problems {
+                log::warn!("{}: {}", path.display(), problem);
+            }
+        }
+
         let mut snapshot = InstanceSnapshot::new()
             .name(instance_name)
             .class_name("LocalizationTable")
@@ -46,13 +65,10 @@ impl SnapshotMiddleware for SnapshotCsv {
             .metadata(
                 InstanceMetadata::new()
                     .instigating_source(path)
-                    .relevant_paths(vec![path.to_path_buf(), meta_path.clone()]),
+                    .relevant_paths(vec![path.to_path_buf()]),
             );
 
-        if let Some(meta_contents) = vfs.read(&meta_path).with_not_found()? {
-            let mut metadata = AdjacentMetadata::from_slice(&meta_contents, &meta_path)?;
-            metadata.apply_all(&mut snapshot);
-        }
+        apply_read_metadata(path, meta, &mut snapshot)?;
 
         Ok(Some(snapshot))
     }
@@ -61,24 +77,72 @@ impl SnapshotMiddleware for SnapshotCsv {
 /// Struct that holds any valid row from a Roblox CSV translation table.
 ///
 /// We manually deserialize into this table from CSV, but let serde_json handle
-/// serialization.
-#[derive(Debug, Default, Serialize)]
+/// serialization. `.loc.json` files are deserialized into it directly.
+#[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct LocalizationEntry<'a> {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    key: Option<&'a str>,
+pub(super) struct LocalizationEntry<'a> {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<Cow<'a, str>>,
 
-    #[serde(skip_serializing_if = "Option::is_none")]
-    context: Option<&'a str>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<Cow<'a, str>>,
 
-    #[serde(skip_serializing_if = "Option::is_none")]
-    example: Option<&'a str>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub example: Option<Cow<'a, str>>,
 
-    #[serde(skip_serializing_if = "Option::is_none")]
-    source: Option<&'a str>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<Cow<'a, str>>,
 
     // We use a BTreeMap here to get deterministic output order.
-    values: BTreeMap<&'a str, &'a str>,
+    #[serde(default)]
+    pub values: BTreeMap<Cow<'a, str>, Cow<'a, str>>,
+}
+
+/// A problem found in a localization CSV. These are warnings unless strict mode
+/// is turned on in the CSV's `.meta.json` file, in which case they're errors.
+///
+/// Rows are counted by line in the file, starting from 1 at the header row.
+/// Columns are counted from 1.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum LocalizationCsvProblem {
+    #[error("row {row}: key \"{key}\" was already used on row {first_row}")]
+    DuplicateKey {
+        key: String,
+        row: u64,
+        first_row: u64,
+    },
+
+    #[error("column {column}: \"{locale}\" is not a known locale")]
+    UnknownLocale { locale: String, column: usize },
+
+    #[error("row {row}: rows need a Key or a Source")]
+    MissingKeyAndSource { row: u64 },
+}
+
+/// Languages that Roblox can translate games into.
+const LANGUAGE_CODES: &[&str] = &[
+    "ar", "bg", "bn", "bs", "cs", "da", "de", "el", "en", "es", "et", "fi", "fil", "fr", "he",
+    "hi", "hr", "hu", "id", "it", "ja", "ka", "kk", "km", "ko", "lt", "lv", "ms", "my", "nb", "nl",
+    "pl", "pt", "ro", "ru", "si", "sk", "sl", "sq", "sr", "sv", "th", "tr", "uk", "vi", "zh",
+];
+
+/// Tells whether a column header names a locale, like `es` or `pt-br`.
+fn is_locale_id(header: &str) -> bool {
+    let header = header.to_ascii_lowercase();
+    let mut parts = header.splitn(2, '-');
+
+    let language = parts.next().unwrap_or("");
+    if !LANGUAGE_CODES.contains(&language) {
+        return false;
+    }
+
+    match parts.next() {
+        Some(region) => {
+            (2..=4).contains(&region.len())
+                && region.chars().all(|char| char.is_ascii_alphanumeric())
+        }
+        None => true,
+    }
 }
 
 /// Normally, we'd be able to let the csv crate construct our struct for us.
@@ -90,7 +154,17 @@ struct LocalizationEntry<'a> {
 /// https://github.com/BurntSushi/rust-csv/issues/151
 ///
 /// This function operates in one step in order to minimize data-copying.
-fn convert_localization_csv(contents: &[u8]) -> Result<String, csv::Error> {
+pub fn convert_localization_csv(contents: &[u8]) -> Result<String, csv::Error> {
+    let (encoded, _problems) = convert_and_validate_localization_csv(contents)?;
+
+    Ok(encoded)
+}
+
+/// Converts a localization CSV like `convert_localization_csv`, also returning
+/// any problems found in it.
+pub fn convert_and_validate_localization_csv(
+    contents: &[u8],
+) -> Result<(String, Vec<LocalizationCsvProblem>), csv::Error> {
     let mut reader = csv::Reader::from_reader(contents);
 
     let headers = reader.headers()?.clone();
@@ -101,38 +175,73 @@ fn convert_localization_csv(contents: &[u8]) -> Result<String, csv::Error> {
         records.push(record?);
     }
 
+    let mut problems = Vec::new();
+
+    for (index, header) in headers.iter().enumerate() {
+        match header {
+            "" | "Key" | "Source" | "Context" | "Example" => {}
+            locale if !is_locale_id(locale) => {
+                problems.push(LocalizationCsvProblem::UnknownLocale {
+                    locale: locale.to_owned(),
+                    column: index + 1,
+                });
+            }
+            _ => {}
+        }
+    }
+
     let mut entries = Vec::new();
+    let mut key_rows = HashMap::new();
 
     for record in &records {
+        let row = record.position().map_or(0, |position| position.line());
         let mut entry = LocalizationEntry::default();
 
-        for (header, value) in headers.iter().zip(record.into_iter()) {
+        for (header, value) in headers.iter().zip(record.iter()) {
             if header.is_empty() || value.is_empty() {
                 continue;
             }
 
             match header {
-                "Key" => entry.key = Some(value),
-                "Source" => entry.source = Some(value),
-                "Context" => entry.context = Some(value),
-                "Example" => entry.example = Some(value),
+                "Key" => entry.key = Some(value.into()),
+                "Source" => entry.source = Some(value.into()),
+                "Context" => entry.context = Some(value.into()),
+                "Example" => entry.example = Some(value.into()),
                 _ => {
-                    entry.values.insert(header, value);
+                    entry.values.insert(header.into(), value.into());
                 }
             }
         }
 
         if entry.key.is_none() && entry.source.is_none() {
+            // Spreadsheet programs like to leave rows of empty cells behind,
+            // which aren't worth complaining about.
+            if record.iter().any(|value| !value.is_empty()) {
+                problems.push(LocalizationCsvProblem::MissingKeyAndSource { row });
+            }
+
             continue;
         }
 
+        if let Some(key) = &entry.key {
+            if let Some(&first_row) = key_rows.get(key) {
+                problems.push(LocalizationCsvProblem::DuplicateKey {
+                    key: key.to_string(),
+                    row,
+                    first_row,
+                });
+            } else {
+                key_rows.insert(key.clone(), row);
+            }
+        }
+
         entries.push(entry);
     }
 
     let encoded =
         serde_json::to_string(&entries).expect("Could not encode JSON for localization table");
 
-    Ok(encoded)
+    Ok((encoded, problems))
 }
 
 #[cfg(test)]
@@ -154,10 +263,10 @@ Ack,Ack!,,An exclamation of despair,¡Ay!"#,
         )
         .unwrap();
 
-        let mut vfs = Vfs::new(imfs);
+        let vfs = Vfs::new(imfs);
 
         let instance_snapshot =
-            SnapshotCsv::from_vfs(&InstanceContext::default(), &mut vfs, Path::new("/foo.csv"))
+            SnapshotCsv::from_vfs(&InstanceContext::default(), &vfs, Path::new("/foo.csv"))
                 .unwrap()
                 .unwrap();
 
@@ -182,11 +291,78 @@ Ack,Ack!,,An exclamation of despair,¡Ay!"#,
         )
         .unwrap();
 
-        let mut vfs = Vfs::new(imfs);
+        let vfs = Vfs::new(imfs);
 
         let instance_snapshot =
-            SnapshotCsv::from_vfs(&InstanceContext::default(), &mut vfs, Path::new("/foo.csv"))
+            SnapshotCsv::from_vfs(&InstanceContext::default(), &vfs, Path::new("/foo.csv"))
                 .unwrap()
                 .unwrap();
+
+        insta::assert_yaml_snapshot!(instance_snapshot);
+    }
+
+    #[test]
+    fn csv_problems() {
+        let contents = r#"Key,Source,Context,Example,es,pt-br,Notes
+Ack,Ack!,,,¡Ay!,Ai!,
+Ack,Ack again!,,,,,
+,,Some context,,,,
+,,,,,,
+"#;
+
+        let (_, problems) = convert_and_validate_localization_csv(contents.as_bytes()).unwrap();
+
+        assert_eq!(
+            problems,
+            vec![
+                LocalizationCsvProblem::UnknownLocale {
+                    locale: "Notes".to_owned(),
+                    column: 7,
+                },
+                LocalizationCsvProblem::DuplicateKey {
+                    key: "Ack".to_owned(),
+                    row: 3,
+                    first_row: 2,
+                },
+                LocalizationCsvProblem::MissingKeyAndSource { row: 4 },
+            ]
+        );
+    }
+
+    #[test]
+    fn strict_csv() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo.csv",
+            VfsSnapshot::file(
+                r#"Key,Source,es
+Ack,Ack!,¡Ay!
+Ack,Ack again!,"#,
+            ),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs.clone());
+
+        // Without strict mode, problems are only warnings.
+        SnapshotCsv::from_vfs(&InstanceContext::default(), &vfs, Path::new("/foo.csv"))
+            .unwrap()
+            .unwrap();
+
+        imfs.load_snapshot("/foo.meta.json", VfsSnapshot::file(r#"{ "strict": true }"#))
+            .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let result =
+            SnapshotCsv::from_vfs(&InstanceContext::default(), &vfs, Path::new("/foo.csv"));
+
+        match result {
+            Err(SnapshotError::InvalidLocalizationCsv { problems, path }) => {
+                assert_eq!(path, Path::new("/foo.csv"));
+                assert_eq!(problems.len(), 1);
+            }
+            other => panic!("expected invalid localization error, got {:?}", other),
+        }
     }
 }
@@ -44,23 +44,17 @@ impl SnapshotMiddleware for SnapshotDir {
 
         let instance_name = path
             .file_name()
-            .expect("Could not extract file name")
+            .ok_or_else(|| SnapshotError::file_name_missing(path))?
             .to_str()
             .ok_or_else(|| SnapshotError::file_name_bad_unicode(path))?
             .to_string();
 
         let meta_path = path.join("init.meta.json");
 
-        let relevant_paths = vec![
-            path.to_path_buf(),
-            meta_path.clone(),
-            // TODO: We shouldn't need to know about Lua existing in this
-            // middleware. Should we figure out a way for that function to add
-            // relevant paths to this middleware?
-            path.join("init.lua"),
-            path.join("init.server.lua"),
-            path.join("init.client.lua"),
-        ];
+        // Files claimed by other middleware, like init.lua, can change what
+        // this directory turns into.
+        let mut relevant_paths = vec![path.to_path_buf(), meta_path.clone()];
+        relevant_paths.extend(context.middleware.claimed_paths(context, path));
 
         let mut snapshot = InstanceSnapshot::new()
             .name(instance_name)
@@ -75,7 +69,7 @@ impl SnapshotMiddleware for SnapshotDir {
 
         if let Some(meta_contents) = vfs.read(&meta_path).with_not_found()? {
             let mut metadata = DirectoryMetadata::from_slice(&meta_contents, &meta_path)?;
-            metadata.apply_all(&mut snapshot);
+            metadata.apply_all(&mut snapshot)?;
         }
 
         Ok(Some(snapshot))
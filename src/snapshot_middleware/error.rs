@@ -2,11 +2,16 @@ use std::{io, path::PathBuf};
 
 use thiserror::Error;
 
+use super::csv::LocalizationCsvProblem;
+
 #[derive(Debug, Error)]
 pub enum SnapshotError {
     #[error("file name had malformed Unicode")]
     FileNameBadUnicode { path: PathBuf },
 
+    #[error("path {} has no file name", .path.display())]
+    FileNameMissing { path: PathBuf },
+
     #[error("file had malformed Unicode contents at path {}", .path.display())]
     FileContentsBadUnicode {
         source: std::str::Utf8Error,
@@ -31,15 +36,196 @@ pub enum SnapshotError {
         path: PathBuf,
     },
 
+    #[error("malformed .model.yaml file at path {}", .path.display())]
+    MalformedModelYaml {
+        source: serde_yaml::Error,
+        path: PathBuf,
+    },
+
+    #[error("malformed .model.toml file at path {}", .path.display())]
+    MalformedModelToml {
+        source: toml::de::Error,
+        path: PathBuf,
+    },
+
     #[error("malformed JSON at path {}", .path.display())]
     MalformedJson {
         source: serde_json::Error,
         path: PathBuf,
     },
 
+    #[error("malformed YAML at path {}", .path.display())]
+    MalformedYaml {
+        source: serde_yaml::Error,
+        path: PathBuf,
+    },
+
+    #[error("malformed TOML at path {}", .path.display())]
+    MalformedToml {
+        source: toml::de::Error,
+        path: PathBuf,
+    },
+
+    #[error("malformed .rbxm file at path {}", .path.display())]
+    MalformedRbxm {
+        source: rbx_binary::DecodeError,
+        path: PathBuf,
+    },
+
+    #[error("malformed .rbxmx file at path {}", .path.display())]
+    MalformedRbxmx {
+        source: rbx_xml::DecodeError,
+        path: PathBuf,
+    },
+
+    #[error("malformed .rbxlx file at path {}", .path.display())]
+    MalformedRbxlx {
+        source: rbx_xml::DecodeError,
+        path: PathBuf,
+    },
+
+    #[error("{class_name} can't be made from the asset at path {}", .path.display())]
+    UnsupportedAssetClass { class_name: String, path: PathBuf },
+
+    #[error("model file at path {} has no top-level instances", .path.display())]
+    EmptyModel { path: PathBuf },
+
+    #[error(
+        "could not resolve property {property_name} of class {class_name} at path {}",
+        .path.display()
+    )]
+    PropertyResolution {
+        source: rbx_reflection::ValueResolveError,
+        class_name: String,
+        property_name: String,
+        path: PathBuf,
+    },
+
+    #[error(
+        "className in init.meta.json at path {} can only be specified if the directory would turn into a Folder instance",
+        .path.display()
+    )]
+    InitMetaClassNameNotFolder { path: PathBuf },
+
+    #[error(
+        "{field} in the meta file at path {} doesn't apply to the instance it describes",
+        .path.display()
+    )]
+    UnusedMetaField { field: String, path: PathBuf },
+
+    #[error(
+        "init script at path {} can only be used if the directory containing it would turn into a Folder instance",
+        .path.display()
+    )]
+    InitScriptParentNotFolder { path: PathBuf },
+
+    #[error(
+        "project node {name} specifies both $className and $path, so its $path {} must turn into a Folder instance, not {class_name}",
+        .path.display()
+    )]
+    ProjectNodeClassNameConflict {
+        name: String,
+        class_name: String,
+        path: PathBuf,
+    },
+
+    #[error(
+        "project node {name} in project at path {} must specify $className or $path",
+        .path.display()
+    )]
+    ProjectNodeMissingClassName { name: String, path: PathBuf },
+
+    #[error(
+        "project node {name} includes a project that doesn't exist at path {}",
+        .path.display()
+    )]
+    ProjectIncludeNotFound { name: String, path: PathBuf },
+
+    #[error(
+        "project node {name} includes the project at path {}, which is already included further up the tree",
+        .path.display()
+    )]
+    ProjectIncludeCycle { name: String, path: PathBuf },
+
+    #[error(
+        "the root of the project at path {} can't be turned off with $enabled",
+        .path.display()
+    )]
+    ProjectRootDisabled { path: PathBuf },
+
+    #[error(
+        "file rule {pattern} in project at path {} must be a * followed by the end of a file name",
+        .path.display()
+    )]
+    InvalidFileRulePattern { pattern: String, path: PathBuf },
+
+    #[error(
+        "file rule {pattern} used for path {} has class {class_name}, which can't hold the contents of a file",
+        .path.display()
+    )]
+    UnsupportedFileRuleClass {
+        pattern: String,
+        class_name: String,
+        path: PathBuf,
+    },
+
+    #[error("project at path {} configures unknown middleware {name}", .path.display())]
+    UnknownMiddleware { name: String, path: PathBuf },
+
+    #[error(
+        "middleware {name} in project at path {} has invalid glob {glob}",
+        .path.display()
+    )]
+    InvalidMiddlewareGlob {
+        name: String,
+        glob: String,
+        source: crate::glob::Error,
+        path: PathBuf,
+    },
+
+    #[error(
+        "attribute {name} at path {} has type {type_name}, which attributes can't hold",
+        .path.display()
+    )]
+    UnsupportedAttributeType {
+        name: String,
+        type_name: String,
+        path: PathBuf,
+    },
+
     #[error("malformed CSV localization data at path {}", .path.display())]
     MalformedLocalizationCsv { source: csv::Error, path: PathBuf },
 
+    #[error(
+        "problems found in CSV localization data at path {}:{}",
+        .path.display(),
+        list_problems(.problems)
+    )]
+    InvalidLocalizationCsv {
+        problems: Vec<LocalizationCsvProblem>,
+        path: PathBuf,
+    },
+
+    #[error("malformed JSON localization data at path {}", .path.display())]
+    MalformedLocalizationJson {
+        source: serde_json::Error,
+        path: PathBuf,
+    },
+
+    #[error("error running user plugin at path {}", .path.display())]
+    UserPlugin { source: rlua::Error, path: PathBuf },
+
+    #[error(
+        "user plugin at path {} returned an invalid instance for path {}: {message}",
+        .plugin_path.display(),
+        .path.display()
+    )]
+    UserPluginBadOutput {
+        message: String,
+        plugin_path: PathBuf,
+        path: PathBuf,
+    },
+
     #[error(transparent)]
     Io {
         #[from]
@@ -52,6 +238,10 @@ impl SnapshotError {
         Self::FileNameBadUnicode { path: path.into() }
     }
 
+    pub(crate) fn file_name_missing(path: impl Into<PathBuf>) -> Self {
+        Self::FileNameMissing { path: path.into() }
+    }
+
     pub(crate) fn file_contents_bad_unicode(
         source: std::str::Utf8Error,
         path: impl Into<PathBuf>,
@@ -79,6 +269,23 @@ impl SnapshotError {
         }
     }
 
+    pub(crate) fn malformed_model_yaml(
+        source: serde_yaml::Error,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        Self::MalformedModelYaml {
+            source,
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn malformed_model_toml(source: toml::de::Error, path: impl Into<PathBuf>) -> Self {
+        Self::MalformedModelToml {
+            source,
+            path: path.into(),
+        }
+    }
+
     pub(crate) fn malformed_meta_json(source: serde_json::Error, path: impl Into<PathBuf>) -> Self {
         Self::MalformedMetaJson {
             source,
@@ -93,10 +300,235 @@ impl SnapshotError {
         }
     }
 
+    pub(crate) fn malformed_yaml(source: serde_yaml::Error, path: impl Into<PathBuf>) -> Self {
+        Self::MalformedYaml {
+            source,
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn malformed_toml(source: toml::de::Error, path: impl Into<PathBuf>) -> Self {
+        Self::MalformedToml {
+            source,
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn malformed_rbxm(
+        source: rbx_binary::DecodeError,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        Self::MalformedRbxm {
+            source,
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn malformed_rbxmx(source: rbx_xml::DecodeError, path: impl Into<PathBuf>) -> Self {
+        Self::MalformedRbxmx {
+            source,
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn malformed_rbxlx(source: rbx_xml::DecodeError, path: impl Into<PathBuf>) -> Self {
+        Self::MalformedRbxlx {
+            source,
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn empty_model(path: impl Into<PathBuf>) -> Self {
+        Self::EmptyModel { path: path.into() }
+    }
+
+    pub(crate) fn unsupported_asset_class(
+        class_name: impl Into<String>,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        Self::UnsupportedAssetClass {
+            class_name: class_name.into(),
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn property_resolution(
+        source: rbx_reflection::ValueResolveError,
+        class_name: impl Into<String>,
+        property_name: impl Into<String>,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        Self::PropertyResolution {
+            source,
+            class_name: class_name.into(),
+            property_name: property_name.into(),
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn init_meta_class_name_not_folder(path: impl Into<PathBuf>) -> Self {
+        Self::InitMetaClassNameNotFolder { path: path.into() }
+    }
+
+    pub(crate) fn unused_meta_field(field: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Self::UnusedMetaField {
+            field: field.into(),
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn init_script_parent_not_folder(path: impl Into<PathBuf>) -> Self {
+        Self::InitScriptParentNotFolder { path: path.into() }
+    }
+
+    pub(crate) fn project_node_class_name_conflict(
+        name: impl Into<String>,
+        class_name: impl Into<String>,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        Self::ProjectNodeClassNameConflict {
+            name: name.into(),
+            class_name: class_name.into(),
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn project_node_missing_class_name(
+        name: impl Into<String>,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        Self::ProjectNodeMissingClassName {
+            name: name.into(),
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn project_include_not_found(
+        name: impl Into<String>,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        Self::ProjectIncludeNotFound {
+            name: name.into(),
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn project_include_cycle(name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Self::ProjectIncludeCycle {
+            name: name.into(),
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn project_root_disabled(path: impl Into<PathBuf>) -> Self {
+        Self::ProjectRootDisabled { path: path.into() }
+    }
+
+    pub(crate) fn invalid_file_rule_pattern(
+        pattern: impl Into<String>,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        Self::InvalidFileRulePattern {
+            pattern: pattern.into(),
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn unsupported_file_rule_class(
+        pattern: impl Into<String>,
+        class_name: impl Into<String>,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        Self::UnsupportedFileRuleClass {
+            pattern: pattern.into(),
+            class_name: class_name.into(),
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn unknown_middleware(name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Self::UnknownMiddleware {
+            name: name.into(),
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn invalid_middleware_glob(
+        name: impl Into<String>,
+        glob: impl Into<String>,
+        source: crate::glob::Error,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        Self::InvalidMiddlewareGlob {
+            name: name.into(),
+            glob: glob.into(),
+            source,
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn unsupported_attribute_type(
+        name: impl Into<String>,
+        type_name: impl Into<String>,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        Self::UnsupportedAttributeType {
+            name: name.into(),
+            type_name: type_name.into(),
+            path: path.into(),
+        }
+    }
+
     pub(crate) fn malformed_l10n_csv(source: csv::Error, path: impl Into<PathBuf>) -> Self {
         Self::MalformedLocalizationCsv {
             source,
             path: path.into(),
         }
     }
+
+    pub(crate) fn invalid_l10n_csv(
+        problems: Vec<LocalizationCsvProblem>,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        Self::InvalidLocalizationCsv {
+            problems,
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn malformed_localization_json(
+        source: serde_json::Error,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        Self::MalformedLocalizationJson {
+            source,
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn user_plugin_error(source: rlua::Error, path: impl Into<PathBuf>) -> Self {
+        Self::UserPlugin {
+            source,
+            path: path.into(),
+        }
+    }
+
+    pub(crate) fn user_plugin_bad_output(
+        message: impl Into<String>,
+        plugin_path: impl Into<PathBuf>,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        Self::UserPluginBadOutput {
+            message: message.into(),
+            plugin_path: plugin_path.into(),
+            path: path.into(),
+        }
+    }
+}
+
+fn list_problems(problems: &[LocalizationCsvProblem]) -> String {
+    problems
+        .iter()
+        .map(|problem| format!("\n  {}", problem))
+        .collect()
 }
@@ -0,0 +1,464 @@
+//! Turns files into instances based on how their names end, like `.lua` files
+//! into ModuleScripts or `.txt` files into StringValues. Rojo's own rules for
+//! Lua and text files are defined this way, and projects can add more with
+//! their `fileRules` field.
+//!
+//! A directory holding an init file for one of these rules, like `init.lua`
+//! for `*.lua` or `init.luau` for `*.luau`, turns into the instance made from
+//! that file, with the rest of the directory as its children.
+
+use std::{
+    cmp::Reverse,
+    io,
+    path::{Path, PathBuf},
+    str,
+};
+
+use maplit::hashmap;
+use memofs::{IoResultExt, Vfs};
+use rbx_dom_weak::RbxValue;
+use serde::{Deserialize, Serialize};
+
+use crate::snapshot::{InstanceContext, InstanceMetadata, InstanceSnapshot};
+
+use super::{
+    dir::SnapshotDir,
+    error::SnapshotError,
+    meta_file::apply_adjacent_metadata,
+    middleware::{SnapshotInstanceResult, SnapshotMiddleware},
+    util::match_file_name,
+};
+
+lazy_static::lazy_static! {
+    pub static ref LUA_RULES: Vec<FileRule> = vec![
+        FileRule::builtin("*.server.lua", "Script"),
+        FileRule::builtin("*.client.lua", "LocalScript"),
+        FileRule::builtin("*.lua", "ModuleScript"),
+    ];
+
+    pub static ref TXT_RULES: Vec<FileRule> = vec![
+        FileRule::builtin("*.txt", "StringValue"),
+    ];
+}
+
+/// Says that files with names matching `pattern` should turn into an instance
+/// of `class_name`, holding the contents of the file.
+///
+/// Patterns are a `*` followed by the end of a file name, like `*.luau` or
+/// `*.spec.lua`. The instance is named after the part of the file name that
+/// `*` matched.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileRule {
+    pub pattern: String,
+    pub class_name: String,
+}
+
+impl FileRule {
+    fn builtin(pattern: &str, class_name: &str) -> Self {
+        FileRule {
+            pattern: pattern.to_owned(),
+            class_name: class_name.to_owned(),
+        }
+    }
+
+    /// Validates a rule from the `fileRules` field of the project at the given
+    /// path.
+    pub fn from_project(
+        pattern: &str,
+        class_name: &str,
+        project_path: &Path,
+    ) -> Result<Self, SnapshotError> {
+        let mut parts = pattern.splitn(2, '*');
+        let suffix = match (parts.next(), parts.next()) {
+            (Some(""), Some(suffix)) => suffix,
+            _ => "",
+        };
+        let is_valid = !suffix.is_empty() && !suffix.contains(&['*', '?', '[', '{'][..]);
+
+        if !is_valid {
+            return Err(SnapshotError::invalid_file_rule_pattern(
+                pattern,
+                project_path,
+            ));
+        }
+
+        if content_property(class_name).is_none() {
+            return Err(SnapshotError::unsupported_file_rule_class(
+                pattern,
+                class_name,
+                project_path,
+            ));
+        }
+
+        Ok(FileRule::builtin(pattern, class_name))
+    }
+
+    /// The end of the file names that this rule matches, like `.lua` for
+    /// `*.lua`.
+    pub fn suffix(&self) -> &str {
+        &self.pattern[1..]
+    }
+
+    /// The property that holds the contents of files matching this rule.
+    pub fn content_property(&self) -> Option<&'static str> {
+        content_property(&self.class_name)
+    }
+
+    /// The name of the file that turns a directory into an instance using this
+    /// rule, like `init.lua` for `*.lua`.
+    pub fn init_file_name(&self) -> String {
+        format!("init{}", self.suffix())
+    }
+
+    fn is_init_file(&self, file_name: &str) -> bool {
+        let suffix = self.suffix();
+
+        file_name.len() == "init".len() + suffix.len()
+            && file_name.starts_with("init")
+            && file_name.ends_with(suffix)
+    }
+}
+
+/// Returns Rojo's own rules for Lua files, whose init files are handled by the
+/// `lua` middleware.
+pub fn lua_rules(_context: &InstanceContext) -> &[FileRule] {
+    &LUA_RULES
+}
+
+/// Returns the rules defined by projects, whose init files are handled by the
+/// `fileRules` middleware.
+pub fn project_rules(context: &InstanceContext) -> &[FileRule] {
+    &context.file_rules
+}
+
+/// Tells whether the given file name is the init file of one of the rules.
+pub fn is_init_file(rules: &[FileRule], file_name: &str) -> bool {
+    rules.iter().any(|rule| rule.is_init_file(file_name))
+}
+
+/// Returns the names of the init files for the given rules in the order they
+/// are looked for. Shorter suffixes come first, so a directory holding both
+/// `init.lua` and `init.server.lua` turns into a ModuleScript.
+pub fn init_file_names(rules: &[FileRule]) -> Vec<String> {
+    let mut rules: Vec<&FileRule> = rules.iter().collect();
+    rules.sort_by_key(|rule| rule.suffix().len());
+
+    rules.into_iter().map(FileRule::init_file_name).collect()
+}
+
+/// Returns the rule that decides which instance the file at the given path
+/// turns into, if any. Rules from projects win over Rojo's own rules for Lua
+/// and text files.
+pub fn rule_for_path<'a>(context: &'a InstanceContext, path: &Path) -> Option<&'a FileRule> {
+    let rules = context
+        .file_rules
+        .iter()
+        .chain(LUA_RULES.iter())
+        .chain(TXT_RULES.iter());
+
+    longest_match(rules, path).map(|(rule, _)| rule)
+}
+
+/// Tells whether the given path matches any of the given rules.
+pub(crate) fn matches_any(rules: &[FileRule], path: &Path) -> bool {
+    longest_match(rules, path).is_some()
+}
+
+/// Finds the init file that turns the given directory into an instance, if it
+/// has one, looking for them in the same order that the middleware do.
+pub fn find_init_file(
+    context: &InstanceContext,
+    vfs: &Vfs,
+    directory: &Path,
+) -> io::Result<Option<PathBuf>> {
+    let names = init_file_names(&context.file_rules)
+        .into_iter()
+        .chain(init_file_names(&LUA_RULES));
+
+    for name in names {
+        let init_path = directory.join(name);
+
+        if vfs.metadata(&init_path).with_not_found()?.is_some() {
+            return Ok(Some(init_path));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns the suffix that a file ending in `old_suffix` should have instead to
+/// turn into an instance of `class_name`, if a rule can do that without
+/// changing the file's extension. For example, with Rojo's own rules, a Script
+/// ending in `.server.lua` becomes a ModuleScript ending in `.lua`.
+pub fn suffix_for_class<'a>(
+    context: &'a InstanceContext,
+    old_suffix: &str,
+    class_name: &str,
+) -> Option<&'a str> {
+    let old_extension = extension(old_suffix);
+
+    context
+        .file_rules
+        .iter()
+        .chain(LUA_RULES.iter())
+        .chain(TXT_RULES.iter())
+        .filter(|rule| rule.class_name == class_name)
+        .map(FileRule::suffix)
+        .find(|suffix| extension(suffix) == old_extension)
+}
+
+fn extension(suffix: &str) -> &str {
+    suffix.rsplit('.').next().unwrap_or(suffix)
+}
+
+/// Handles files matching the rules that projects define in `fileRules`.
+///
+/// Rules from projects are tried before Rojo's middleware for Lua, text, CSV,
+/// JSON, YAML, and TOML files, so they can also change what happens to those
+/// files.
+pub struct SnapshotFileRules;
+
+impl SnapshotMiddleware for SnapshotFileRules {
+    fn from_vfs(context: &InstanceContext, vfs: &Vfs, path: &Path) -> SnapshotInstanceResult {
+        if context.file_rules.is_empty() {
+            return Ok(None);
+        }
+
+        if vfs.metadata(path)?.is_dir() {
+            snapshot_init(context, vfs, path, &context.file_rules)
+        } else {
+            snapshot_with_rules(context, vfs, path, context.file_rules.iter())
+        }
+    }
+}
+
+/// Snapshots a file using whichever of the given rules matches the most of its
+/// name, if any of them do.
+pub fn snapshot_with_rules<'a>(
+    context: &InstanceContext,
+    vfs: &Vfs,
+    path: &Path,
+    rules: impl IntoIterator<Item = &'a FileRule>,
+) -> SnapshotInstanceResult {
+    let (rule, instance_name) = match longest_match(rules, path) {
+        Some(matched) => matched,
+        None => return Ok(None),
+    };
+
+    let property = content_property(&rule.class_name).ok_or_else(|| {
+        SnapshotError::unsupported_file_rule_class(&rule.pattern, &rule.class_name, path)
+    })?;
+
+    let contents = vfs.read(path)?;
+    let contents_str = str::from_utf8(&contents)
+        .map_err(|err| SnapshotError::file_contents_bad_unicode(err, path))?
+        .to_string();
+
+    let mut snapshot = InstanceSnapshot::new()
+        .name(instance_name)
+        .class_name(rule.class_name.clone())
+        .properties(hashmap! {
+            property.to_owned() => RbxValue::String {
+                value: contents_str,
+            },
+        })
+        .metadata(
+            InstanceMetadata::new()
+                .instigating_source(path)
+                .relevant_paths(vec![path.to_path_buf()])
+                .context(context),
+        );
+
+    apply_adjacent_metadata(vfs, path, &mut snapshot)?;
+
+    Ok(Some(snapshot))
+}
+
+/// Snapshots a directory holding the init file of one of the given rules,
+/// which turns into the instance made from that file, named after the
+/// directory and with the directory's children.
+///
+/// This acts similarly to `__init__.py` from the Python world.
+pub fn snapshot_init(
+    context: &InstanceContext,
+    vfs: &Vfs,
+    folder_path: &Path,
+    rules: &[FileRule],
+) -> SnapshotInstanceResult {
+    for init_name in init_file_names(rules) {
+        let init_path = folder_path.join(init_name);
+
+        if vfs.metadata(&init_path).with_not_found()?.is_none() {
+            continue;
+        }
+
+        if let Some(dir_snapshot) = SnapshotDir::from_vfs(context, vfs, folder_path)? {
+            if let Some(mut init_snapshot) =
+                snapshot_with_rules(context, vfs, &init_path, rules.iter())?
+            {
+                if dir_snapshot.class_name != "Folder" {
+                    return Err(SnapshotError::init_script_parent_not_folder(init_path));
+                }
+
+                init_snapshot.name = dir_snapshot.name;
+                init_snapshot.children = dir_snapshot.children;
+                init_snapshot.metadata = dir_snapshot.metadata;
+
+                return Ok(Some(init_snapshot));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Finds the rule with the longest suffix that the file name of `path` ends
+/// with, along with the rest of the file name. The first of the rules wins
+/// when several have the same suffix.
+fn longest_match<'a, 'p>(
+    rules: impl IntoIterator<Item = &'a FileRule>,
+    path: &'p Path,
+) -> Option<(&'a FileRule, &'p str)> {
+    rules
+        .into_iter()
+        .filter_map(|rule| Some((rule, match_file_name(path, rule.suffix())?)))
+        .min_by_key(|(rule, _)| Reverse(rule.suffix().len()))
+}
+
+/// Returns the property that holds the contents of a file for instances of the
+/// given class, if file rules can use it.
+fn content_property(class_name: &str) -> Option<&'static str> {
+    match class_name {
+        "Script" | "LocalScript" | "ModuleScript" => Some("Source"),
+        "StringValue" => Some("Value"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use memofs::{InMemoryFs, VfsSnapshot};
+
+    use crate::snapshot_middleware::snapshot_from_vfs;
+
+    fn context_with_rules(rules: &[(&str, &str)]) -> InstanceContext {
+        let project_path = Path::new("/default.project.json");
+
+        let rules = rules
+            .iter()
+            .map(|(pattern, class_name)| {
+                FileRule::from_project(pattern, class_name, project_path).unwrap()
+            })
+            .collect();
+
+        InstanceContext {
+            file_rules: Arc::new(rules),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn longest_match_wins() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/foo.story.luau", VfsSnapshot::file("return {}"))
+            .unwrap();
+
+        let vfs = Vfs::new(imfs);
+        let context = context_with_rules(&[("*.luau", "ModuleScript"), ("*.story.luau", "Script")]);
+
+        let snapshot = SnapshotFileRules::from_vfs(&context, &vfs, Path::new("/foo.story.luau"))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(snapshot.name, "foo");
+        assert_eq!(snapshot.class_name, "Script");
+        assert_eq!(
+            snapshot.properties.get("Source"),
+            Some(&RbxValue::String {
+                value: "return {}".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn string_value() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/shader.vert", VfsSnapshot::file("void main() {}"))
+            .unwrap();
+
+        let vfs = Vfs::new(imfs);
+        let context = context_with_rules(&[("*.vert", "StringValue")]);
+
+        let snapshot = SnapshotFileRules::from_vfs(&context, &vfs, Path::new("/shader.vert"))
+            .unwrap()
+            .unwrap();
+
+        insta::assert_yaml_snapshot!(snapshot);
+    }
+
+    #[test]
+    fn init_file() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/root",
+            VfsSnapshot::dir(hashmap! {
+                "init.luau" => VfsSnapshot::file("return {}"),
+                "Child.luau" => VfsSnapshot::file("return nil"),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+        let context = context_with_rules(&[("*.luau", "ModuleScript")]);
+
+        let snapshot = snapshot_from_vfs(&context, &vfs, Path::new("/root"))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(snapshot.name, "root");
+        assert_eq!(snapshot.class_name, "ModuleScript");
+        assert_eq!(
+            snapshot.properties.get("Source"),
+            Some(&RbxValue::String {
+                value: "return {}".to_owned()
+            })
+        );
+
+        // The init file is claimed, so it doesn't also turn into a child.
+        let children: Vec<_> = snapshot
+            .children
+            .iter()
+            .map(|child| child.name.as_ref())
+            .collect();
+        assert_eq!(children, vec!["Child"]);
+
+        assert!(snapshot
+            .metadata
+            .relevant_paths
+            .contains(&PathBuf::from("/root/init.luau")));
+    }
+
+    #[test]
+    fn invalid_rules() {
+        let project_path = Path::new("/default.project.json");
+
+        for pattern in &["foo.lua", "*", "*.lua*"] {
+            match FileRule::from_project(pattern, "ModuleScript", project_path) {
+                Err(SnapshotError::InvalidFileRulePattern { .. }) => {}
+                other => panic!("expected invalid pattern error, got {:?}", other),
+            }
+        }
+
+        match FileRule::from_project("*.png", "Decal", project_path) {
+            Err(SnapshotError::UnsupportedFileRuleClass { class_name, .. }) => {
+                assert_eq!(class_name, "Decal");
+            }
+            other => panic!("expected unsupported class error, got {:?}", other),
+        }
+    }
+}
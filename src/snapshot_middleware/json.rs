@@ -1,7 +1,7 @@
-use std::path::Path;
+use std::{collections::BTreeMap, path::Path};
 
 use maplit::hashmap;
-use memofs::{IoResultExt, Vfs};
+use memofs::Vfs;
 use rbx_dom_weak::RbxValue;
 
 use crate::{
@@ -11,7 +11,7 @@ use crate::{
 
 use super::{
     error::SnapshotError,
-    meta_file::AdjacentMetadata,
+    meta_file::apply_adjacent_metadata,
     middleware::{SnapshotInstanceResult, SnapshotMiddleware},
     util::match_file_name,
 };
@@ -28,13 +28,6 @@ impl SnapshotMiddleware for SnapshotJson {
             return Ok(None);
         }
 
-        // FIXME: This middleware should not need to know about the .meta.json
-        // middleware. Should there be a way to signal "I'm not returning an
-        // instance and no one should"?
-        if match_file_name(path, ".meta.json").is_some() {
-            return Ok(None);
-        }
-
         let instance_name = match match_file_name(path, ".json") {
             Some(name) => name,
             None => return Ok(None),
@@ -45,36 +38,43 @@ impl SnapshotMiddleware for SnapshotJson {
         let value: serde_json::Value = serde_json::from_slice(&contents)
             .map_err(|err| SnapshotError::malformed_json(err, path))?;
 
-        let as_lua = json_to_lua(value).to_string();
-
-        let properties = hashmap! {
-            "Source".to_owned() => RbxValue::String {
-                value: as_lua,
-            },
-        };
-
-        let meta_path = path.with_file_name(format!("{}.meta.json", instance_name));
-
-        let mut snapshot = InstanceSnapshot::new()
-            .name(instance_name)
-            .class_name("ModuleScript")
-            .properties(properties)
-            .metadata(
-                InstanceMetadata::new()
-                    .instigating_source(path)
-                    .relevant_paths(vec![path.to_path_buf(), meta_path.clone()])
-                    .context(context),
-            );
-
-        if let Some(meta_contents) = vfs.read(&meta_path).with_not_found()? {
-            let mut metadata = AdjacentMetadata::from_slice(&meta_contents, &meta_path)?;
-            metadata.apply_all(&mut snapshot);
-        }
+        let snapshot = data_module_snapshot(context, vfs, path, instance_name, json_to_lua(value))?;
 
         Ok(Some(snapshot))
     }
 }
 
+/// Creates a ModuleScript that returns the given data, which is how JSON, YAML,
+/// and TOML files are turned into instances.
+pub(super) fn data_module_snapshot(
+    context: &InstanceContext,
+    vfs: &Vfs,
+    path: &Path,
+    instance_name: &str,
+    data: Statement,
+) -> Result<InstanceSnapshot, SnapshotError> {
+    let properties = hashmap! {
+        "Source".to_owned() => RbxValue::String {
+            value: data.to_string(),
+        },
+    };
+
+    let mut snapshot = InstanceSnapshot::new()
+        .name(instance_name)
+        .class_name("ModuleScript")
+        .properties(properties)
+        .metadata(
+            InstanceMetadata::new()
+                .instigating_source(path)
+                .relevant_paths(vec![path.to_path_buf()])
+                .context(context),
+        );
+
+    apply_adjacent_metadata(vfs, path, &mut snapshot)?;
+
+    Ok(snapshot)
+}
+
 fn json_to_lua(value: serde_json::Value) -> Statement {
     Statement::Return(json_to_lua_value(value))
 }
@@ -90,8 +90,12 @@ fn json_to_lua_value(value: serde_json::Value) -> Expression {
         Value::Array(values) => {
             Expression::Array(values.into_iter().map(json_to_lua_value).collect())
         }
+        // Keys are sorted so that the generated code doesn't depend on the
+        // order that keys were written in.
         Value::Object(values) => Expression::table(
             values
+                .into_iter()
+                .collect::<BTreeMap<_, _>>()
                 .into_iter()
                 .map(|(key, value)| (key.into(), json_to_lua_value(value)))
                 .collect(),
@@ -1,15 +1,18 @@
-use std::{borrow::Cow, collections::HashMap, path::Path};
+use std::{borrow::Cow, collections::HashMap, path::Path, str};
 
 use memofs::Vfs;
 use rbx_dom_weak::UnresolvedRbxValue;
 use rbx_reflection::try_resolve_value;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::snapshot::{InstanceContext, InstanceSnapshot};
 
 use super::{
+    attributes::{encode_attributes, ATTRIBUTES_PROPERTY},
     error::SnapshotError,
+    meta_file::apply_adjacent_metadata,
     middleware::{SnapshotInstanceResult, SnapshotMiddleware},
+    tags::{encode_tags, TAGS_PROPERTY},
     util::match_file_name,
 };
 
@@ -23,14 +26,13 @@ impl SnapshotMiddleware for SnapshotJsonModel {
             return Ok(None);
         }
 
-        let instance_name = match match_file_name(path, ".model.json") {
-            Some(name) => name,
+        let (instance_name, format) = match ModelFormat::from_path(path) {
+            Some(matched) => matched,
             None => return Ok(None),
         };
 
         let contents = vfs.read(path)?;
-        let instance: JsonModel = serde_json::from_slice(&contents)
-            .map_err(|source| SnapshotError::malformed_model_json(source, path))?;
+        let instance = format.parse(&contents, path)?;
 
         if let Some(json_name) = &instance.name {
             if json_name != instance_name {
@@ -50,7 +52,9 @@ impl SnapshotMiddleware for SnapshotJsonModel {
             }
         }
 
-        let mut snapshot = instance.core.into_snapshot(instance_name.to_owned());
+        let mut snapshot = instance
+            .core
+            .into_snapshot(instance_name.to_owned(), path)?;
 
         snapshot.metadata = snapshot
             .metadata
@@ -58,10 +62,55 @@ impl SnapshotMiddleware for SnapshotJsonModel {
             .relevant_paths(vec![path.to_path_buf()])
             .context(context);
 
+        apply_adjacent_metadata(vfs, path, &mut snapshot)?;
+
         Ok(Some(snapshot))
     }
 }
 
+/// The languages that models can be written in. Each of them describes the same
+/// structure, so `.model.yaml` and `.model.toml` files use the same field names
+/// as `.model.json` files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ModelFormat {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl ModelFormat {
+    /// Returns the name of the model in the given file and the language it's
+    /// written in, if the file is a model.
+    fn from_path(path: &Path) -> Option<(&str, Self)> {
+        let formats = [
+            (".model.json", ModelFormat::Json),
+            (".model.yaml", ModelFormat::Yaml),
+            (".model.yml", ModelFormat::Yaml),
+            (".model.toml", ModelFormat::Toml),
+        ];
+
+        formats
+            .iter()
+            .find_map(|(suffix, format)| Some((match_file_name(path, suffix)?, *format)))
+    }
+
+    fn parse(self, contents: &[u8], path: &Path) -> Result<JsonModel, SnapshotError> {
+        match self {
+            ModelFormat::Json => serde_json::from_slice(contents)
+                .map_err(|source| SnapshotError::malformed_model_json(source, path)),
+            ModelFormat::Yaml => serde_yaml::from_slice(contents)
+                .map_err(|source| SnapshotError::malformed_model_yaml(source, path)),
+            ModelFormat::Toml => {
+                let contents = str::from_utf8(contents)
+                    .map_err(|err| SnapshotError::file_contents_bad_unicode(err, path))?;
+
+                toml::from_str(contents)
+                    .map_err(|source| SnapshotError::malformed_model_toml(source, path))
+            }
+        }
+    }
+}
+
 fn match_trailing<'a>(input: &'a str, trailer: &str) -> Option<&'a str> {
     if input.ends_with(trailer) {
         let end = input.len().saturating_sub(trailer.len());
@@ -71,63 +120,92 @@ fn match_trailing<'a>(input: &'a str, trailer: &str) -> Option<&'a str> {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
-struct JsonModel {
-    name: Option<String>,
+pub struct JsonModel {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
 
     #[serde(flatten)]
-    core: JsonModelCore,
+    pub core: JsonModelCore,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
-struct JsonModelInstance {
-    name: String,
+pub struct JsonModelInstance {
+    pub name: String,
 
     #[serde(flatten)]
-    core: JsonModelCore,
+    pub core: JsonModelCore,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
-struct JsonModelCore {
-    class_name: String,
+pub struct JsonModelCore {
+    pub class_name: String,
 
     #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
-    children: Vec<JsonModelInstance>,
+    pub children: Vec<JsonModelInstance>,
 
     #[serde(default = "HashMap::new", skip_serializing_if = "HashMap::is_empty")]
-    properties: HashMap<String, UnresolvedRbxValue>,
+    pub properties: HashMap<String, UnresolvedRbxValue>,
+
+    #[serde(default = "HashMap::new", skip_serializing_if = "HashMap::is_empty")]
+    pub attributes: HashMap<String, UnresolvedRbxValue>,
+
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 impl JsonModelCore {
-    fn into_snapshot(self, name: String) -> InstanceSnapshot {
+    /// Turns this instance into a snapshot. `path` is the file the instance
+    /// came from, which is used for error messages.
+    pub fn into_snapshot(
+        self,
+        name: String,
+        path: &Path,
+    ) -> Result<InstanceSnapshot, SnapshotError> {
         let class_name = self.class_name;
 
         let children = self
             .children
             .into_iter()
-            .map(|child| child.core.into_snapshot(child.name))
-            .collect();
+            .map(|child| child.core.into_snapshot(child.name, path))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        let properties = self
+        let mut properties = self
             .properties
             .into_iter()
-            .map(|(key, value)| {
-                try_resolve_value(&class_name, &key, &value).map(|resolved| (key, resolved))
-            })
-            .collect::<Result<HashMap<_, _>, _>>()
-            .expect("TODO: Handle rbx_reflection errors");
+            .map(
+                |(key, value)| match try_resolve_value(&class_name, &key, &value) {
+                    Ok(resolved) => Ok((key, resolved)),
+                    Err(source) => Err(SnapshotError::property_resolution(
+                        source,
+                        class_name.as_str(),
+                        key,
+                        path,
+                    )),
+                },
+            )
+            .collect::<Result<HashMap<_, _>, _>>()?;
+
+        if !self.attributes.is_empty() {
+            let attributes = encode_attributes(&self.attributes, path)?;
+            properties.insert(ATTRIBUTES_PROPERTY.to_owned(), attributes);
+        }
+
+        if !self.tags.is_empty() {
+            properties.insert(TAGS_PROPERTY.to_owned(), encode_tags(&self.tags));
+        }
 
-        InstanceSnapshot {
+        Ok(InstanceSnapshot {
             snapshot_id: None,
             metadata: Default::default(),
             name: Cow::Owned(name),
             class_name: Cow::Owned(class_name),
             properties,
             children,
-        }
+        })
     }
 }
 
@@ -135,6 +213,7 @@ impl JsonModelCore {
 mod test {
     use super::*;
 
+    use maplit::hashmap;
     use memofs::{InMemoryFs, VfsSnapshot};
 
     #[test]
@@ -174,4 +253,144 @@ mod test {
 
         insta::assert_yaml_snapshot!(instance_snapshot);
     }
+
+    #[test]
+    fn model_with_meta() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/root",
+            VfsSnapshot::dir(hashmap! {
+                "foo.model.json" => VfsSnapshot::file(r#"
+                    {
+                      "ClassName": "IntValue",
+                      "Properties": {
+                        "Value": 5
+                      }
+                    }
+                "#),
+                "foo.meta.json" => VfsSnapshot::file(r#"
+                    {
+                      "ignoreUnknownInstances": true,
+                      "properties": {
+                        "Value": 10
+                      }
+                    }
+                "#),
+            }),
+        )
+        .unwrap();
+
+        let mut vfs = Vfs::new(imfs);
+
+        let instance_snapshot = SnapshotJsonModel::from_vfs(
+            &InstanceContext::default(),
+            &mut vfs,
+            Path::new("/root/foo.model.json"),
+        )
+        .unwrap()
+        .unwrap();
+
+        insta::assert_yaml_snapshot!(instance_snapshot);
+    }
+
+    #[test]
+    fn model_from_yaml() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo.model.yaml",
+            VfsSnapshot::file(
+                r#"
+                ClassName: Part
+                Properties:
+                  Size: [4, 1, 2]
+                  Anchored: true
+                Children:
+                  - Name: Weld
+                    ClassName: WeldConstraint
+                  - Name: Health
+                    ClassName: IntValue
+                    Properties:
+                      Value: 100
+                "#,
+            ),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let instance_snapshot = SnapshotJsonModel::from_vfs(
+            &InstanceContext::default(),
+            &vfs,
+            Path::new("/foo.model.yaml"),
+        )
+        .unwrap()
+        .unwrap();
+
+        insta::with_settings!({ sort_maps => true }, {
+            insta::assert_yaml_snapshot!(instance_snapshot);
+        });
+    }
+
+    #[test]
+    fn model_from_toml() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo.model.toml",
+            VfsSnapshot::file(
+                r#"
+                ClassName = "Part"
+
+                [Properties]
+                Size = [4, 1, 2]
+                Anchored = true
+
+                [[Children]]
+                Name = "Weld"
+                ClassName = "WeldConstraint"
+
+                [[Children]]
+                Name = "Health"
+                ClassName = "IntValue"
+                Properties = { Value = 100 }
+                "#,
+            ),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let instance_snapshot = SnapshotJsonModel::from_vfs(
+            &InstanceContext::default(),
+            &vfs,
+            Path::new("/foo.model.toml"),
+        )
+        .unwrap()
+        .unwrap();
+
+        insta::with_settings!({ sort_maps => true }, {
+            insta::assert_yaml_snapshot!(instance_snapshot);
+        });
+    }
+
+    #[test]
+    fn malformed_yaml_model() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/foo.model.yml", VfsSnapshot::file("Children: 5"))
+            .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let result = SnapshotJsonModel::from_vfs(
+            &InstanceContext::default(),
+            &vfs,
+            Path::new("/foo.model.yml"),
+        );
+
+        match result {
+            Err(SnapshotError::MalformedModelYaml { path, .. }) => {
+                assert_eq!(path, Path::new("/foo.model.yml"));
+            }
+            other => panic!("expected malformed model error, got {:?}", other),
+        }
+    }
 }
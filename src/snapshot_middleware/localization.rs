@@ -0,0 +1,396 @@
+//! Turns localization tables written as JSON into LocalizationTable instances.
+//!
+//! A `.loc.json` file holds a list of entries in the same shape that Roblox
+//! uses for the contents of a LocalizationTable. A directory containing an
+//! `init.loc.json` file also turns into a LocalizationTable. Its entries are
+//! merged with every other JSON file in the directory, each of which is named
+//! after a locale, like `de.json`, and maps keys to text in that locale.
+//! Directories without any entries of their own can leave `init.loc.json`
+//! empty, or set their `className` to LocalizationTable in `init.meta.json`.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use maplit::hashmap;
+use memofs::{IoResultExt, Vfs};
+use rbx_dom_weak::RbxValue;
+
+use crate::snapshot::{InstanceContext, InstanceMetadata, InstanceSnapshot};
+
+use super::{
+    csv::LocalizationEntry,
+    error::SnapshotError,
+    meta_file::{apply_adjacent_metadata, DirectoryMetadata},
+    middleware::{SnapshotInstanceResult, SnapshotMiddleware},
+    util::match_file_name,
+};
+
+pub struct SnapshotLocalization;
+
+impl SnapshotMiddleware for SnapshotLocalization {
+    fn from_vfs(context: &InstanceContext, vfs: &Vfs, path: &Path) -> SnapshotInstanceResult {
+        let meta = vfs.metadata(path)?;
+
+        if meta.is_dir() {
+            snapshot_locale_dir(context, vfs, path)
+        } else {
+            snapshot_loc_json(context, vfs, path)
+        }
+    }
+}
+
+fn snapshot_loc_json(context: &InstanceContext, vfs: &Vfs, path: &Path) -> SnapshotInstanceResult {
+    let instance_name = match match_file_name(path, ".loc.json") {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+
+    let contents = vfs.read(path)?;
+    let entries: Vec<LocalizationEntry> = serde_json::from_slice(&contents)
+        .map_err(|source| SnapshotError::malformed_localization_json(source, path))?;
+
+    let mut snapshot = localization_table(instance_name, &entries).metadata(
+        InstanceMetadata::new()
+            .instigating_source(path)
+            .relevant_paths(vec![path.to_path_buf()])
+            .context(context),
+    );
+
+    apply_adjacent_metadata(vfs, path, &mut snapshot)?;
+
+    Ok(Some(snapshot))
+}
+
+/// Snapshots a directory holding an `init.loc.json` file and one JSON file per
+/// locale, like `en-us.json` and `de.json`. Directories whose `init.meta.json`
+/// makes them a LocalizationTable don't need an `init.loc.json` file.
+fn snapshot_locale_dir(
+    context: &InstanceContext,
+    vfs: &Vfs,
+    path: &Path,
+) -> SnapshotInstanceResult {
+    let init_path = path.join("init.loc.json");
+    let meta_path = path.join("init.meta.json");
+
+    let mut metadata = match vfs.read(&meta_path).with_not_found()? {
+        Some(meta_contents) => Some(DirectoryMetadata::from_slice(&meta_contents, &meta_path)?),
+        None => None,
+    };
+
+    // The class name only says that this directory is a LocalizationTable,
+    // which the snapshot already is, so it isn't applied again.
+    let is_table = match &mut metadata {
+        Some(metadata) if metadata.class_name.as_deref() == Some("LocalizationTable") => {
+            metadata.class_name = None;
+            true
+        }
+        _ => false,
+    };
+
+    let mut entries: Vec<LocalizationEntry> = match vfs.read(&init_path).with_not_found()? {
+        Some(contents) if contents.iter().all(u8::is_ascii_whitespace) => Vec::new(),
+        Some(contents) => serde_json::from_slice(&contents)
+            .map_err(|source| SnapshotError::malformed_localization_json(source, &init_path))?,
+        None if is_table => Vec::new(),
+        None => return Ok(None),
+    };
+
+    let mut locale_paths = Vec::new();
+
+    for entry in vfs.read_dir(path)? {
+        let entry = entry?;
+        let entry_path = entry.path();
+
+        let passes_filter_rules = context
+            .path_ignore_rules
+            .iter()
+            .all(|rule| rule.passes(entry_path));
+
+        if !passes_filter_rules || entry_path == init_path || vfs.metadata(entry_path)?.is_dir() {
+            continue;
+        }
+
+        if match_file_name(entry_path, ".meta.json").is_some() {
+            continue;
+        }
+
+        if let Some(locale) = match_file_name(entry_path, ".json") {
+            locale_paths.push((entry_path.to_path_buf(), locale.to_owned()));
+        }
+    }
+
+    // Directories can be read in any order, but entries that only come from
+    // locale files should always end up in the same order.
+    locale_paths.sort();
+
+    let mut indices: HashMap<String, usize> = entries
+        .iter()
+        .enumerate()
+        .filter_map(|(index, entry)| Some((entry.key.as_ref()?.to_string(), index)))
+        .collect();
+
+    for (locale_path, locale) in &locale_paths {
+        let contents = vfs.read(locale_path)?;
+        let texts: BTreeMap<String, String> = serde_json::from_slice(&contents)
+            .map_err(|source| SnapshotError::malformed_localization_json(source, locale_path))?;
+
+        for (key, text) in texts {
+            let index = *indices.entry(key.clone()).or_insert_with(|| {
+                entries.push(LocalizationEntry {
+                    key: Some(key.into()),
+                    ..Default::default()
+                });
+
+                entries.len() - 1
+            });
+
+            entries[index]
+                .values
+                .insert(locale.clone().into(), text.into());
+        }
+    }
+
+    let instance_name = path
+        .file_name()
+        .ok_or_else(|| SnapshotError::file_name_missing(path))?
+        .to_str()
+        .ok_or_else(|| SnapshotError::file_name_bad_unicode(path))?;
+
+    let mut relevant_paths = vec![path.to_path_buf(), init_path, meta_path.clone()];
+    relevant_paths.extend(locale_paths.into_iter().map(|(locale_path, _)| locale_path));
+
+    let mut snapshot = localization_table(instance_name, &entries).metadata(
+        InstanceMetadata::new()
+            .instigating_source(path)
+            .relevant_paths(relevant_paths)
+            .context(context),
+    );
+
+    if let Some(metadata) = &mut metadata {
+        metadata.apply_all(&mut snapshot)?;
+    }
+
+    Ok(Some(snapshot))
+}
+
+fn localization_table(name: &str, entries: &[LocalizationEntry]) -> InstanceSnapshot {
+    let contents =
+        serde_json::to_string(entries).expect("Could not encode JSON for localization table");
+
+    InstanceSnapshot::new()
+        .name(name.to_owned())
+        .class_name("LocalizationTable")
+        .properties(hashmap! {
+            "Contents".to_owned() => RbxValue::String {
+                value: contents,
+            },
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use memofs::{InMemoryFs, VfsSnapshot};
+
+    #[test]
+    fn loc_json_from_vfs() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo.loc.json",
+            VfsSnapshot::file(
+                r#"
+                    [
+                      {
+                        "key": "Ack",
+                        "source": "Ack!",
+                        "example": "An exclamation of despair",
+                        "values": {
+                          "es": "¡Ay!"
+                        }
+                      }
+                    ]
+                "#,
+            ),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let instance_snapshot = SnapshotLocalization::from_vfs(
+            &InstanceContext::default(),
+            &vfs,
+            Path::new("/foo.loc.json"),
+        )
+        .unwrap()
+        .unwrap();
+
+        insta::assert_yaml_snapshot!(instance_snapshot);
+    }
+
+    #[test]
+    fn locale_dir_from_vfs() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/Strings",
+            VfsSnapshot::dir(hashmap! {
+                "init.loc.json" => VfsSnapshot::file(r#"
+                    [
+                      {
+                        "key": "Greeting",
+                        "source": "Hello!",
+                        "context": "Shown when a player joins"
+                      }
+                    ]
+                "#),
+                "de.json" => VfsSnapshot::file(r#"
+                    {
+                      "Greeting": "Hallo!",
+                      "Farewell": "Tschüss!"
+                    }
+                "#),
+                "es.json" => VfsSnapshot::file(r#"
+                    {
+                      "Greeting": "¡Hola!"
+                    }
+                "#),
+                "init.meta.json" => VfsSnapshot::file(r#"
+                    {
+                      "properties": {
+                        "SourceLocaleId": "en-us"
+                      }
+                    }
+                "#),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let instance_snapshot = SnapshotLocalization::from_vfs(
+            &InstanceContext::default(),
+            &vfs,
+            Path::new("/Strings"),
+        )
+        .unwrap()
+        .unwrap();
+
+        insta::with_settings!({ sort_maps => true }, {
+            insta::assert_yaml_snapshot!(instance_snapshot);
+        });
+    }
+
+    #[test]
+    fn dir_without_init() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/Strings",
+            VfsSnapshot::dir(hashmap! {
+                "de.json" => VfsSnapshot::file("{}"),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let result = SnapshotLocalization::from_vfs(
+            &InstanceContext::default(),
+            &vfs,
+            Path::new("/Strings"),
+        )
+        .unwrap();
+
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn dir_with_meta_class_name() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/Strings",
+            VfsSnapshot::dir(hashmap! {
+                "init.meta.json" => VfsSnapshot::file(r#"{ "className": "LocalizationTable" }"#),
+                "de.json" => VfsSnapshot::file(r#"{ "Greeting": "Hallo!" }"#),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let instance_snapshot = SnapshotLocalization::from_vfs(
+            &InstanceContext::default(),
+            &vfs,
+            Path::new("/Strings"),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(instance_snapshot.class_name, "LocalizationTable");
+        assert_eq!(
+            instance_snapshot.properties.get("Contents"),
+            Some(&RbxValue::String {
+                value: r#"[{"key":"Greeting","values":{"de":"Hallo!"}}]"#.to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn dir_with_empty_init() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/Strings",
+            VfsSnapshot::dir(hashmap! {
+                "init.loc.json" => VfsSnapshot::file(""),
+                "de.json" => VfsSnapshot::file(r#"{ "Greeting": "Hallo!" }"#),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let instance_snapshot = SnapshotLocalization::from_vfs(
+            &InstanceContext::default(),
+            &vfs,
+            Path::new("/Strings"),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            instance_snapshot.properties.get("Contents"),
+            Some(&RbxValue::String {
+                value: r#"[{"key":"Greeting","values":{"de":"Hallo!"}}]"#.to_owned(),
+            })
+        );
+    }
+
+    #[test]
+    fn malformed_locale_file() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/Strings",
+            VfsSnapshot::dir(hashmap! {
+                "init.loc.json" => VfsSnapshot::file("[]"),
+                "de.json" => VfsSnapshot::file(r#"{ "Greeting": 5 }"#),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let result = SnapshotLocalization::from_vfs(
+            &InstanceContext::default(),
+            &vfs,
+            Path::new("/Strings"),
+        );
+
+        match result {
+            Err(SnapshotError::MalformedLocalizationJson { path, .. }) => {
+                assert_eq!(path, Path::new("/Strings/de.json"));
+            }
+            other => panic!("expected malformed localization error, got {:?}", other),
+        }
+    }
+}
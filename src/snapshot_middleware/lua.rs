@@ -1,142 +1,40 @@
-use std::{path::Path, str};
+use std::path::Path;
 
-use maplit::hashmap;
-use memofs::{IoResultExt, Vfs};
-use rbx_dom_weak::RbxValue;
+use memofs::Vfs;
 
-use crate::snapshot::{InstanceContext, InstanceMetadata, InstanceSnapshot};
+use crate::snapshot::InstanceContext;
 
 use super::{
-    dir::SnapshotDir,
-    meta_file::AdjacentMetadata,
+    file_rules::{snapshot_init, snapshot_with_rules, LUA_RULES},
     middleware::{SnapshotInstanceResult, SnapshotMiddleware},
-    util::match_trailing,
 };
 
 pub struct SnapshotLua;
 
 impl SnapshotMiddleware for SnapshotLua {
     fn from_vfs(context: &InstanceContext, vfs: &Vfs, path: &Path) -> SnapshotInstanceResult {
-        let file_name = path.file_name().unwrap().to_string_lossy();
-
-        // These paths alter their parent instance, so we don't need to turn
-        // them into a script instance here.
-        match &*file_name {
-            "init.lua" | "init.server.lua" | "init.client.lua" => return Ok(None),
-            _ => {}
-        }
-
         let meta = vfs.metadata(path)?;
 
         if meta.is_file() {
-            snapshot_lua_file(context, vfs, path)
+            snapshot_with_rules(context, vfs, path, LUA_RULES.iter())
         } else {
-            // At this point, our entry is definitely a directory!
-
-            if let Some(snapshot) = snapshot_init(context, vfs, path, "init.lua")? {
-                // An `init.lua` file turns its parent into a ModuleScript
-                Ok(Some(snapshot))
-            } else if let Some(snapshot) = snapshot_init(context, vfs, path, "init.server.lua")? {
-                // An `init.server.lua` file turns its parent into a Script
-                Ok(Some(snapshot))
-            } else if let Some(snapshot) = snapshot_init(context, vfs, path, "init.client.lua")? {
-                // An `init.client.lua` file turns its parent into a LocalScript
-                Ok(Some(snapshot))
-            } else {
-                Ok(None)
-            }
+            // A directory holding an `init.lua`, `init.server.lua`, or
+            // `init.client.lua` file turns into a ModuleScript, Script, or
+            // LocalScript respectively.
+            snapshot_init(context, vfs, path, &LUA_RULES)
         }
     }
 }
 
-/// Core routine for turning Lua files into snapshots.
-fn snapshot_lua_file(context: &InstanceContext, vfs: &Vfs, path: &Path) -> SnapshotInstanceResult {
-    let file_name = path.file_name().unwrap().to_string_lossy();
-
-    let (class_name, instance_name) = if let Some(name) = match_trailing(&file_name, ".server.lua")
-    {
-        ("Script", name)
-    } else if let Some(name) = match_trailing(&file_name, ".client.lua") {
-        ("LocalScript", name)
-    } else if let Some(name) = match_trailing(&file_name, ".lua") {
-        ("ModuleScript", name)
-    } else {
-        return Ok(None);
-    };
-
-    let contents = vfs.read(path)?;
-    let contents_str = str::from_utf8(&contents)
-        // TODO: Turn into error type
-        .expect("File content was not valid UTF-8")
-        .to_string();
-
-    let meta_path = path.with_file_name(format!("{}.meta.json", instance_name));
-
-    let mut snapshot = InstanceSnapshot::new()
-        .name(instance_name)
-        .class_name(class_name)
-        .properties(hashmap! {
-            "Source".to_owned() => RbxValue::String {
-                value: contents_str,
-            },
-        })
-        .metadata(
-            InstanceMetadata::new()
-                .instigating_source(path)
-                .relevant_paths(vec![path.to_path_buf(), meta_path.clone()])
-                .context(context),
-        );
-
-    if let Some(meta_contents) = vfs.read(&meta_path).with_not_found()? {
-        let mut metadata = AdjacentMetadata::from_slice(&meta_contents, &meta_path)?;
-        metadata.apply_all(&mut snapshot);
-    }
-
-    Ok(Some(snapshot))
-}
-
-/// Attempts to snapshot an 'init' Lua script contained inside of a folder with
-/// the given name.
-///
-/// Scripts named `init.lua`, `init.server.lua`, or `init.client.lua` usurp
-/// their parents, which acts similarly to `__init__.py` from the Python world.
-fn snapshot_init(
-    context: &InstanceContext,
-    vfs: &Vfs,
-    folder_path: &Path,
-    init_name: &str,
-) -> SnapshotInstanceResult {
-    let init_path = folder_path.join(init_name);
-
-    if vfs.metadata(&init_path).with_not_found()?.is_some() {
-        if let Some(dir_snapshot) = SnapshotDir::from_vfs(context, vfs, folder_path)? {
-            if let Some(mut init_snapshot) = snapshot_lua_file(context, vfs, &init_path)? {
-                if dir_snapshot.class_name != "Folder" {
-                    panic!(
-                        "init.lua, init.server.lua, and init.client.lua can \
-                         only be used if the instance produced by the parent \
-                         directory would be a Folder."
-                    );
-                }
-
-                init_snapshot.name = dir_snapshot.name;
-                init_snapshot.children = dir_snapshot.children;
-                init_snapshot.metadata = dir_snapshot.metadata;
-
-                return Ok(Some(init_snapshot));
-            }
-        }
-    }
-
-    Ok(None)
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
 
+    use maplit::hashmap;
     use memofs::{InMemoryFs, VfsSnapshot};
 
+    use crate::snapshot_middleware::SnapshotError;
+
     #[test]
     fn module_from_vfs() {
         let mut imfs = InMemoryFs::new();
@@ -302,4 +200,60 @@ mod test {
             insta::assert_yaml_snapshot!(instance_snapshot);
         });
     }
+
+    #[test]
+    fn module_bad_unicode() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/foo.lua", VfsSnapshot::file(&[0xff, 0xfe][..]))
+            .unwrap();
+
+        let mut vfs = Vfs::new(imfs);
+
+        let result =
+            SnapshotLua::from_vfs(&InstanceContext::default(), &mut vfs, Path::new("/foo.lua"));
+
+        match result {
+            Err(SnapshotError::FileContentsBadUnicode { path, .. }) => {
+                assert_eq!(path, Path::new("/foo.lua"));
+            }
+            other => panic!("expected bad Unicode error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn module_with_bad_meta_property() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/foo.lua", VfsSnapshot::file("Hello there!"))
+            .unwrap();
+        imfs.load_snapshot(
+            "/foo.meta.json",
+            VfsSnapshot::file(
+                r#"
+                    {
+                        "properties": {
+                            "Disabled": "not a bool"
+                        }
+                    }
+                "#,
+            ),
+        )
+        .unwrap();
+
+        let mut vfs = Vfs::new(imfs);
+
+        let result =
+            SnapshotLua::from_vfs(&InstanceContext::default(), &mut vfs, Path::new("/foo.lua"));
+
+        match result {
+            Err(SnapshotError::PropertyResolution {
+                property_name,
+                path,
+                ..
+            }) => {
+                assert_eq!(property_name, "Disabled");
+                assert_eq!(path, Path::new("/foo.meta.json"));
+            }
+            other => panic!("expected property resolution error, got {:?}", other),
+        }
+    }
 }
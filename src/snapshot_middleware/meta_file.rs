@@ -1,18 +1,23 @@
-use std::{borrow::Cow, collections::HashMap, path::Path};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
-use rbx_dom_weak::UnresolvedRbxValue;
+use memofs::{IoResultExt, Vfs};
+use rbx_dom_weak::{RbxValue, UnresolvedRbxValue};
 use rbx_reflection::try_resolve_value;
 use serde::{Deserialize, Serialize};
 
 use crate::snapshot::InstanceSnapshot;
 
-use super::error::SnapshotError;
+use super::{attributes::add_attributes, error::SnapshotError, tags::add_tags};
 
 /// Represents metadata in a sibling file with the same basename.
 ///
 /// As an example, hello.meta.json next to hello.lua would allow assigning
 /// additional metadata to the instance resulting from hello.lua.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AdjacentMetadata {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -20,12 +25,53 @@ pub struct AdjacentMetadata {
 
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub properties: HashMap<String, UnresolvedRbxValue>,
+
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub attributes: HashMap<String, UnresolvedRbxValue>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// The class of the instance that holds every top-level instance of a
+    /// model file with more than one, which defaults to Folder, or the class of
+    /// the instance made from an image or audio file. Other files can't use
+    /// it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class_name: Option<String>,
+
+    /// Turns problems found in a localization CSV, like duplicate keys, into
+    /// errors instead of warnings. Other files can't use it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+
+    /// The path this metadata was read from, used for error messages.
+    #[serde(skip)]
+    pub path: PathBuf,
 }
 
 impl AdjacentMetadata {
     pub fn from_slice(slice: &[u8], path: &Path) -> Result<Self, SnapshotError> {
-        serde_json::from_slice(slice)
-            .map_err(|source| SnapshotError::malformed_meta_json(source, path))
+        let mut metadata: Self = serde_json::from_slice(slice)
+            .map_err(|source| SnapshotError::malformed_meta_json(source, path))?;
+
+        metadata.path = path.to_path_buf();
+
+        Ok(metadata)
+    }
+
+    /// Reads the metadata next to the file at `path`, which turns into an
+    /// instance named `instance_name`, if there is any.
+    pub fn read_adjacent(
+        vfs: &Vfs,
+        path: &Path,
+        instance_name: &str,
+    ) -> Result<Option<Self>, SnapshotError> {
+        let meta_path = adjacent_meta_path(path, instance_name);
+
+        match vfs.read(&meta_path).with_not_found()? {
+            Some(contents) => Ok(Some(Self::from_slice(&contents, &meta_path)?)),
+            None => Ok(None),
+        }
     }
 
     pub fn apply_ignore_unknown_instances(&mut self, snapshot: &mut InstanceSnapshot) {
@@ -34,34 +80,96 @@ impl AdjacentMetadata {
         }
     }
 
-    pub fn apply_properties(&mut self, snapshot: &mut InstanceSnapshot) {
-        let class_name = &snapshot.class_name;
+    pub fn apply_properties(
+        &mut self,
+        snapshot: &mut InstanceSnapshot,
+    ) -> Result<(), SnapshotError> {
+        let properties =
+            resolve_properties(&snapshot.class_name, &mut self.properties, &self.path)?;
+        snapshot.properties.extend(properties);
 
-        let source_properties = self.properties.drain().map(|(key, value)| {
-            try_resolve_value(class_name, &key, &value)
-                .map(|resolved| (key, resolved))
-                .expect("TODO: Handle rbx_reflection errors")
-        });
+        Ok(())
+    }
 
-        for (key, value) in source_properties {
-            snapshot.properties.insert(key, value);
-        }
+    pub fn apply_attributes(
+        &mut self,
+        snapshot: &mut InstanceSnapshot,
+    ) -> Result<(), SnapshotError> {
+        apply_attributes(&mut self.attributes, snapshot, &self.path)
     }
 
-    pub fn apply_all(&mut self, snapshot: &mut InstanceSnapshot) {
+    pub fn apply_tags(&mut self, snapshot: &mut InstanceSnapshot) {
+        apply_tags(&mut self.tags, snapshot);
+    }
+
+    /// Applies every part of the metadata that works for any instance.
+    ///
+    /// `className` and `strict` only mean something to some middleware, which
+    /// take them out of the metadata before this is called. If they're still
+    /// here, they were given for a file that can't use them.
+    pub fn apply_all(&mut self, snapshot: &mut InstanceSnapshot) -> Result<(), SnapshotError> {
+        if self.class_name.is_some() {
+            return Err(SnapshotError::unused_meta_field("className", &self.path));
+        }
+
+        if self.strict.is_some() {
+            return Err(SnapshotError::unused_meta_field("strict", &self.path));
+        }
+
         self.apply_ignore_unknown_instances(snapshot);
-        self.apply_properties(snapshot);
+        self.apply_properties(snapshot)?;
+        self.apply_attributes(snapshot)?;
+        self.apply_tags(snapshot);
+
+        Ok(())
+    }
+}
+
+/// Applies the metadata next to the file at `path` to the snapshot that was
+/// made from it, if there is any. Every middleware that turns a single file
+/// into an instance goes through this.
+///
+/// The meta file is marked as relevant to the snapshot even if it doesn't
+/// exist, so that creating it later causes the file to be snapshotted again.
+pub fn apply_adjacent_metadata(
+    vfs: &Vfs,
+    path: &Path,
+    snapshot: &mut InstanceSnapshot,
+) -> Result<(), SnapshotError> {
+    let metadata = AdjacentMetadata::read_adjacent(vfs, path, &snapshot.name)?;
+    apply_read_metadata(path, metadata, snapshot)
+}
+
+/// Like [`apply_adjacent_metadata`], but for metadata that the middleware
+/// already read so that it could take out the parts only it uses.
+pub fn apply_read_metadata(
+    path: &Path,
+    metadata: Option<AdjacentMetadata>,
+    snapshot: &mut InstanceSnapshot,
+) -> Result<(), SnapshotError> {
+    let meta_path = adjacent_meta_path(path, &snapshot.name);
+    let relevant_paths = &mut snapshot.metadata.relevant_paths;
+
+    if !relevant_paths.contains(&meta_path) {
+        relevant_paths.push(meta_path);
+    }
+
+    if let Some(mut metadata) = metadata {
+        metadata.apply_all(snapshot)?;
     }
 
-    // TODO: Add method to allow selectively applying parts of metadata and
-    // throwing errors if invalid parts are specified.
+    Ok(())
+}
+
+fn adjacent_meta_path(path: &Path, instance_name: &str) -> PathBuf {
+    path.with_file_name(format!("{}.meta.json", instance_name))
 }
 
 /// Represents metadata that affects the instance resulting from the containing
 /// folder.
 ///
 /// This is always sourced from a file named init.meta.json.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DirectoryMetadata {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -70,31 +178,50 @@ pub struct DirectoryMetadata {
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub properties: HashMap<String, UnresolvedRbxValue>,
 
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub attributes: HashMap<String, UnresolvedRbxValue>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub class_name: Option<String>,
+
+    /// The path this metadata was read from, used for error messages.
+    #[serde(skip)]
+    pub path: PathBuf,
 }
 
 impl DirectoryMetadata {
     pub fn from_slice(slice: &[u8], path: &Path) -> Result<Self, SnapshotError> {
-        serde_json::from_slice(slice)
-            .map_err(|source| SnapshotError::malformed_meta_json(source, path))
+        let mut metadata: Self = serde_json::from_slice(slice)
+            .map_err(|source| SnapshotError::malformed_meta_json(source, path))?;
+
+        metadata.path = path.to_path_buf();
+
+        Ok(metadata)
     }
 
-    pub fn apply_all(&mut self, snapshot: &mut InstanceSnapshot) {
+    pub fn apply_all(&mut self, snapshot: &mut InstanceSnapshot) -> Result<(), SnapshotError> {
         self.apply_ignore_unknown_instances(snapshot);
-        self.apply_class_name(snapshot);
-        self.apply_properties(snapshot);
+        self.apply_class_name(snapshot)?;
+        self.apply_properties(snapshot)?;
+        apply_attributes(&mut self.attributes, snapshot, &self.path)?;
+        apply_tags(&mut self.tags, snapshot);
+
+        Ok(())
     }
 
-    fn apply_class_name(&mut self, snapshot: &mut InstanceSnapshot) {
+    fn apply_class_name(&mut self, snapshot: &mut InstanceSnapshot) -> Result<(), SnapshotError> {
         if let Some(class_name) = self.class_name.take() {
             if snapshot.class_name != "Folder" {
-                // TODO: Turn into error type
-                panic!("className in init.meta.json can only be specified if the affected directory would turn into a Folder instance.");
+                return Err(SnapshotError::init_meta_class_name_not_folder(&self.path));
             }
 
             snapshot.class_name = Cow::Owned(class_name);
         }
+
+        Ok(())
     }
 
     fn apply_ignore_unknown_instances(&mut self, snapshot: &mut InstanceSnapshot) {
@@ -103,17 +230,52 @@ impl DirectoryMetadata {
         }
     }
 
-    fn apply_properties(&mut self, snapshot: &mut InstanceSnapshot) {
-        let class_name = &snapshot.class_name;
+    fn apply_properties(&mut self, snapshot: &mut InstanceSnapshot) -> Result<(), SnapshotError> {
+        let properties =
+            resolve_properties(&snapshot.class_name, &mut self.properties, &self.path)?;
+        snapshot.properties.extend(properties);
 
-        let source_properties = self.properties.drain().map(|(key, value)| {
-            try_resolve_value(class_name, &key, &value)
-                .map(|resolved| (key, resolved))
-                .expect("TODO: Handle rbx_reflection errors")
-        });
+        Ok(())
+    }
+}
 
-        for (key, value) in source_properties {
-            snapshot.properties.insert(key, value);
-        }
+/// Adds the given attributes to the snapshot, replacing any it already has
+/// with the same names.
+fn apply_attributes(
+    attributes: &mut HashMap<String, UnresolvedRbxValue>,
+    snapshot: &mut InstanceSnapshot,
+    path: &Path,
+) -> Result<(), SnapshotError> {
+    if attributes.is_empty() {
+        return Ok(());
     }
+
+    add_attributes(&mut snapshot.properties, attributes.iter(), path)?;
+    attributes.clear();
+
+    Ok(())
+}
+
+/// Adds the given tags to the ones the snapshot already has.
+fn apply_tags(tags: &mut Vec<String>, snapshot: &mut InstanceSnapshot) {
+    add_tags(&mut snapshot.properties, tags);
+    tags.clear();
+}
+
+fn resolve_properties(
+    class_name: &str,
+    properties: &mut HashMap<String, UnresolvedRbxValue>,
+    path: &Path,
+) -> Result<HashMap<String, RbxValue>, SnapshotError> {
+    properties
+        .drain()
+        .map(
+            |(key, value)| match try_resolve_value(class_name, &key, &value) {
+                Ok(resolved) => Ok((key, resolved)),
+                Err(source) => Err(SnapshotError::property_resolution(
+                    source, class_name, key, path,
+                )),
+            },
+        )
+        .collect()
 }
@@ -3,20 +3,30 @@
 
 #![allow(dead_code)]
 
+mod assets;
+mod attributes;
 mod csv;
 mod dir;
 mod error;
+mod file_rules;
 mod json;
 mod json_model;
+mod localization;
 mod lua;
 mod meta_file;
 mod middleware;
+mod model_tree;
 mod project;
 mod rbxlx;
 mod rbxm;
 mod rbxmx;
+mod registry;
+mod tags;
+mod toml;
 mod txt;
+mod user_plugins;
 mod util;
+mod yaml;
 
 use std::path::Path;
 
@@ -24,55 +34,26 @@ use memofs::Vfs;
 
 use crate::snapshot::InstanceContext;
 
-use self::{
-    csv::SnapshotCsv,
-    dir::SnapshotDir,
-    json::SnapshotJson,
-    json_model::SnapshotJsonModel,
-    lua::SnapshotLua,
-    middleware::{SnapshotInstanceResult, SnapshotMiddleware},
-    project::SnapshotProject,
-    rbxlx::SnapshotRbxlx,
-    rbxm::SnapshotRbxm,
-    rbxmx::SnapshotRbxmx,
-    txt::SnapshotTxt,
-};
+use self::middleware::SnapshotInstanceResult;
 
+pub use self::assets::{asset_content_property, relative_asset_path};
+pub use self::attributes::ATTRIBUTES_PROPERTY;
+pub use self::csv::convert_localization_csv;
 pub use self::error::*;
+pub use self::file_rules::{find_init_file, rule_for_path, suffix_for_class, FileRule};
+pub use self::json_model::{JsonModel, JsonModelCore, JsonModelInstance};
+pub use self::meta_file::{AdjacentMetadata, DirectoryMetadata};
 pub use self::project::snapshot_project_node;
-
-macro_rules! middlewares {
-    ( $($middleware: ident,)* ) => {
-        /// Generates a snapshot of instances from the given path.
-        pub fn snapshot_from_vfs(
-            context: &InstanceContext,
-            vfs: &Vfs,
-            path: &Path,
-        ) -> SnapshotInstanceResult {
-            $(
-                log::trace!("trying middleware {} on {}", stringify!($middleware), path.display());
-
-                if let Some(snapshot) = $middleware::from_vfs(context, vfs, path)? {
-                    log::trace!("middleware {} success on {}", stringify!($middleware), path.display());
-                    return Ok(Some(snapshot));
-                }
-            )*
-
-            log::trace!("no middleware returned Ok(Some)");
-            Ok(None)
-        }
-    };
-}
-
-middlewares! {
-    SnapshotProject,
-    SnapshotJsonModel,
-    SnapshotRbxlx,
-    SnapshotRbxmx,
-    SnapshotRbxm,
-    SnapshotLua,
-    SnapshotCsv,
-    SnapshotTxt,
-    SnapshotJson,
-    SnapshotDir,
+pub use self::registry::MiddlewareRegistry;
+pub use self::tags::TAGS_PROPERTY;
+pub use self::user_plugins::UserPlugin;
+
+/// Generates a snapshot of instances from the given path, using the middleware
+/// registered in the given context.
+pub fn snapshot_from_vfs(
+    context: &InstanceContext,
+    vfs: &Vfs,
+    path: &Path,
+) -> SnapshotInstanceResult {
+    context.middleware.snapshot(context, vfs, path)
 }
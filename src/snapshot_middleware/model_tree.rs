@@ -0,0 +1,66 @@
+//! Shared logic for turning the contents of model files like `.rbxm` and
+//! `.rbxmx` into instance snapshots.
+
+use std::{borrow::Cow, path::Path};
+
+use memofs::Vfs;
+use rbx_dom_weak::{RbxId, RbxTree};
+
+use crate::snapshot::{InstanceContext, InstanceMetadata, InstanceSnapshot};
+
+use super::{
+    error::SnapshotError,
+    meta_file::{apply_read_metadata, AdjacentMetadata},
+};
+
+/// Creates a snapshot out of the top-level instances of a decoded model file.
+///
+/// Models with a single top-level instance turn into that instance. Models
+/// with more than one turn into a Folder holding all of them. The model's
+/// adjacent `.meta.json` file is applied to the resulting instance, and can
+/// also give that container a different class with `className`. Empty models
+/// are an error, since there's nothing for them to turn into.
+pub fn snapshot_model_roots(
+    context: &InstanceContext,
+    vfs: &Vfs,
+    path: &Path,
+    instance_name: &str,
+    tree: &RbxTree,
+    roots: &[RbxId],
+) -> Result<InstanceSnapshot, SnapshotError> {
+    let metadata = InstanceMetadata::new()
+        .instigating_source(path)
+        .relevant_paths(vec![path.to_path_buf()])
+        .context(context);
+
+    let mut meta = AdjacentMetadata::read_adjacent(vfs, path, instance_name)?;
+
+    let mut snapshot = match roots {
+        [] => return Err(SnapshotError::empty_model(path)),
+        [root] => InstanceSnapshot::from_tree(tree, *root)
+            .name(instance_name)
+            .metadata(metadata),
+        _ => {
+            let children: Vec<_> = roots
+                .iter()
+                .map(|&id| InstanceSnapshot::from_tree(tree, id))
+                .collect();
+
+            let mut snapshot = InstanceSnapshot::new()
+                .name(instance_name)
+                .class_name("Folder")
+                .children(children)
+                .metadata(metadata);
+
+            if let Some(class_name) = meta.as_mut().and_then(|meta| meta.class_name.take()) {
+                snapshot.class_name = Cow::Owned(class_name);
+            }
+
+            snapshot
+        }
+    };
+
+    apply_read_metadata(path, meta, &mut snapshot)?;
+
+    Ok(snapshot)
+}
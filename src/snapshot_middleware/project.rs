@@ -1,4 +1,9 @@
-use std::{borrow::Cow, collections::HashMap, path::Path};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use memofs::{IoResultExt, Vfs};
 use rbx_reflection::{get_class_descriptor, try_resolve_value};
@@ -12,8 +17,11 @@ use crate::{
 
 use super::{
     error::SnapshotError,
+    file_rules::FileRule,
     middleware::{SnapshotInstanceResult, SnapshotMiddleware},
     snapshot_from_vfs,
+    tags::add_tags,
+    user_plugins::UserPlugin,
 };
 
 /// Handles snapshots for:
@@ -42,9 +50,13 @@ impl SnapshotMiddleware for SnapshotProject {
             return Ok(None);
         }
 
-        let project = Project::load_from_slice(&vfs.read(path)?, path)
+        let mut project = Project::load_from_slice(&vfs.read(path)?, path)
             .map_err(|err| SnapshotError::malformed_project(err, path))?;
 
+        if let Some(profile) = &context.profile {
+            project.apply_profile(profile);
+        }
+
         let mut context = context.clone();
 
         let rules = project.glob_ignore_paths.iter().map(|glob| PathIgnoreRule {
@@ -54,17 +66,47 @@ impl SnapshotMiddleware for SnapshotProject {
 
         context.add_path_ignore_rules(rules);
 
-        // Snapshotting a project should always return an instance, so this
-        // unwrap is safe.
-        let mut snapshot = snapshot_project_node(
-            &context,
-            project.folder_location(),
-            &project.name,
-            &project.tree,
-            vfs,
-            None,
-        )?
-        .unwrap();
+        if context.asset_root.is_none() {
+            context.asset_root = Some(Arc::from(project.folder_location()));
+        }
+
+        let plugin_paths: Vec<_> = project
+            .plugins
+            .iter()
+            .map(|plugin_path| project.folder_location().join(plugin_path))
+            .collect();
+
+        let plugins = plugin_paths
+            .iter()
+            .map(|plugin_path| {
+                let source = vfs.read(plugin_path)?;
+                UserPlugin::load(plugin_path, &source).map(Arc::new)
+            })
+            .collect::<Result<Vec<_>, SnapshotError>>()?;
+
+        context.add_user_plugins(plugins);
+
+        let file_rules = project
+            .file_rules
+            .iter()
+            .map(|(pattern, class_name)| FileRule::from_project(pattern, class_name, path))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        context.add_file_rules(file_rules);
+
+        if !project.middleware.is_empty() {
+            let middleware = Arc::make_mut(&mut context.middleware);
+
+            for (name, options) in &project.middleware {
+                middleware.configure(name, options, path)?;
+            }
+        }
+
+        // Project nodes only skip returning an instance when they're turned
+        // off, which isn't allowed for the root of a project.
+        let mut snapshot =
+            snapshot_project_node(&context, path, &project.name, &project.tree, vfs, None)?
+                .ok_or_else(|| SnapshotError::project_root_disabled(path))?;
 
         // Setting the instigating source to the project file path is a little
         // coarse.
@@ -84,18 +126,68 @@ impl SnapshotMiddleware for SnapshotProject {
         // file being updated.
         snapshot.metadata.relevant_paths.push(path.to_path_buf());
 
+        // Changing a plugin can change how any file in the project is
+        // snapshotted, so plugins are relevant to the whole project too.
+        snapshot.metadata.relevant_paths.extend(plugin_paths);
+
         Ok(Some(snapshot))
     }
 }
 
 pub fn snapshot_project_node(
     context: &InstanceContext,
-    project_folder: &Path,
+    project_path: &Path,
     instance_name: &str,
     node: &ProjectNode,
     vfs: &Vfs,
     parent_class: Option<&str>,
 ) -> SnapshotInstanceResult {
+    snapshot_project_node_inner(
+        context,
+        project_path,
+        instance_name,
+        node,
+        vfs,
+        parent_class,
+        &[],
+    )
+}
+
+/// Snapshots a project node, keeping track of every project that was pulled in
+/// with `$include` on the way down to this node in `include_chain` so that
+/// projects including themselves can be caught.
+fn snapshot_project_node_inner(
+    context: &InstanceContext,
+    project_path: &Path,
+    instance_name: &str,
+    node: &ProjectNode,
+    vfs: &Vfs,
+    parent_class: Option<&str>,
+    include_chain: &[PathBuf],
+) -> SnapshotInstanceResult {
+    let original_node = node;
+    let mut include_chain = include_chain.to_vec();
+    let first_include = include_chain.len();
+
+    let resolved_node;
+    let node = if node.include.is_some() {
+        resolved_node = resolve_includes(
+            context,
+            vfs,
+            project_path,
+            instance_name,
+            node,
+            &mut include_chain,
+        )?;
+        &resolved_node
+    } else {
+        node
+    };
+
+    if node.enabled == Some(false) {
+        return Ok(None);
+    }
+
     let name = Cow::Owned(instance_name.to_owned());
     let mut class_name = node
         .class_name
@@ -107,9 +199,9 @@ pub fn snapshot_project_node(
 
     if let Some(path) = &node.path {
         // If the path specified in the project is relative, we assume it's
-        // relative to the folder that the project is in, project_folder.
+        // relative to the folder that the project is in.
         let path = if path.is_relative() {
-            Cow::Owned(project_folder.join(path))
+            Cow::Owned(project_folder(project_path).join(path))
         } else {
             Cow::Borrowed(path)
         };
@@ -127,8 +219,11 @@ pub fn snapshot_project_node(
                     if snapshot.class_name == "Folder" {
                         Some(class_name)
                     } else {
-                        // TODO: Turn this into an error object.
-                        panic!("If $className and $path are specified, $path must yield an instance of class Folder");
+                        return Err(SnapshotError::project_node_class_name_conflict(
+                            instance_name,
+                            snapshot.class_name,
+                            path.into_owned(),
+                        ));
                     }
                 }
                 None => Some(snapshot.class_name),
@@ -185,29 +280,39 @@ pub fn snapshot_project_node(
 
             None
         })
-        // TODO: Turn this into an error object.
-        .expect("$className or $path must be specified");
+        .ok_or_else(|| {
+            SnapshotError::project_node_missing_class_name(instance_name, project_path)
+        })?;
 
     for (child_name, child_project_node) in &node.children {
-        if let Some(child) = snapshot_project_node(
+        if let Some(child) = snapshot_project_node_inner(
             context,
-            project_folder,
+            project_path,
             child_name,
             child_project_node,
             vfs,
             Some(&class_name),
+            &include_chain,
         )? {
             children.push(child);
         }
     }
 
     for (key, value) in &node.properties {
-        let resolved_value = try_resolve_value(&class_name, key, value)
-            .expect("TODO: Properly handle value resolution errors");
+        let resolved_value = try_resolve_value(&class_name, key, value).map_err(|source| {
+            SnapshotError::property_resolution(
+                source,
+                class_name.as_ref(),
+                key.as_str(),
+                project_path,
+            )
+        })?;
 
         properties.insert(key.clone(), resolved_value);
     }
 
+    add_tags(&mut properties, &node.tags);
+
     // If the user specified $ignoreUnknownInstances, overwrite the existing
     // value.
     //
@@ -222,10 +327,22 @@ pub fn snapshot_project_node(
         metadata.ignore_unknown_instances = true;
     }
 
+    // Projects pulled in by this node are only read while snapshotting it, so
+    // this instance is the one that needs to be snapshotted again when they
+    // change. The node is kept with its $include intact for that reason.
+    metadata
+        .relevant_paths
+        .extend(include_chain.drain(first_include..));
+
+    // Nodes without a $path don't get a context from snapshotting a file, but
+    // need one to be snapshotted the same way again later, like with the same
+    // profile.
+    metadata.context = context.clone();
+
     metadata.instigating_source = Some(InstigatingSource::ProjectNode(
-        project_folder.to_path_buf(),
+        project_path.to_path_buf(),
         instance_name.to_string(),
-        node.clone(),
+        Box::new(original_node.clone()),
         parent_class.map(|name| name.to_owned()),
     ));
 
@@ -239,12 +356,63 @@ pub fn snapshot_project_node(
     }))
 }
 
+/// Follows the `$include` of a project node, along with any `$include` at the
+/// root of the included tree, and layers the node on top of what it includes.
+/// Included projects get the active profile applied, like any other project.
+fn resolve_includes(
+    context: &InstanceContext,
+    vfs: &Vfs,
+    project_path: &Path,
+    instance_name: &str,
+    node: &ProjectNode,
+    include_chain: &mut Vec<PathBuf>,
+) -> Result<ProjectNode, SnapshotError> {
+    let mut node = node.clone();
+
+    while let Some(include) = node.include.take() {
+        let include_path = project_folder(project_path).join(include);
+
+        if include_chain.contains(&include_path) {
+            return Err(SnapshotError::project_include_cycle(
+                instance_name,
+                include_path,
+            ));
+        }
+
+        let contents = vfs.read(&include_path).with_not_found()?.ok_or_else(|| {
+            SnapshotError::project_include_not_found(instance_name, &include_path)
+        })?;
+
+        let mut project = Project::load_from_slice(&contents, &include_path)
+            .map_err(|err| SnapshotError::malformed_project(err, &include_path))?;
+
+        if let Some(profile) = &context.profile {
+            project.apply_profile(profile);
+        }
+
+        let included_folder = project.folder_location().to_path_buf();
+        project.tree.rebase_paths(&included_folder);
+
+        include_chain.push(include_path);
+        node = node.with_base(project.tree);
+    }
+
+    Ok(node)
+}
+
+/// Finds the folder that paths in the project at the given path are relative
+/// to.
+fn project_folder(project_path: &Path) -> &Path {
+    project_path.parent().unwrap_or(project_path)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
     use maplit::hashmap;
     use memofs::{InMemoryFs, VfsSnapshot};
+    use rbx_dom_weak::RbxValue;
 
     #[test]
     fn project_from_folder() {
@@ -526,6 +694,337 @@ mod test {
     /// Ensures that if a property is defined both in the resulting instance
     /// from $path and also in $properties, that the $properties value takes
     /// precedence.
+    #[test]
+    fn project_with_include() {
+        let _ = env_logger::try_init();
+
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "default.project.json" => VfsSnapshot::file(r#"
+                    {
+                        "name": "include-project",
+                        "tree": {
+                            "$include": "shared/base.project.json",
+                            "$className": "Model",
+
+                            "Greeting": {
+                                "$properties": {
+                                    "Value": "Overridden"
+                                }
+                            },
+                            "Extra": {
+                                "$className": "BoolValue"
+                            }
+                        }
+                    }
+                "#),
+                "shared" => VfsSnapshot::dir(hashmap! {
+                    "base.project.json" => VfsSnapshot::file(r#"
+                        {
+                            "name": "base-project",
+                            "tree": {
+                                "$className": "Folder",
+
+                                "Greeting": {
+                                    "$className": "StringValue",
+                                    "$properties": {
+                                        "Value": "Hello"
+                                    }
+                                },
+                                "Notes": {
+                                    "$path": "notes.txt"
+                                }
+                            }
+                        }
+                    "#),
+                    "notes.txt" => VfsSnapshot::file("Shared notes"),
+                }),
+            }),
+        )
+        .unwrap();
+
+        let mut vfs = Vfs::new(imfs);
+
+        let instance_snapshot =
+            SnapshotProject::from_vfs(&InstanceContext::default(), &mut vfs, Path::new("/foo"))
+                .expect("snapshot error")
+                .expect("snapshot returned no instances");
+
+        insta::assert_yaml_snapshot!(instance_snapshot);
+    }
+
+    #[test]
+    fn project_with_include_profile() {
+        let _ = env_logger::try_init();
+
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "default.project.json" => VfsSnapshot::file(r#"
+                    {
+                        "name": "include-profile-project",
+                        "tree": {
+                            "$include": "shared/base.project.json"
+                        }
+                    }
+                "#),
+                "shared" => VfsSnapshot::dir(hashmap! {
+                    "base.project.json" => VfsSnapshot::file(r#"
+                        {
+                            "name": "base-project",
+                            "tree": {
+                                "$className": "Folder",
+
+                                "Mode": {
+                                    "$className": "StringValue",
+                                    "$properties": {
+                                        "Value": "release"
+                                    }
+                                }
+                            },
+                            "profiles": {
+                                "dev": {
+                                    "tree": {
+                                        "Mode": {
+                                            "$properties": {
+                                                "Value": "dev"
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    "#),
+                }),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+        let context = InstanceContext {
+            profile: Some("dev".to_owned()),
+            ..InstanceContext::default()
+        };
+
+        let instance_snapshot = SnapshotProject::from_vfs(&context, &vfs, Path::new("/foo"))
+            .expect("snapshot error")
+            .expect("snapshot returned no instances");
+
+        assert_eq!(instance_snapshot.children.len(), 1);
+        assert_eq!(
+            instance_snapshot.children[0].properties.get("Value"),
+            Some(&RbxValue::String {
+                value: "dev".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn project_tags_are_added() {
+        let _ = env_logger::try_init();
+
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "default.project.json" => VfsSnapshot::file(r#"
+                    {
+                        "name": "tags-project",
+                        "tree": {
+                            "$include": "base.project.json",
+                            "$tags": ["Zone"],
+
+                            "Enemy": {
+                                "$path": "enemy.model.json",
+                                "$tags": ["Boss", "Flying"]
+                            }
+                        }
+                    }
+                "#),
+                "base.project.json" => VfsSnapshot::file(r#"
+                    {
+                        "name": "base-project",
+                        "tree": {
+                            "$className": "Folder",
+                            "$tags": ["Arena", "Zone"]
+                        }
+                    }
+                "#),
+                "enemy.model.json" => VfsSnapshot::file(r#"
+                    {
+                        "Name": "Enemy",
+                        "ClassName": "Model",
+                        "Tags": ["Enemy", "Boss"]
+                    }
+                "#),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let instance_snapshot =
+            SnapshotProject::from_vfs(&InstanceContext::default(), &vfs, Path::new("/foo"))
+                .expect("snapshot error")
+                .expect("snapshot returned no instances");
+
+        let tags = |snapshot: &InstanceSnapshot| snapshot.properties.get("Tags").cloned();
+
+        assert_eq!(
+            tags(&instance_snapshot),
+            Some(RbxValue::BinaryString {
+                value: b"Arena\0Zone".to_vec(),
+            })
+        );
+        assert_eq!(
+            tags(&instance_snapshot.children[0]),
+            Some(RbxValue::BinaryString {
+                value: b"Enemy\0Boss\0Flying".to_vec(),
+            })
+        );
+    }
+
+    #[test]
+    fn project_with_include_cycle() {
+        let _ = env_logger::try_init();
+
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "default.project.json" => VfsSnapshot::file(r#"
+                    {
+                        "name": "include-cycle",
+                        "tree": {
+                            "$include": "other.project.json"
+                        }
+                    }
+                "#),
+                "other.project.json" => VfsSnapshot::file(r#"
+                    {
+                        "name": "other",
+                        "tree": {
+                            "$className": "Folder",
+
+                            "Child": {
+                                "$include": "default.project.json"
+                            }
+                        }
+                    }
+                "#),
+            }),
+        )
+        .unwrap();
+
+        let mut vfs = Vfs::new(imfs);
+
+        let result =
+            SnapshotProject::from_vfs(&InstanceContext::default(), &mut vfs, Path::new("/foo"));
+
+        match result {
+            Err(SnapshotError::ProjectIncludeCycle { name, path }) => {
+                assert_eq!(name, "Child");
+                assert_eq!(path, Path::new("/foo/other.project.json"));
+            }
+            other => panic!("expected include cycle error, got {:?}", other),
+        }
+    }
+
+    fn profile_vfs() -> Vfs {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "default.project.json" => VfsSnapshot::file(r#"
+                    {
+                        "name": "profile-project",
+                        "tree": {
+                            "$className": "Folder",
+
+                            "Tests": {
+                                "$className": "Folder",
+                                "$enabled": false
+                            },
+                            "Config": {
+                                "$className": "StringValue",
+                                "$properties": {
+                                    "Value": "release"
+                                }
+                            },
+                            "Assets": {
+                                "$path": "release.txt"
+                            }
+                        },
+                        "profiles": {
+                            "dev": {
+                                "tree": {
+                                    "Tests": {
+                                        "$enabled": true
+                                    },
+                                    "Config": {
+                                        "$properties": {
+                                            "Value": "dev"
+                                        }
+                                    },
+                                    "Assets": {
+                                        "$path": "dev.txt"
+                                    },
+                                    "Debug": {
+                                        "$className": "Folder"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                "#),
+                "release.txt" => VfsSnapshot::file("Release assets"),
+                "dev.txt" => VfsSnapshot::file("Dev assets"),
+            }),
+        )
+        .unwrap();
+
+        Vfs::new(imfs)
+    }
+
+    #[test]
+    fn project_without_profile() {
+        let _ = env_logger::try_init();
+
+        let mut vfs = profile_vfs();
+
+        let instance_snapshot =
+            SnapshotProject::from_vfs(&InstanceContext::default(), &mut vfs, Path::new("/foo"))
+                .expect("snapshot error")
+                .expect("snapshot returned no instances");
+
+        let children: Vec<_> = instance_snapshot
+            .children
+            .iter()
+            .map(|child| child.name.as_ref())
+            .collect();
+
+        assert_eq!(children, vec!["Assets", "Config"]);
+    }
+
+    #[test]
+    fn project_with_profile() {
+        let _ = env_logger::try_init();
+
+        let mut vfs = profile_vfs();
+
+        let mut context = InstanceContext::default();
+        context.profile = Some("dev".to_owned());
+
+        let instance_snapshot = SnapshotProject::from_vfs(&context, &mut vfs, Path::new("/foo"))
+            .expect("snapshot error")
+            .expect("snapshot returned no instances");
+
+        insta::assert_yaml_snapshot!(instance_snapshot);
+    }
+
     #[test]
     fn project_path_property_overrides() {
         let _ = env_logger::try_init();
@@ -569,4 +1068,39 @@ mod test {
 
         insta::assert_yaml_snapshot!(instance_snapshot);
     }
+
+    #[test]
+    fn project_with_class_name_and_path_to_script() {
+        let _ = env_logger::try_init();
+
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "default.project.json" => VfsSnapshot::file(r#"
+                    {
+                        "name": "conflict-project",
+                        "tree": {
+                            "$className": "ReplicatedStorage",
+                            "$path": "main.lua"
+                        }
+                    }
+                "#),
+                "main.lua" => VfsSnapshot::file("print('Hello')"),
+            }),
+        )
+        .unwrap();
+
+        let mut vfs = Vfs::new(imfs);
+
+        let result =
+            SnapshotProject::from_vfs(&InstanceContext::default(), &mut vfs, Path::new("/foo"));
+
+        match result {
+            Err(SnapshotError::ProjectNodeClassNameConflict { class_name, .. }) => {
+                assert_eq!(class_name, "ModuleScript");
+            }
+            other => panic!("expected $className conflict error, got {:?}", other),
+        }
+    }
 }
@@ -5,6 +5,8 @@ use memofs::Vfs;
 use crate::snapshot::{InstanceContext, InstanceMetadata, InstanceSnapshot};
 
 use super::{
+    error::SnapshotError,
+    meta_file::apply_adjacent_metadata,
     middleware::{SnapshotInstanceResult, SnapshotMiddleware},
     util::match_file_name,
 };
@@ -28,11 +30,11 @@ impl SnapshotMiddleware for SnapshotRbxlx {
             .property_behavior(rbx_xml::DecodePropertyBehavior::ReadUnknown);
 
         let temp_tree = rbx_xml::from_reader(vfs.read(path)?.as_slice(), options)
-            .expect("TODO: Handle rbx_xml errors");
+            .map_err(|source| SnapshotError::malformed_rbxlx(source, path))?;
 
         let root_id = temp_tree.get_root_id();
 
-        let snapshot = InstanceSnapshot::from_tree(&temp_tree, root_id)
+        let mut snapshot = InstanceSnapshot::from_tree(&temp_tree, root_id)
             .name(instance_name)
             .metadata(
                 InstanceMetadata::new()
@@ -41,6 +43,8 @@ impl SnapshotMiddleware for SnapshotRbxlx {
                     .context(context),
             );
 
+        apply_adjacent_metadata(vfs, path, &mut snapshot)?;
+
         Ok(Some(snapshot))
     }
 }
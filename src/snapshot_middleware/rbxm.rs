@@ -3,10 +3,12 @@ use std::{collections::HashMap, path::Path};
 use memofs::Vfs;
 use rbx_dom_weak::{RbxInstanceProperties, RbxTree};
 
-use crate::snapshot::{InstanceContext, InstanceMetadata, InstanceSnapshot};
+use crate::snapshot::InstanceContext;
 
 use super::{
+    error::SnapshotError,
     middleware::{SnapshotInstanceResult, SnapshotMiddleware},
+    model_tree::snapshot_model_roots,
     util::match_file_name,
 };
 
@@ -33,25 +35,14 @@ impl SnapshotMiddleware for SnapshotRbxm {
 
         let root_id = temp_tree.get_root_id();
         rbx_binary::decode(&mut temp_tree, root_id, vfs.read(path)?.as_slice())
-            .expect("TODO: Handle rbx_binary errors");
+            .map_err(|source| SnapshotError::malformed_rbxm(source, path))?;
 
         let root_instance = temp_tree.get_instance(root_id).unwrap();
-        let children = root_instance.get_children_ids();
-
-        if children.len() == 1 {
-            let snapshot = InstanceSnapshot::from_tree(&temp_tree, children[0])
-                .name(instance_name)
-                .metadata(
-                    InstanceMetadata::new()
-                        .instigating_source(path)
-                        .relevant_paths(vec![path.to_path_buf()])
-                        .context(context),
-                );
-
-            Ok(Some(snapshot))
-        } else {
-            panic!("Rojo doesn't have support for model files with zero or more than one top-level instances yet.");
-        }
+        let roots = root_instance.get_children_ids();
+
+        let snapshot = snapshot_model_roots(context, vfs, path, instance_name, &temp_tree, roots)?;
+
+        Ok(Some(snapshot))
     }
 }
 
@@ -2,10 +2,12 @@ use std::path::Path;
 
 use memofs::Vfs;
 
-use crate::snapshot::{InstanceContext, InstanceMetadata, InstanceSnapshot};
+use crate::snapshot::InstanceContext;
 
 use super::{
+    error::SnapshotError,
     middleware::{SnapshotInstanceResult, SnapshotMiddleware},
+    model_tree::snapshot_model_roots,
     util::match_file_name,
 };
 
@@ -28,25 +30,14 @@ impl SnapshotMiddleware for SnapshotRbxmx {
             .property_behavior(rbx_xml::DecodePropertyBehavior::ReadUnknown);
 
         let temp_tree = rbx_xml::from_reader(vfs.read(path)?.as_slice(), options)
-            .expect("TODO: Handle rbx_xml errors");
+            .map_err(|source| SnapshotError::malformed_rbxmx(source, path))?;
 
         let root_instance = temp_tree.get_instance(temp_tree.get_root_id()).unwrap();
-        let children = root_instance.get_children_ids();
-
-        if children.len() == 1 {
-            let snapshot = InstanceSnapshot::from_tree(&temp_tree, children[0])
-                .name(instance_name)
-                .metadata(
-                    InstanceMetadata::new()
-                        .instigating_source(path)
-                        .relevant_paths(vec![path.to_path_buf()])
-                        .context(context),
-                );
-
-            Ok(Some(snapshot))
-        } else {
-            panic!("Rojo doesn't have support for model files with zero or more than one top-level instances yet.");
-        }
+        let roots = root_instance.get_children_ids();
+
+        let snapshot = snapshot_model_roots(context, vfs, path, instance_name, &temp_tree, roots)?;
+
+        Ok(Some(snapshot))
     }
 }
 
@@ -54,7 +45,11 @@ impl SnapshotMiddleware for SnapshotRbxmx {
 mod test {
     use super::*;
 
+    use std::path::PathBuf;
+
+    use maplit::hashmap;
     use memofs::{InMemoryFs, VfsSnapshot};
+    use rbx_dom_weak::RbxValue;
 
     #[test]
     fn plain_folder() {
@@ -90,4 +85,248 @@ mod test {
         assert_eq!(instance_snapshot.properties, Default::default());
         assert_eq!(instance_snapshot.children, Vec::new());
     }
+
+    #[test]
+    fn with_meta() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/root",
+            VfsSnapshot::dir(hashmap! {
+                "foo.rbxmx" => VfsSnapshot::file(r#"
+                    <roblox version="4">
+                        <Item class="StringValue" referent="0" />
+                    </roblox>
+                "#),
+                "foo.meta.json" => VfsSnapshot::file(r#"{
+                    "ignoreUnknownInstances": true,
+                    "properties": {
+                        "Value": "Hello"
+                    }
+                }"#),
+            }),
+        )
+        .unwrap();
+
+        let mut vfs = Vfs::new(imfs);
+
+        let instance_snapshot = SnapshotRbxmx::from_vfs(
+            &InstanceContext::default(),
+            &mut vfs,
+            Path::new("/root/foo.rbxmx"),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            instance_snapshot.properties.get("Value"),
+            Some(&RbxValue::String {
+                value: "Hello".to_owned()
+            })
+        );
+        assert!(instance_snapshot.metadata.ignore_unknown_instances);
+        assert_eq!(
+            instance_snapshot.metadata.relevant_paths,
+            vec![
+                PathBuf::from("/root/foo.rbxmx"),
+                PathBuf::from("/root/foo.meta.json")
+            ]
+        );
+    }
+
+    #[test]
+    fn meta_tags_are_added() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/root",
+            VfsSnapshot::dir(hashmap! {
+                "foo.rbxmx" => VfsSnapshot::file(r#"
+                    <roblox version="4">
+                        <Item class="Folder" referent="0">
+                            <Properties>
+                                <BinaryString name="Tags">RW5lbXk=</BinaryString>
+                            </Properties>
+                        </Item>
+                    </roblox>
+                "#),
+                "foo.meta.json" => VfsSnapshot::file(r#"{
+                    "tags": ["Boss"]
+                }"#),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let instance_snapshot = SnapshotRbxmx::from_vfs(
+            &InstanceContext::default(),
+            &vfs,
+            Path::new("/root/foo.rbxmx"),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            instance_snapshot.properties.get("Tags"),
+            Some(&RbxValue::BinaryString {
+                value: b"Enemy\0Boss".to_vec()
+            })
+        );
+    }
+
+    #[test]
+    fn multiple_roots() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo.rbxmx",
+            VfsSnapshot::file(
+                r#"
+                    <roblox version="4">
+                        <Item class="Folder" referent="0">
+                            <Properties>
+                                <string name="Name">First</string>
+                            </Properties>
+                        </Item>
+                        <Item class="StringValue" referent="1">
+                            <Properties>
+                                <string name="Name">Second</string>
+                            </Properties>
+                        </Item>
+                    </roblox>
+                "#,
+            ),
+        )
+        .unwrap();
+
+        let mut vfs = Vfs::new(imfs);
+
+        let instance_snapshot = SnapshotRbxmx::from_vfs(
+            &InstanceContext::default(),
+            &mut vfs,
+            Path::new("/foo.rbxmx"),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(instance_snapshot.name, "foo");
+        assert_eq!(instance_snapshot.class_name, "Folder");
+
+        let children: Vec<_> = instance_snapshot
+            .children
+            .iter()
+            .map(|child| (child.name.as_ref(), child.class_name.as_ref()))
+            .collect();
+
+        assert_eq!(
+            children,
+            vec![("First", "Folder"), ("Second", "StringValue")]
+        );
+    }
+
+    #[test]
+    fn multiple_roots_with_meta_class_name() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/root",
+            VfsSnapshot::dir(hashmap! {
+                "foo.rbxmx" => VfsSnapshot::file(r#"
+                    <roblox version="4">
+                        <Item class="Folder" referent="0" />
+                        <Item class="Folder" referent="1" />
+                    </roblox>
+                "#),
+                "foo.meta.json" => VfsSnapshot::file(r#"{ "className": "Model" }"#),
+            }),
+        )
+        .unwrap();
+
+        let mut vfs = Vfs::new(imfs);
+
+        let instance_snapshot = SnapshotRbxmx::from_vfs(
+            &InstanceContext::default(),
+            &mut vfs,
+            Path::new("/root/foo.rbxmx"),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(instance_snapshot.class_name, "Model");
+        assert_eq!(instance_snapshot.children.len(), 2);
+    }
+
+    #[test]
+    fn single_root_with_meta_class_name() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/root",
+            VfsSnapshot::dir(hashmap! {
+                "foo.rbxmx" => VfsSnapshot::file(r#"
+                    <roblox version="4">
+                        <Item class="Folder" referent="0" />
+                    </roblox>
+                "#),
+                "foo.meta.json" => VfsSnapshot::file(r#"{ "className": "Model" }"#),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let result = SnapshotRbxmx::from_vfs(
+            &InstanceContext::default(),
+            &vfs,
+            Path::new("/root/foo.rbxmx"),
+        );
+
+        match result {
+            Err(SnapshotError::UnusedMetaField { field, path }) => {
+                assert_eq!(field, "className");
+                assert_eq!(path, Path::new("/root/foo.meta.json"));
+            }
+            other => panic!("expected unused meta field error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn empty_model() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/foo.rbxmx", VfsSnapshot::file(r#"<roblox version="4" />"#))
+            .unwrap();
+
+        let mut vfs = Vfs::new(imfs);
+
+        let result = SnapshotRbxmx::from_vfs(
+            &InstanceContext::default(),
+            &mut vfs,
+            Path::new("/foo.rbxmx"),
+        );
+
+        match result {
+            Err(SnapshotError::EmptyModel { path }) => {
+                assert_eq!(path, Path::new("/foo.rbxmx"));
+            }
+            other => panic!("expected empty model error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn malformed_xml() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/foo.rbxmx", VfsSnapshot::file("<roblox"))
+            .unwrap();
+
+        let mut vfs = Vfs::new(imfs);
+
+        let result = SnapshotRbxmx::from_vfs(
+            &InstanceContext::default(),
+            &mut vfs,
+            Path::new("/foo.rbxmx"),
+        );
+
+        match result {
+            Err(SnapshotError::MalformedRbxmx { path, .. }) => {
+                assert_eq!(path, Path::new("/foo.rbxmx"));
+            }
+            other => panic!("expected malformed .rbxmx error, got {:?}", other),
+        }
+    }
 }
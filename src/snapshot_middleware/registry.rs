@@ -0,0 +1,681 @@
+//! Defines the registry that decides which middleware gets to turn each path
+//! into an instance.
+//!
+//! Every middleware is registered with the file names it handles, whether it
+//! handles directories, and a priority. For each path, matching middleware are
+//! tried from the highest priority to the lowest until one of them returns an
+//! instance. Middleware can also claim file names, like `init.lua`, which are
+//! never turned into instances on their own because they change the instance
+//! made from something else. Middleware built on file rules claim the init
+//! files of those rules, so a project rule for `*.luau` claims `init.luau`.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use memofs::Vfs;
+
+use crate::{glob::Glob, project::MiddlewareOptions, snapshot::InstanceContext};
+
+use super::{
+    assets::SnapshotAsset,
+    csv::SnapshotCsv,
+    dir::SnapshotDir,
+    error::SnapshotError,
+    file_rules::{self, FileRule, SnapshotFileRules},
+    json::SnapshotJson,
+    json_model::SnapshotJsonModel,
+    localization::SnapshotLocalization,
+    lua::SnapshotLua,
+    middleware::{SnapshotInstanceResult, SnapshotMiddleware},
+    project::SnapshotProject,
+    rbxlx::SnapshotRbxlx,
+    rbxm::SnapshotRbxm,
+    rbxmx::SnapshotRbxmx,
+    toml::SnapshotToml,
+    txt::SnapshotTxt,
+    user_plugins::SnapshotUserPlugins,
+    yaml::SnapshotYaml,
+};
+
+pub type SnapshotFn = fn(&InstanceContext, &Vfs, &Path) -> SnapshotInstanceResult;
+
+/// Returns the file rules that a middleware turns init files into instances
+/// with, which can depend on the projects a path is in.
+pub type InitRulesFn = fn(&InstanceContext) -> &[FileRule];
+
+/// A single middleware, along with the paths it should be tried on.
+#[derive(Clone)]
+pub struct MiddlewareEntry {
+    /// The name used to refer to this middleware from project files.
+    pub name: &'static str,
+
+    /// Middleware with a higher priority are tried first. Middleware with the
+    /// same priority are tried in the order they were registered.
+    pub priority: i32,
+
+    /// Globs matched against the names of files that this middleware should
+    /// be tried on.
+    pub files: Vec<Glob>,
+
+    /// Whether this middleware should be tried on directories.
+    pub directories: bool,
+
+    /// Middleware that are turned off aren't tried on anything and don't
+    /// claim any files.
+    pub enabled: bool,
+
+    /// Names of files that change the instance made from the directory they're
+    /// in, and so shouldn't be turned into instances themselves.
+    pub claims: Vec<&'static str>,
+
+    /// Claims the init files of the file rules that this returns, like
+    /// `init.lua` for `*.lua`.
+    pub init_rules: Option<InitRulesFn>,
+
+    /// Whether the instances this middleware makes from files take properties
+    /// from an adjacent `.meta.json` file.
+    pub reads_metadata: bool,
+
+    /// Whether this middleware can return nothing for files it's tried on,
+    /// leaving them to the next middleware, like user plugins do.
+    pub declines_files: bool,
+
+    pub snapshot: SnapshotFn,
+}
+
+impl MiddlewareEntry {
+    pub fn new(name: &'static str, priority: i32, snapshot: SnapshotFn) -> Self {
+        MiddlewareEntry {
+            name,
+            priority,
+            files: Vec::new(),
+            directories: false,
+            enabled: true,
+            claims: Vec::new(),
+            init_rules: None,
+            reads_metadata: false,
+            declines_files: false,
+            snapshot,
+        }
+    }
+
+    /// Tries this middleware on files with names matching any of the given
+    /// globs, which must be valid. Globs from projects are set through
+    /// [`MiddlewareRegistry::configure`] instead, which reports invalid ones.
+    pub fn files(mut self, globs: &[&str]) -> Self {
+        self.files.extend(
+            globs
+                .iter()
+                .map(|glob| Glob::new(glob).expect("invalid middleware glob")),
+        );
+        self
+    }
+
+    pub fn directories(mut self) -> Self {
+        self.directories = true;
+        self
+    }
+
+    pub fn claims(mut self, names: &[&'static str]) -> Self {
+        self.claims.extend_from_slice(names);
+        self
+    }
+
+    pub fn claims_init_files(mut self, rules: InitRulesFn) -> Self {
+        self.init_rules = Some(rules);
+        self
+    }
+
+    pub fn reads_metadata(mut self) -> Self {
+        self.reads_metadata = true;
+        self
+    }
+
+    pub fn declines_files(mut self) -> Self {
+        self.declines_files = true;
+        self
+    }
+
+    fn claimed_names(&self, context: &InstanceContext) -> Vec<String> {
+        let mut names: Vec<String> = self.claims.iter().map(|name| (*name).to_owned()).collect();
+
+        if let Some(init_rules) = self.init_rules {
+            names.extend(file_rules::init_file_names(init_rules(context)));
+        }
+
+        names
+    }
+
+    fn claims_file(&self, context: &InstanceContext, file_name: &str) -> bool {
+        if self.claims.contains(&file_name) {
+            return true;
+        }
+
+        match self.init_rules {
+            Some(init_rules) => file_rules::is_init_file(init_rules(context), file_name),
+            None => false,
+        }
+    }
+
+    fn handles(&self, file_name: Option<&str>, is_dir: bool) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        if is_dir {
+            return self.directories;
+        }
+
+        match file_name {
+            Some(file_name) => self.files.iter().any(|glob| glob.is_match(file_name)),
+            None => false,
+        }
+    }
+
+    /// Tells whether this middleware is tried on the given file. Middleware
+    /// built on file rules only take files that match one of those rules.
+    fn takes_file(&self, context: &InstanceContext, path: &Path, file_name: &str) -> bool {
+        if !self.handles(Some(file_name), false) {
+            return false;
+        }
+
+        match self.init_rules {
+            Some(init_rules) => file_rules::matches_any(init_rules(context), path),
+            None => true,
+        }
+    }
+}
+
+impl fmt::Debug for MiddlewareEntry {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}({})", self.name, self.priority)?;
+
+        if !self.enabled {
+            write!(formatter, " (disabled)")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The set of middleware used to snapshot paths, which is carried along in
+/// each instance's context so that projects can change it for the paths
+/// inside of them.
+#[derive(Debug, Clone)]
+pub struct MiddlewareRegistry {
+    entries: Vec<MiddlewareEntry>,
+
+    /// Globs for file names that no middleware should turn into an instance,
+    /// like `.meta.json` files.
+    claimed_files: Vec<Glob>,
+}
+
+lazy_static::lazy_static! {
+    static ref DEFAULT_REGISTRY: Arc<MiddlewareRegistry> = Arc::new(MiddlewareRegistry::builtin());
+}
+
+impl MiddlewareRegistry {
+    pub fn new() -> Self {
+        MiddlewareRegistry {
+            entries: Vec::new(),
+            claimed_files: Vec::new(),
+        }
+    }
+
+    /// Returns a shared copy of the registry with Rojo's built-in middleware.
+    pub fn shared_default() -> Arc<Self> {
+        Arc::clone(&DEFAULT_REGISTRY)
+    }
+
+    fn builtin() -> Self {
+        let mut registry = MiddlewareRegistry::new();
+
+        // User plugins come first so that they can claim any file before
+        // Rojo's built-in middleware does.
+        let builtin = vec![
+            MiddlewareEntry::new("userPlugins", 100, SnapshotUserPlugins::from_vfs)
+                .files(&["*"])
+                .declines_files(),
+            MiddlewareEntry::new("project", 90, SnapshotProject::from_vfs)
+                .files(&["*.project.json"])
+                .directories(),
+            MiddlewareEntry::new("jsonModel", 80, SnapshotJsonModel::from_vfs)
+                .files(&[
+                    "*.model.json",
+                    "*.model.yaml",
+                    "*.model.yml",
+                    "*.model.toml",
+                ])
+                .reads_metadata(),
+            MiddlewareEntry::new("rbxlx", 70, SnapshotRbxlx::from_vfs)
+                .files(&["*.rbxlx"])
+                .reads_metadata(),
+            MiddlewareEntry::new("rbxmx", 70, SnapshotRbxmx::from_vfs)
+                .files(&["*.rbxmx"])
+                .reads_metadata(),
+            MiddlewareEntry::new("rbxm", 70, SnapshotRbxm::from_vfs)
+                .files(&["*.rbxm"])
+                .reads_metadata(),
+            MiddlewareEntry::new("fileRules", 65, SnapshotFileRules::from_vfs)
+                .files(&["*"])
+                .directories()
+                .claims_init_files(file_rules::project_rules)
+                .reads_metadata(),
+            MiddlewareEntry::new("lua", 60, SnapshotLua::from_vfs)
+                .files(&["*.lua"])
+                .directories()
+                .claims_init_files(file_rules::lua_rules)
+                .reads_metadata(),
+            MiddlewareEntry::new("csv", 50, SnapshotCsv::from_vfs)
+                .files(&["*.csv"])
+                .reads_metadata(),
+            MiddlewareEntry::new("localization", 50, SnapshotLocalization::from_vfs)
+                .files(&["*.loc.json"])
+                .directories()
+                .claims(&["init.loc.json"])
+                .reads_metadata(),
+            MiddlewareEntry::new("assets", 50, SnapshotAsset::from_vfs)
+                .files(&["*.png", "*.jpg", "*.jpeg", "*.ogg", "*.mp3"])
+                .reads_metadata(),
+            MiddlewareEntry::new("txt", 50, SnapshotTxt::from_vfs)
+                .files(&["*.txt"])
+                .reads_metadata(),
+            MiddlewareEntry::new("json", 40, SnapshotJson::from_vfs)
+                .files(&["*.json"])
+                .reads_metadata(),
+            MiddlewareEntry::new("yaml", 40, SnapshotYaml::from_vfs)
+                .files(&["*.yaml", "*.yml"])
+                .reads_metadata(),
+            MiddlewareEntry::new("toml", 40, SnapshotToml::from_vfs)
+                .files(&["*.toml"])
+                .reads_metadata(),
+            MiddlewareEntry::new("dir", 0, SnapshotDir::from_vfs).directories(),
+        ];
+
+        for entry in builtin {
+            registry.add(entry);
+        }
+
+        // Meta files change the instance made from the file next to them or
+        // the directory they're in.
+        registry.claim_files("*.meta.json");
+
+        registry
+    }
+
+    /// Adds a middleware, keeping the entries ordered by priority.
+    pub fn add(&mut self, entry: MiddlewareEntry) {
+        let index = self
+            .entries
+            .iter()
+            .position(|existing| existing.priority < entry.priority)
+            .unwrap_or(self.entries.len());
+
+        self.entries.insert(index, entry);
+    }
+
+    /// Claims every file with a name matching the given glob, so that none of
+    /// them are turned into instances on their own. The glob must be valid.
+    pub fn claim_files(&mut self, glob: &str) {
+        self.claimed_files
+            .push(Glob::new(glob).expect("invalid middleware glob"));
+    }
+
+    /// Changes the priority of a middleware, the files it's tried on, or turns
+    /// it off, as configured in the `middleware` field of a project.
+    pub fn configure(
+        &mut self,
+        name: &str,
+        options: &MiddlewareOptions,
+        project_path: &Path,
+    ) -> Result<(), SnapshotError> {
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| entry.name == name)
+            .ok_or_else(|| SnapshotError::unknown_middleware(name, project_path))?;
+
+        let files = match &options.files {
+            Some(files) => Some(
+                files
+                    .iter()
+                    .map(|glob| {
+                        Glob::new(glob).map_err(|source| {
+                            SnapshotError::invalid_middleware_glob(name, glob, source, project_path)
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            None => None,
+        };
+
+        let mut entry = self.entries.remove(index);
+
+        if let Some(enabled) = options.enabled {
+            entry.enabled = enabled;
+        }
+
+        if let Some(priority) = options.priority {
+            entry.priority = priority;
+        }
+
+        if let Some(files) = files {
+            entry.files = files;
+        }
+
+        self.add(entry);
+        Ok(())
+    }
+
+    /// Returns the paths inside of the given directory that middleware have
+    /// claimed, which can change the instance made from that directory.
+    pub fn claimed_paths(&self, context: &InstanceContext, directory: &Path) -> Vec<PathBuf> {
+        self.enabled_entries()
+            .flat_map(|entry| entry.claimed_names(context))
+            .map(|name| directory.join(name))
+            .collect()
+    }
+
+    /// Generates a snapshot of instances from the given path using the first
+    /// middleware that returns one.
+    pub fn snapshot(
+        &self,
+        context: &InstanceContext,
+        vfs: &Vfs,
+        path: &Path,
+    ) -> SnapshotInstanceResult {
+        let is_dir = vfs.metadata(path)?.is_dir();
+        let file_name = path.file_name().and_then(|name| name.to_str());
+
+        if !is_dir && self.is_claimed(context, file_name) {
+            log::trace!("{} is claimed, skipping it", path.display());
+            return Ok(None);
+        }
+
+        for entry in &self.entries {
+            if !entry.handles(file_name, is_dir) {
+                continue;
+            }
+
+            log::trace!("trying middleware {} on {}", entry.name, path.display());
+
+            if let Some(snapshot) = (entry.snapshot)(context, vfs, path)? {
+                log::trace!("middleware {} success on {}", entry.name, path.display());
+                return Ok(Some(snapshot));
+            }
+        }
+
+        log::trace!("no middleware returned Ok(Some)");
+        Ok(None)
+    }
+
+    /// Tells whether the instance made from the given file takes properties
+    /// from an adjacent `.meta.json` file, going by the first middleware that
+    /// is sure to turn the file into an instance.
+    pub fn reads_adjacent_metadata(&self, context: &InstanceContext, path: &Path) -> bool {
+        let file_name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(file_name) => file_name,
+            None => return false,
+        };
+
+        if self.is_claimed(context, Some(file_name)) {
+            return false;
+        }
+
+        for entry in self.enabled_entries() {
+            if !entry.takes_file(context, path, file_name) {
+                continue;
+            }
+
+            if entry.reads_metadata {
+                return true;
+            }
+
+            if !entry.declines_files {
+                return false;
+            }
+        }
+
+        false
+    }
+
+    fn is_claimed(&self, context: &InstanceContext, file_name: Option<&str>) -> bool {
+        let file_name = match file_name {
+            Some(file_name) => file_name,
+            None => return false,
+        };
+
+        self.claimed_files
+            .iter()
+            .any(|glob| glob.is_match(file_name))
+            || self
+                .enabled_entries()
+                .any(|entry| entry.claims_file(context, file_name))
+    }
+
+    fn enabled_entries(&self) -> impl Iterator<Item = &MiddlewareEntry> {
+        self.entries.iter().filter(|entry| entry.enabled)
+    }
+}
+
+impl Default for MiddlewareRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl PartialEq for MiddlewareRegistry {
+    fn eq(&self, other: &Self) -> bool {
+        fn key(entry: &MiddlewareEntry) -> (&str, i32, bool, &[Glob]) {
+            (entry.name, entry.priority, entry.enabled, &entry.files)
+        }
+
+        self.claimed_files == other.claimed_files
+            && self
+                .entries
+                .iter()
+                .map(key)
+                .eq(other.entries.iter().map(key))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use maplit::hashmap;
+    use memofs::{InMemoryFs, VfsSnapshot};
+
+    fn entry_names(registry: &MiddlewareRegistry) -> Vec<&'static str> {
+        registry.enabled_entries().map(|entry| entry.name).collect()
+    }
+
+    #[test]
+    fn claimed_files() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "init.lua" => VfsSnapshot::file("return nil"),
+                "bar.meta.json" => VfsSnapshot::file("{}"),
+                "bar.json" => VfsSnapshot::file("{}"),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+        let context = InstanceContext::default();
+        let registry = MiddlewareRegistry::default();
+
+        for path in &["/foo/init.lua", "/foo/bar.meta.json"] {
+            assert!(registry
+                .snapshot(&context, &vfs, Path::new(path))
+                .unwrap()
+                .is_none());
+        }
+
+        let snapshot = registry
+            .snapshot(&context, &vfs, Path::new("/foo/bar.json"))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(snapshot.class_name, "ModuleScript");
+    }
+
+    #[test]
+    fn configure() {
+        let mut registry = MiddlewareRegistry::default();
+        let path = Path::new("/foo/default.project.json");
+
+        registry
+            .configure(
+                "json",
+                &MiddlewareOptions {
+                    enabled: None,
+                    priority: Some(75),
+                    ..Default::default()
+                },
+                path,
+            )
+            .unwrap();
+
+        registry
+            .configure(
+                "txt",
+                &MiddlewareOptions {
+                    enabled: Some(false),
+                    priority: None,
+                    ..Default::default()
+                },
+                path,
+            )
+            .unwrap();
+
+        assert_eq!(
+            entry_names(&registry),
+            vec![
+                "userPlugins",
+                "project",
+                "jsonModel",
+                "json",
+                "rbxlx",
+                "rbxmx",
+                "rbxm",
+                "fileRules",
+                "lua",
+                "csv",
+                "localization",
+                "assets",
+                "yaml",
+                "toml",
+                "dir",
+            ]
+        );
+
+        match registry.configure("python", &MiddlewareOptions::default(), path) {
+            Err(SnapshotError::UnknownMiddleware { name, .. }) => assert_eq!(name, "python"),
+            other => panic!("expected unknown middleware error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn configure_files() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "settings.json" => VfsSnapshot::file("{}"),
+                "package.json" => VfsSnapshot::file("{}"),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+        let context = InstanceContext::default();
+        let mut registry = MiddlewareRegistry::default();
+        let path = Path::new("/foo/default.project.json");
+
+        registry
+            .configure(
+                "json",
+                &MiddlewareOptions {
+                    files: Some(vec!["settings.json".to_owned()]),
+                    ..Default::default()
+                },
+                path,
+            )
+            .unwrap();
+
+        assert!(registry != MiddlewareRegistry::default());
+
+        let snapshot = registry
+            .snapshot(&context, &vfs, Path::new("/foo/settings.json"))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(snapshot.class_name, "ModuleScript");
+
+        assert!(registry
+            .snapshot(&context, &vfs, Path::new("/foo/package.json"))
+            .unwrap()
+            .is_none());
+
+        let result = registry.configure(
+            "json",
+            &MiddlewareOptions {
+                files: Some(vec!["*.{json".to_owned()]),
+                ..Default::default()
+            },
+            path,
+        );
+
+        match result {
+            Err(SnapshotError::InvalidMiddlewareGlob { name, glob, .. }) => {
+                assert_eq!(name, "json");
+                assert_eq!(glob, "*.{json");
+            }
+            other => panic!("expected invalid middleware glob error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reads_adjacent_metadata() {
+        let context = InstanceContext::default();
+        let mut registry = MiddlewareRegistry::default();
+        let reads = |registry: &MiddlewareRegistry, path: &str| {
+            registry.reads_adjacent_metadata(&context, Path::new(path))
+        };
+
+        assert!(reads(&registry, "/foo/bar.lua"));
+        assert!(reads(&registry, "/foo/bar.model.json"));
+        assert!(!reads(&registry, "/foo/bar.meta.json"));
+        assert!(!reads(&registry, "/foo/default.project.json"));
+        assert!(!reads(&registry, "/foo/bar.vert"));
+
+        registry
+            .configure(
+                "lua",
+                &MiddlewareOptions {
+                    enabled: Some(false),
+                    ..Default::default()
+                },
+                Path::new("/foo/default.project.json"),
+            )
+            .unwrap();
+
+        assert!(!reads(&registry, "/foo/bar.lua"));
+
+        let mut context = context.clone();
+        context.add_file_rules(vec![FileRule::from_project(
+            "*.vert",
+            "StringValue",
+            Path::new("/foo/default.project.json"),
+        )
+        .unwrap()]);
+
+        assert!(registry.reads_adjacent_metadata(&context, Path::new("/foo/bar.vert")));
+    }
+}
@@ -0,0 +1,89 @@
+//! Turns the `tags` of meta files, JSON models, and project nodes into the
+//! format that Roblox stores CollectionService tags in, which lives in the
+//! `Tags` property.
+
+use std::collections::HashMap;
+
+use rbx_dom_weak::RbxValue;
+
+/// The property that Roblox serializes CollectionService tags into.
+pub const TAGS_PROPERTY: &str = "Tags";
+
+/// Encodes the given tags into a value for the `Tags` property, which holds
+/// every tag separated by null bytes.
+///
+/// Tags that are listed more than once are only kept the first time.
+pub fn encode_tags(tags: &[String]) -> RbxValue {
+    let mut seen = Vec::with_capacity(tags.len());
+
+    for tag in tags {
+        if !seen.contains(&tag.as_str()) {
+            seen.push(tag.as_str());
+        }
+    }
+
+    RbxValue::BinaryString {
+        value: seen.join("\0").into_bytes(),
+    }
+}
+
+/// Adds the given tags to the ones already in the `Tags` property of
+/// `properties`, if it has any.
+pub fn add_tags(properties: &mut HashMap<String, RbxValue>, tags: &[String]) {
+    if tags.is_empty() {
+        return;
+    }
+
+    let mut all_tags: Vec<String> = match properties.get(TAGS_PROPERTY) {
+        Some(RbxValue::BinaryString { value }) => String::from_utf8_lossy(value)
+            .split('\0')
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    all_tags.extend_from_slice(tags);
+    properties.insert(TAGS_PROPERTY.to_owned(), encode_tags(&all_tags));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn null_separated() {
+        let tags = vec![
+            "Enemy".to_owned(),
+            "Damageable".to_owned(),
+            "Enemy".to_owned(),
+        ];
+
+        assert_eq!(
+            encode_tags(&tags),
+            RbxValue::BinaryString {
+                value: b"Enemy\0Damageable".to_vec(),
+            }
+        );
+    }
+
+    #[test]
+    fn added_to_existing() {
+        let mut properties = HashMap::new();
+        properties.insert(
+            TAGS_PROPERTY.to_owned(),
+            RbxValue::BinaryString {
+                value: b"Enemy\0Boss".to_vec(),
+            },
+        );
+
+        add_tags(&mut properties, &["Boss".to_owned(), "Flying".to_owned()]);
+
+        assert_eq!(
+            properties[TAGS_PROPERTY],
+            RbxValue::BinaryString {
+                value: b"Enemy\0Boss\0Flying".to_vec(),
+            }
+        );
+    }
+}
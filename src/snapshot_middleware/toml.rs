@@ -0,0 +1,111 @@
+use std::{path::Path, str};
+
+use memofs::Vfs;
+
+use crate::{
+    lua_ast::{Expression, Statement},
+    snapshot::InstanceContext,
+};
+
+use super::{
+    error::SnapshotError,
+    json::data_module_snapshot,
+    middleware::{SnapshotInstanceResult, SnapshotMiddleware},
+    util::match_file_name,
+};
+
+/// Turns TOML files into ModuleScripts that return the same data, the same way
+/// that JSON files are.
+pub struct SnapshotToml;
+
+impl SnapshotMiddleware for SnapshotToml {
+    fn from_vfs(context: &InstanceContext, vfs: &Vfs, path: &Path) -> SnapshotInstanceResult {
+        let meta = vfs.metadata(path)?;
+
+        if meta.is_dir() {
+            return Ok(None);
+        }
+
+        let instance_name = match match_file_name(path, ".toml") {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+
+        let contents = vfs.read(path)?;
+        let contents_str = str::from_utf8(&contents)
+            .map_err(|err| SnapshotError::file_contents_bad_unicode(err, path))?;
+
+        let value: toml::Value =
+            toml::from_str(contents_str).map_err(|err| SnapshotError::malformed_toml(err, path))?;
+
+        let snapshot = data_module_snapshot(context, vfs, path, instance_name, toml_to_lua(value))?;
+
+        Ok(Some(snapshot))
+    }
+}
+
+fn toml_to_lua(value: toml::Value) -> Statement {
+    Statement::Return(toml_to_lua_value(value))
+}
+
+fn toml_to_lua_value(value: toml::Value) -> Expression {
+    use toml::Value;
+
+    match value {
+        Value::Boolean(value) => Expression::Bool(value),
+        Value::Integer(value) => Expression::Number(value as f64),
+        Value::Float(value) => Expression::Number(value),
+        Value::String(value) => Expression::String(value),
+
+        // Lua has no type for dates, so they're kept the way they were written.
+        Value::Datetime(value) => Expression::String(value.to_string()),
+        Value::Array(values) => {
+            Expression::Array(values.into_iter().map(toml_to_lua_value).collect())
+        }
+        Value::Table(values) => Expression::table(
+            values
+                .into_iter()
+                .map(|(key, value)| (key.into(), toml_to_lua_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use memofs::{InMemoryFs, VfsSnapshot};
+
+    #[test]
+    fn instance_from_vfs() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo.toml",
+            VfsSnapshot::file(
+                r#"
+                array = [1, 2, 3]
+                "true" = true
+                "false" = false
+                int = 1234
+                float = 1234.5452
+                date = 1979-05-27
+                1invalidident = "nice"
+
+                [object]
+                hello = "world"
+                "#,
+            ),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let instance_snapshot =
+            SnapshotToml::from_vfs(&InstanceContext::default(), &vfs, Path::new("/foo.toml"))
+                .unwrap()
+                .unwrap();
+
+        insta::assert_yaml_snapshot!(instance_snapshot);
+    }
+}
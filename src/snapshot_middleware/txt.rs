@@ -1,16 +1,12 @@
-use std::{path::Path, str};
+use std::path::Path;
 
-use maplit::hashmap;
-use memofs::{IoResultExt, Vfs};
-use rbx_dom_weak::RbxValue;
+use memofs::Vfs;
 
-use crate::snapshot::{InstanceContext, InstanceMetadata, InstanceSnapshot};
+use crate::snapshot::InstanceContext;
 
 use super::{
-    error::SnapshotError,
-    meta_file::AdjacentMetadata,
+    file_rules::{snapshot_with_rules, TXT_RULES},
     middleware::{SnapshotInstanceResult, SnapshotMiddleware},
-    util::match_file_name,
 };
 
 pub struct SnapshotTxt;
@@ -23,41 +19,7 @@ impl SnapshotMiddleware for SnapshotTxt {
             return Ok(None);
         }
 
-        let instance_name = match match_file_name(path, ".txt") {
-            Some(name) => name,
-            None => return Ok(None),
-        };
-
-        let contents = vfs.read(path)?;
-        let contents_str = str::from_utf8(&contents)
-            .map_err(|err| SnapshotError::file_contents_bad_unicode(err, path))?
-            .to_string();
-
-        let properties = hashmap! {
-            "Value".to_owned() => RbxValue::String {
-                value: contents_str,
-            },
-        };
-
-        let meta_path = path.with_file_name(format!("{}.meta.json", instance_name));
-
-        let mut snapshot = InstanceSnapshot::new()
-            .name(instance_name)
-            .class_name("StringValue")
-            .properties(properties)
-            .metadata(
-                InstanceMetadata::new()
-                    .instigating_source(path)
-                    .relevant_paths(vec![path.to_path_buf(), meta_path.clone()])
-                    .context(context),
-            );
-
-        if let Some(meta_contents) = vfs.read(&meta_path).with_not_found()? {
-            let mut metadata = AdjacentMetadata::from_slice(&meta_contents, &meta_path)?;
-            metadata.apply_all(&mut snapshot);
-        }
-
-        Ok(Some(snapshot))
+        snapshot_with_rules(context, vfs, path, TXT_RULES.iter())
     }
 }
 
@@ -67,6 +29,8 @@ mod test {
 
     use memofs::{InMemoryFs, VfsSnapshot};
 
+    use crate::snapshot_middleware::SnapshotError;
+
     #[test]
     fn instance_from_vfs() {
         let mut imfs = InMemoryFs::new();
@@ -82,4 +46,23 @@ mod test {
 
         insta::assert_yaml_snapshot!(instance_snapshot);
     }
+
+    #[test]
+    fn meta_with_strict() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/foo.txt", VfsSnapshot::file("Hello there!"))
+            .unwrap();
+        imfs.load_snapshot("/foo.meta.json", VfsSnapshot::file(r#"{ "strict": true }"#))
+            .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let result =
+            SnapshotTxt::from_vfs(&InstanceContext::default(), &vfs, Path::new("/foo.txt"));
+
+        match result {
+            Err(SnapshotError::UnusedMetaField { field, .. }) => assert_eq!(field, "strict"),
+            other => panic!("expected unused meta field error, got {:?}", other),
+        }
+    }
 }
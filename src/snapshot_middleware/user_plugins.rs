@@ -1,56 +1,392 @@
-use crate::{
-    snapshot::InstanceContext,
-    vfs::{Vfs, VfsEntry, VfsFetcher},
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::Mutex,
 };
 
-use super::middleware::{SnapshotInstanceResult, SnapshotMiddleware};
+use memofs::Vfs;
+use rbx_dom_weak::UnresolvedRbxValue;
+use rlua::{Function, Lua, RegistryKey, Table, Value};
+
+use crate::snapshot::{InstanceContext, InstanceMetadata};
+
+use super::{
+    error::SnapshotError,
+    json_model::{JsonModelCore, JsonModelInstance},
+    middleware::{SnapshotInstanceResult, SnapshotMiddleware},
+};
 
 /// Handles snapshotting of any file that a user plugin wants to handle.
 ///
-/// User plugins are specified in the project file, but there are never user
-/// plugins specified unless a Cargo feature is enabled, `user-plugins`.
-/// Additionally, extra data needs to be set up inside the snapshot context
-/// which is not currently wired up.
+/// User plugins are Lua files listed in the `plugins` field of a project. Each
+/// plugin returns a function that is called with the path and contents of
+/// every file inside the project. That function can either return a table
+/// describing an instance, in the same shape as a `.model.json` file, or `nil`
+/// to defer to the next plugin and eventually Rojo's built-in middleware.
 pub struct SnapshotUserPlugins;
 
 impl SnapshotMiddleware for SnapshotUserPlugins {
-    fn from_vfs<F: VfsFetcher>(
-        _context: &InstanceContext,
-        _vfs: &Vfs<F>,
-        _entry: &VfsEntry,
-    ) -> SnapshotInstanceResult {
-        // TODO: Invoke plugin here and get result out.
-
-        // The current plan for plugins here is to make them work
-        // like Redux/Rodux middleware. A plugin will be a function
-        // that accepts the next middleware in the chain as a
-        // function and the snapshot subject (the VFS entry).
-        //
-        // Plugins can (but don't have to) invoke the next snapshot
-        // function and may or may not mutate the result. The hope
-        // is that this model enables the most flexibility possible
-        // for plugins to modify existing Rojo output, as well as
-        // generate new outputs.
-        //
-        // Open questions:
-        // * How will middleware be ordered? Does putting user
-        //   middleware always at the beginning or always at the end
-        //   of the chain reduce the scope of what that middleware
-        //   can do?
-        //
-        // * Will plugins hurt Rojo's ability to parallelize
-        //   snapshotting in the future?
-        //
-        // * Do the mutable handles to the Vfs and the snapshot
-        //   context prevent plugins from invoking other plugins
-        //   indirectly?
-        //
-        // * Will there be problems using a single Lua state because
-        //   of re-entrancy?
-        //
-        // * Can the Lua <-> Rojo bindings used for middleware be
-        //   reused for or from another project like Remodel?
+    fn from_vfs(context: &InstanceContext, vfs: &Vfs, path: &Path) -> SnapshotInstanceResult {
+        if context.user_plugins.is_empty() {
+            return Ok(None);
+        }
+
+        let meta = vfs.metadata(path)?;
+
+        if meta.is_dir() {
+            return Ok(None);
+        }
+
+        let contents = vfs.read(path)?;
+
+        for plugin in context.user_plugins.iter() {
+            let (name, core) = match plugin.call(path, &contents)? {
+                Some(result) => result,
+                None => continue,
+            };
+
+            let name = match name {
+                Some(name) => name,
+                None => path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .ok_or_else(|| SnapshotError::file_name_bad_unicode(path))?
+                    .to_owned(),
+            };
+
+            let mut snapshot = core.into_snapshot(name, path)?;
+
+            snapshot.metadata = InstanceMetadata::new()
+                .instigating_source(path)
+                .relevant_paths(vec![path.to_path_buf()])
+                .context(context);
+
+            return Ok(Some(snapshot));
+        }
 
         Ok(None)
     }
 }
+
+/// A snapshot plugin written in Lua, along with the Lua state it runs in.
+///
+/// Each plugin gets its own Lua state so that plugins can't interfere with
+/// each other through globals.
+pub struct UserPlugin {
+    path: PathBuf,
+    lua: Mutex<Lua>,
+    function: RegistryKey,
+}
+
+impl UserPlugin {
+    /// Loads a plugin from the given Lua source. The source should return the
+    /// function that will be invoked for every file.
+    pub fn load(path: &Path, source: &[u8]) -> Result<Self, SnapshotError> {
+        let lua = Lua::new();
+
+        let function = lua
+            .context(|context| {
+                let function: Function = context
+                    .load(source)
+                    .set_name(&path.to_string_lossy().as_bytes())?
+                    .call(())?;
+
+                context.create_registry_value(function)
+            })
+            .map_err(|source| SnapshotError::user_plugin_error(source, path))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            lua: Mutex::new(lua),
+            function,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Invokes the plugin on a file. Returns the name the plugin picked for the
+    /// instance, if any, along with a description of the instance.
+    fn call(
+        &self,
+        path: &Path,
+        contents: &[u8],
+    ) -> Result<Option<(Option<String>, JsonModelCore)>, SnapshotError> {
+        let lua = self.lua.lock().unwrap();
+
+        lua.context(|context| {
+            let result = context
+                .registry_value::<Function>(&self.function)
+                .and_then(|function| {
+                    let path = path.to_string_lossy();
+                    let contents = context.create_string(contents)?;
+
+                    function.call::<_, Value>((path.as_ref(), contents))
+                })
+                .map_err(|source| SnapshotError::user_plugin_error(source, &self.path))?;
+
+            let table = match result {
+                Value::Nil => return Ok(None),
+                Value::Table(table) => table,
+                _ => {
+                    return Err(self.bad_output(path, "plugins must return a table or nil"));
+                }
+            };
+
+            let name = match table.get::<_, Value>("Name") {
+                Ok(Value::Nil) => None,
+                Ok(Value::String(name)) => Some(self.to_utf8(path, name.as_bytes())?),
+                _ => return Err(self.bad_output(path, "Name must be a string")),
+            };
+
+            let core = self.instance_from_table(path, table)?;
+
+            Ok(Some((name, core)))
+        })
+    }
+
+    fn instance_from_table(
+        &self,
+        path: &Path,
+        table: Table,
+    ) -> Result<JsonModelCore, SnapshotError> {
+        let class_name = match table.get::<_, Value>("ClassName") {
+            Ok(Value::String(class_name)) => self.to_utf8(path, class_name.as_bytes())?,
+            _ => return Err(self.bad_output(path, "ClassName must be a string")),
+        };
+
+        let mut properties = HashMap::new();
+
+        match table.get::<_, Value>("Properties") {
+            Ok(Value::Nil) => {}
+            Ok(Value::Table(properties_table)) => {
+                for pair in properties_table.pairs::<Value, Value>() {
+                    let (key, value) = pair
+                        .map_err(|source| SnapshotError::user_plugin_error(source, &self.path))?;
+
+                    let key = match key {
+                        Value::String(key) => self.to_utf8(path, key.as_bytes())?,
+                        _ => return Err(self.bad_output(path, "property names must be strings")),
+                    };
+
+                    let value: UnresolvedRbxValue = serde_json::from_value(
+                        self.value_to_json(path, value)?,
+                    )
+                    .map_err(|err| {
+                        self.bad_output(path, &format!("invalid value for {}: {}", key, err))
+                    })?;
+
+                    properties.insert(key, value);
+                }
+            }
+            _ => return Err(self.bad_output(path, "Properties must be a table")),
+        }
+
+        let mut children = Vec::new();
+
+        match table.get::<_, Value>("Children") {
+            Ok(Value::Nil) => {}
+            Ok(Value::Table(children_table)) => {
+                for child in children_table.sequence_values::<Value>() {
+                    let child = match child {
+                        Ok(Value::Table(child)) => child,
+                        _ => return Err(self.bad_output(path, "children must be tables")),
+                    };
+
+                    let name = match child.get::<_, Value>("Name") {
+                        Ok(Value::String(name)) => self.to_utf8(path, name.as_bytes())?,
+                        _ => return Err(self.bad_output(path, "children must have a Name")),
+                    };
+
+                    children.push(JsonModelInstance {
+                        name,
+                        core: self.instance_from_table(path, child)?,
+                    });
+                }
+            }
+            _ => return Err(self.bad_output(path, "Children must be a table")),
+        }
+
+        Ok(JsonModelCore {
+            class_name,
+            children,
+            properties,
+            attributes: HashMap::new(),
+            tags: Vec::new(),
+        })
+    }
+
+    /// Converts a Lua value into JSON so that property values returned by
+    /// plugins are understood the same way as values in `.model.json` files.
+    fn value_to_json(&self, path: &Path, value: Value) -> Result<serde_json::Value, SnapshotError> {
+        Ok(match value {
+            Value::Nil => serde_json::Value::Null,
+            Value::Boolean(value) => value.into(),
+            Value::Integer(value) => value.into(),
+            Value::Number(value) => value.into(),
+            Value::String(value) => self.to_utf8(path, value.as_bytes())?.into(),
+            Value::Table(table) => {
+                let len = table.raw_len();
+
+                // Tables with only sequential keys, like {1, 2, 3}, are arrays.
+                // Empty tables are also treated as arrays, which matches how
+                // values like empty NumberSequences are written.
+                let is_array = table
+                    .clone()
+                    .pairs::<Value, Value>()
+                    .all(|pair| match pair {
+                        Ok((Value::Integer(key), _)) => key >= 1 && key <= len,
+                        _ => false,
+                    });
+
+                if is_array {
+                    let mut array = Vec::new();
+
+                    for value in table.sequence_values::<Value>() {
+                        let value = value.map_err(|source| {
+                            SnapshotError::user_plugin_error(source, &self.path)
+                        })?;
+                        array.push(self.value_to_json(path, value)?);
+                    }
+
+                    serde_json::Value::Array(array)
+                } else {
+                    let mut object = serde_json::Map::new();
+
+                    for pair in table.pairs::<Value, Value>() {
+                        let (key, value) = pair.map_err(|source| {
+                            SnapshotError::user_plugin_error(source, &self.path)
+                        })?;
+
+                        let key = match key {
+                            Value::String(key) => self.to_utf8(path, key.as_bytes())?,
+                            _ => return Err(self.bad_output(path, "table keys must be strings")),
+                        };
+
+                        object.insert(key, self.value_to_json(path, value)?);
+                    }
+
+                    serde_json::Value::Object(object)
+                }
+            }
+            _ => return Err(self.bad_output(path, "values must be plain data")),
+        })
+    }
+
+    fn to_utf8(&self, path: &Path, bytes: &[u8]) -> Result<String, SnapshotError> {
+        std::str::from_utf8(bytes)
+            .map(|value| value.to_owned())
+            .map_err(|_| self.bad_output(path, "strings must be valid UTF-8"))
+    }
+
+    fn bad_output(&self, path: &Path, message: &str) -> SnapshotError {
+        SnapshotError::user_plugin_bad_output(message, &self.path, path)
+    }
+}
+
+impl fmt::Debug for UserPlugin {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "UserPlugin({})", self.path.display())
+    }
+}
+
+impl PartialEq for UserPlugin {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use maplit::hashmap;
+    use memofs::{InMemoryFs, VfsSnapshot};
+
+    fn context_with_plugin(source: &str) -> InstanceContext {
+        let plugin = UserPlugin::load(Path::new("/plugin.lua"), source.as_bytes()).unwrap();
+
+        let mut context = InstanceContext::default();
+        context.add_user_plugins(vec![Arc::new(plugin)]);
+        context
+    }
+
+    #[test]
+    fn markdown_plugin() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "README.md" => VfsSnapshot::file("# Hello"),
+            }),
+        )
+        .unwrap();
+
+        let mut vfs = Vfs::new(imfs);
+
+        let context = context_with_plugin(
+            r#"
+                return function(path, contents)
+                    if not path:match("%.md$") then
+                        return nil
+                    end
+
+                    return {
+                        ClassName = "StringValue",
+                        Properties = {
+                            Value = contents,
+                        },
+                        Children = {
+                            { Name = "Length", ClassName = "IntValue", Properties = { Value = #contents } },
+                        },
+                    }
+                end
+            "#,
+        );
+
+        let instance_snapshot =
+            SnapshotUserPlugins::from_vfs(&context, &mut vfs, Path::new("/foo/README.md"))
+                .unwrap()
+                .unwrap();
+
+        insta::assert_yaml_snapshot!(instance_snapshot);
+    }
+
+    #[test]
+    fn plugin_defers() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/foo.txt", VfsSnapshot::file("Hello"))
+            .unwrap();
+
+        let mut vfs = Vfs::new(imfs);
+
+        let context = context_with_plugin("return function() return nil end");
+
+        let instance_snapshot =
+            SnapshotUserPlugins::from_vfs(&context, &mut vfs, Path::new("/foo.txt")).unwrap();
+
+        assert!(instance_snapshot.is_none());
+    }
+
+    #[test]
+    fn plugin_bad_output() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/foo.txt", VfsSnapshot::file("Hello"))
+            .unwrap();
+
+        let mut vfs = Vfs::new(imfs);
+
+        let context = context_with_plugin("return function() return { Name = 5 } end");
+
+        let result = SnapshotUserPlugins::from_vfs(&context, &mut vfs, Path::new("/foo.txt"));
+
+        match result {
+            Err(SnapshotError::UserPluginBadOutput { .. }) => {}
+            other => panic!("expected bad output error, got {:?}", other),
+        }
+    }
+}
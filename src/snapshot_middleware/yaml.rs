@@ -0,0 +1,129 @@
+use std::path::Path;
+
+use memofs::Vfs;
+
+use crate::{
+    lua_ast::{Expression, Statement},
+    snapshot::InstanceContext,
+};
+
+use super::{
+    error::SnapshotError,
+    json::data_module_snapshot,
+    middleware::{SnapshotInstanceResult, SnapshotMiddleware},
+    util::match_file_name,
+};
+
+/// Turns YAML files into ModuleScripts that return the same data, the same way
+/// that JSON files are.
+pub struct SnapshotYaml;
+
+impl SnapshotMiddleware for SnapshotYaml {
+    fn from_vfs(context: &InstanceContext, vfs: &Vfs, path: &Path) -> SnapshotInstanceResult {
+        let meta = vfs.metadata(path)?;
+
+        if meta.is_dir() {
+            return Ok(None);
+        }
+
+        let instance_name = match match_file_name(path, ".yaml") {
+            Some(name) => name,
+            None => match match_file_name(path, ".yml") {
+                Some(name) => name,
+                None => return Ok(None),
+            },
+        };
+
+        let contents = vfs.read(path)?;
+
+        let value: serde_yaml::Value = serde_yaml::from_slice(&contents)
+            .map_err(|err| SnapshotError::malformed_yaml(err, path))?;
+
+        let snapshot = data_module_snapshot(context, vfs, path, instance_name, yaml_to_lua(value))?;
+
+        Ok(Some(snapshot))
+    }
+}
+
+fn yaml_to_lua(value: serde_yaml::Value) -> Statement {
+    Statement::Return(yaml_to_lua_value(value))
+}
+
+fn yaml_to_lua_value(value: serde_yaml::Value) -> Expression {
+    use serde_yaml::Value;
+
+    match value {
+        Value::Null => Expression::Nil,
+        Value::Bool(value) => Expression::Bool(value),
+        Value::Number(value) => Expression::Number(value.as_f64().unwrap()),
+        Value::String(value) => Expression::String(value),
+        Value::Sequence(values) => {
+            Expression::Array(values.into_iter().map(yaml_to_lua_value).collect())
+        }
+        Value::Mapping(values) => Expression::table(
+            values
+                .into_iter()
+                // Lua tables can't have nil as a key, so entries like that are
+                // left out.
+                .filter(|(key, _)| !key.is_null())
+                .map(|(key, value)| (yaml_to_lua_value(key), yaml_to_lua_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use memofs::{InMemoryFs, VfsSnapshot};
+
+    #[test]
+    fn instance_from_vfs() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo.yaml",
+            VfsSnapshot::file(
+                r#"
+                array: [1, 2, 3]
+                object:
+                  hello: world
+                "true": true
+                "false": false
+                "null": null
+                int: 1234
+                float: 1234.5452
+                1invalidident: nice
+                5: number key
+                "#,
+            ),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let instance_snapshot =
+            SnapshotYaml::from_vfs(&InstanceContext::default(), &vfs, Path::new("/foo.yaml"))
+                .unwrap()
+                .unwrap();
+
+        insta::assert_yaml_snapshot!(instance_snapshot);
+    }
+
+    #[test]
+    fn yml_extension() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/foo.yml", VfsSnapshot::file("- 1\n- 2\n"))
+            .unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let instance_snapshot =
+            SnapshotYaml::from_vfs(&InstanceContext::default(), &vfs, Path::new("/foo.yml"))
+                .unwrap()
+                .unwrap();
+
+        assert_eq!(instance_snapshot.name, "foo");
+        assert_eq!(instance_snapshot.class_name, "ModuleScript");
+    }
+}
@@ -0,0 +1,1115 @@
+//! Defines how Rojo turns instances back into files on disk.
+//!
+//! Syncback is the inverse of the snapshot middleware defined in
+//! `src/snapshot_middleware`. Given an instance, it produces a set of files
+//! that the middleware would turn back into an equivalent instance. Formats are
+//! picked so that the output is as pleasant to work with as possible: scripts
+//! become Lua files, folders become directories, and anything that can't be
+//! represented any other way falls back to an `.rbxmx` model.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io,
+    path::{Path, PathBuf},
+};
+
+use memofs::{Vfs, VfsSnapshot};
+use rbx_dom_weak::{
+    RbxId, RbxInstance, RbxInstanceProperties, RbxTree, RbxValue, UnresolvedRbxValue,
+};
+use rbx_reflection::get_class_descriptor;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    project::{Project, ProjectNode},
+    snapshot::InstanceSnapshot,
+    snapshot_middleware::{
+        convert_localization_csv, AdjacentMetadata, DirectoryMetadata, JsonModel, JsonModelCore,
+        JsonModelInstance,
+    },
+};
+
+/// The folder, relative to the generated project file, that instances are
+/// written into.
+static SOURCE_FOLDER: &str = "src";
+
+#[derive(Debug, Error)]
+pub enum SyncbackError {
+    #[error("the top-level instance {name} can't be written as a file because of its name")]
+    InvalidServiceName { name: String },
+
+    #[error("there is more than one top-level instance named {name}")]
+    DuplicateServiceName { name: String },
+
+    #[error(transparent)]
+    XmlEncode {
+        #[from]
+        source: rbx_xml::EncodeError,
+    },
+
+    #[error(transparent)]
+    Json {
+        #[from]
+        source: serde_json::Error,
+    },
+}
+
+/// Converts a place, given as a tree whose root is a DataModel, into a Rojo
+/// project.
+///
+/// The returned snapshot is a directory containing a `default.project.json`
+/// file and a folder containing the files for all instances in the place.
+pub fn syncback_place(tree: &RbxTree, project_name: &str) -> Result<VfsSnapshot, SyncbackError> {
+    let root_instance = tree.get_instance(tree.get_root_id()).unwrap();
+
+    let mut source_files = BTreeMap::new();
+    let mut root_node = ProjectNode {
+        class_name: Some("DataModel".to_owned()),
+        ..Default::default()
+    };
+
+    for &service_id in root_instance.get_children_ids() {
+        let service = tree.get_instance(service_id).unwrap();
+
+        if root_node.children.contains_key(&service.name) {
+            return Err(SyncbackError::DuplicateServiceName {
+                name: service.name.clone(),
+            });
+        }
+
+        let node = syncback_service(tree, service, &mut source_files)?;
+        root_node.children.insert(service.name.clone(), node);
+    }
+
+    let project = Project {
+        name: project_name.to_owned(),
+        tree: root_node,
+        serve_port: None,
+        serve_place_ids: None,
+        glob_ignore_paths: Vec::new(),
+        variables: BTreeMap::new(),
+        profiles: BTreeMap::new(),
+        file_rules: BTreeMap::new(),
+        middleware: BTreeMap::new(),
+        plugins: Vec::new(),
+        file_location: PathBuf::new(),
+    };
+
+    Ok(VfsSnapshot::dir(vec![
+        ("default.project.json", json_file(&project)?),
+        (SOURCE_FOLDER, VfsSnapshot::dir(source_files)),
+    ]))
+}
+
+/// Turns a direct child of the DataModel into a project node, writing any
+/// files needed to describe its descendants into `output`.
+fn syncback_service(
+    tree: &RbxTree,
+    service: &RbxInstance,
+    output: &mut BTreeMap<String, VfsSnapshot>,
+) -> Result<ProjectNode, SyncbackError> {
+    if !is_valid_file_name(&service.name) {
+        return Err(SyncbackError::InvalidServiceName {
+            name: service.name.clone(),
+        });
+    }
+
+    // Ref properties on services, like Workspace.CurrentCamera, can't be
+    // described by a project node and are left out. Studio fills in the ones
+    // that matter when the place is opened.
+    let mut node = ProjectNode {
+        class_name: Some(service.class_name.clone()),
+        properties: unresolved(syncable_properties(service, &[])),
+        ..Default::default()
+    };
+
+    if !service.get_children_ids().is_empty() {
+        match children_dir(tree, service, None)? {
+            Some(dir) => {
+                output.insert(service.name.clone(), dir);
+                node.path = Some(PathBuf::from(SOURCE_FOLDER).join(&service.name));
+            }
+            None => {
+                let file_name = format!("{}.rbxmx", service.name);
+                output.insert(file_name.clone(), rbxmx_file(tree, service)?);
+
+                return Ok(ProjectNode {
+                    path: Some(PathBuf::from(SOURCE_FOLDER).join(file_name)),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    Ok(node)
+}
+
+/// Creates the files for an instance described by a snapshot, like the ones
+/// sent by the Studio plugin when instances are added. The returned map is
+/// keyed by file name and should be written into the directory that
+/// represents the instance's parent.
+///
+/// Ref properties are only kept if they point to other instances inside of the
+/// snapshot.
+pub fn syncback_snapshot(
+    snapshot: &InstanceSnapshot,
+) -> Result<BTreeMap<String, VfsSnapshot>, SyncbackError> {
+    let mut tree = RbxTree::new(RbxInstanceProperties {
+        name: snapshot.name.to_string(),
+        class_name: snapshot.class_name.to_string(),
+        properties: HashMap::new(),
+    });
+
+    let root_id = tree.get_root_id();
+
+    let mut ids = Vec::new();
+    let mut snapshot_ids = HashMap::new();
+    insert_snapshot_children(&mut tree, root_id, snapshot, &mut ids, &mut snapshot_ids);
+
+    // Properties are assigned after every instance exists so that Ref
+    // properties can be pointed at their new IDs.
+    for (id, snapshot) in ids {
+        let instance = tree.get_instance_mut(id).unwrap();
+
+        for (key, value) in &snapshot.properties {
+            let value = match value {
+                RbxValue::Ref {
+                    value: Some(target),
+                } => RbxValue::Ref {
+                    value: snapshot_ids.get(target).copied(),
+                },
+                _ => value.clone(),
+            };
+
+            instance.properties.insert(key.clone(), value);
+        }
+    }
+
+    let mut output = BTreeMap::new();
+    syncback_instance(&tree, tree.get_instance(root_id).unwrap(), &mut output)?;
+
+    Ok(output)
+}
+
+fn insert_snapshot_children<'a>(
+    tree: &mut RbxTree,
+    id: RbxId,
+    snapshot: &'a InstanceSnapshot,
+    ids: &mut Vec<(RbxId, &'a InstanceSnapshot)>,
+    snapshot_ids: &mut HashMap<RbxId, RbxId>,
+) {
+    ids.push((id, snapshot));
+
+    if let Some(snapshot_id) = snapshot.snapshot_id {
+        snapshot_ids.insert(snapshot_id, id);
+    }
+
+    for child in &snapshot.children {
+        let child_id = tree.insert_instance(
+            RbxInstanceProperties {
+                name: child.name.to_string(),
+                class_name: child.class_name.to_string(),
+                properties: HashMap::new(),
+            },
+            id,
+        );
+
+        insert_snapshot_children(tree, child_id, child, ids, snapshot_ids);
+    }
+}
+
+/// Writes a snapshot of files created by syncback to the given path.
+pub fn write_snapshot(vfs: &Vfs, path: &Path, snapshot: &VfsSnapshot) -> io::Result<()> {
+    match snapshot {
+        VfsSnapshot::File { contents } => vfs.write(path, contents)?,
+        VfsSnapshot::Dir { children } => {
+            vfs.create_dir(path)?;
+
+            for (name, child) in children {
+                write_snapshot(vfs, &path.join(name), child)?;
+            }
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("can't write unknown kind of file to {}", path.display()),
+            ))
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the given instance and its descendants into `output`, which is the
+/// contents of the directory that the instance's parent is represented by.
+///
+/// The instance's name must already have been checked with
+/// `is_valid_file_name`.
+pub fn syncback_instance(
+    tree: &RbxTree,
+    instance: &RbxInstance,
+    output: &mut BTreeMap<String, VfsSnapshot>,
+) -> Result<(), SyncbackError> {
+    let name = &instance.name;
+    let has_children = !instance.get_children_ids().is_empty();
+
+    if has_refs(tree, instance) {
+        output.insert(format!("{}.rbxmx", name), rbxmx_file(tree, instance)?);
+        return Ok(());
+    }
+
+    if let Some(suffix) = script_suffix(&instance.class_name) {
+        let source = match instance.properties.get("Source") {
+            Some(RbxValue::String { value }) => Some(value.as_str()),
+            None => Some(""),
+            _ => None,
+        };
+
+        if let Some(source) = source {
+            let properties = syncable_properties(instance, &["Source"]);
+
+            if !has_children {
+                output.insert(format!("{}{}", name, suffix), VfsSnapshot::file(source));
+
+                if !properties.is_empty() {
+                    output.insert(format!("{}.meta.json", name), meta_file(properties)?);
+                }
+
+                return Ok(());
+            } else if let Some(mut dir) = syncback_children(tree, instance)? {
+                dir.insert(format!("init{}", suffix), VfsSnapshot::file(source));
+
+                if !properties.is_empty() {
+                    dir.insert("init.meta.json".to_owned(), meta_file(properties)?);
+                }
+
+                output.insert(name.clone(), VfsSnapshot::dir(dir));
+                return Ok(());
+            }
+        }
+    }
+
+    if instance.class_name == "StringValue" && !has_children {
+        let value = match instance.properties.get("Value") {
+            Some(RbxValue::String { value }) => Some(value.as_str()),
+            None => Some(""),
+            _ => None,
+        };
+
+        if let Some(value) = value {
+            output.insert(format!("{}.txt", name), VfsSnapshot::file(value));
+
+            let properties = syncable_properties(instance, &["Value"]);
+            if !properties.is_empty() {
+                output.insert(format!("{}.meta.json", name), meta_file(properties)?);
+            }
+
+            return Ok(());
+        }
+    }
+
+    if instance.class_name == "LocalizationTable" && !has_children {
+        if let Some(csv) = localization_csv(instance) {
+            output.insert(format!("{}.csv", name), VfsSnapshot::file(csv));
+
+            let properties = syncable_properties(instance, &["Contents"]);
+            if !properties.is_empty() {
+                output.insert(format!("{}.meta.json", name), meta_file(properties)?);
+            }
+
+            return Ok(());
+        }
+    }
+
+    let is_folder = instance.class_name == "Folder";
+
+    if (!has_children || !subtree_needs_files(tree, instance)) && !is_folder {
+        if subtree_has_refs(tree, instance) {
+            output.insert(format!("{}.rbxmx", name), rbxmx_file(tree, instance)?);
+        } else {
+            let model = JsonModel {
+                name: None,
+                core: json_model_core(tree, instance),
+            };
+
+            output.insert(format!("{}.model.json", name), json_file(&model)?);
+        }
+
+        return Ok(());
+    }
+
+    let class_name = if is_folder {
+        None
+    } else {
+        Some(instance.class_name.clone())
+    };
+
+    match children_dir(tree, instance, class_name)? {
+        Some(dir) => output.insert(name.clone(), dir),
+        None => output.insert(format!("{}.rbxmx", name), rbxmx_file(tree, instance)?),
+    };
+
+    Ok(())
+}
+
+/// Writes the children of the given instance into a new map of files. Returns
+/// `None` if the children can't share a directory, either because one of them
+/// has a name that can't be a file name or because the files for two of them
+/// would have the same path.
+fn syncback_children(
+    tree: &RbxTree,
+    instance: &RbxInstance,
+) -> Result<Option<BTreeMap<String, VfsSnapshot>>, SyncbackError> {
+    let mut output = BTreeMap::new();
+
+    // Some filesystems are case-insensitive, so two files whose names differ
+    // only by case would also clash.
+    let mut seen_names = HashSet::new();
+
+    for &child_id in instance.get_children_ids() {
+        let child = tree.get_instance(child_id).unwrap();
+
+        if !is_valid_file_name(&child.name) {
+            return Ok(None);
+        }
+
+        let mut child_output = BTreeMap::new();
+        syncback_instance(tree, child, &mut child_output)?;
+
+        for (file_name, file) in child_output {
+            if !seen_names.insert(file_name.to_lowercase()) {
+                return Ok(None);
+            }
+
+            output.insert(file_name, file);
+        }
+    }
+
+    Ok(Some(output))
+}
+
+/// Creates a directory containing the children of the given instance. If the
+/// instance has properties or isn't a Folder, an `init.meta.json` file is
+/// created too. Returns `None` if the children can't be written into the same
+/// directory.
+fn children_dir(
+    tree: &RbxTree,
+    instance: &RbxInstance,
+    class_name: Option<String>,
+) -> Result<Option<VfsSnapshot>, SyncbackError> {
+    let mut dir = match syncback_children(tree, instance)? {
+        Some(dir) => dir,
+        None => return Ok(None),
+    };
+
+    let properties = unresolved(syncable_properties(instance, &[]));
+
+    if class_name.is_some() || !properties.is_empty() {
+        let metadata = DirectoryMetadata {
+            ignore_unknown_instances: None,
+            properties,
+            class_name,
+            ..Default::default()
+        };
+
+        dir.insert("init.meta.json".to_owned(), json_file(&metadata)?);
+    }
+
+    Ok(Some(VfsSnapshot::dir(dir)))
+}
+
+fn json_model_core(tree: &RbxTree, instance: &RbxInstance) -> JsonModelCore {
+    let children = instance
+        .get_children_ids()
+        .iter()
+        .map(|&child_id| {
+            let child = tree.get_instance(child_id).unwrap();
+
+            JsonModelInstance {
+                name: child.name.clone(),
+                core: json_model_core(tree, child),
+            }
+        })
+        .collect();
+
+    JsonModelCore {
+        class_name: instance.class_name.clone(),
+        children,
+        properties: unresolved(syncable_properties(instance, &[])),
+        attributes: HashMap::new(),
+        tags: Vec::new(),
+    }
+}
+
+fn meta_file(properties: HashMap<String, RbxValue>) -> Result<VfsSnapshot, SyncbackError> {
+    let metadata = AdjacentMetadata {
+        ignore_unknown_instances: None,
+        properties: unresolved(properties),
+        ..Default::default()
+    };
+
+    json_file(&metadata)
+}
+
+fn rbxmx_file(tree: &RbxTree, instance: &RbxInstance) -> Result<VfsSnapshot, SyncbackError> {
+    let options = rbx_xml::EncodeOptions::new()
+        .property_behavior(rbx_xml::EncodePropertyBehavior::WriteUnknown);
+
+    let mut contents = Vec::new();
+    rbx_xml::to_writer(&mut contents, tree, &[instance.get_id()], options)?;
+
+    Ok(VfsSnapshot::file(contents))
+}
+
+/// Serializes the given value as pretty-printed JSON. Map keys are sorted so
+/// that the output is the same every time, since properties are stored in
+/// hash maps.
+fn json_file<T: Serialize>(value: &T) -> Result<VfsSnapshot, SyncbackError> {
+    let value = sort_keys(serde_json::to_value(value)?);
+
+    let mut contents = serde_json::to_string_pretty(&value)?;
+    contents.push('\n');
+
+    Ok(VfsSnapshot::file(contents))
+}
+
+fn sort_keys(value: serde_json::Value) -> serde_json::Value {
+    use serde_json::Value;
+
+    match value {
+        Value::Object(object) => {
+            let sorted: BTreeMap<_, _> = object
+                .into_iter()
+                .map(|(key, value)| (key, sort_keys(value)))
+                .collect();
+
+            Value::Object(sorted.into_iter().collect())
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(sort_keys).collect()),
+        value => value,
+    }
+}
+
+/// Attempts to turn the contents of a LocalizationTable into a CSV file. If
+/// reading the CSV back wouldn't produce exactly the same contents, returns
+/// `None` so that a lossless format can be used instead.
+fn localization_csv(instance: &RbxInstance) -> Option<Vec<u8>> {
+    let contents = match instance.properties.get("Contents") {
+        Some(RbxValue::String { value }) => value,
+        _ => return None,
+    };
+
+    let entries: Vec<serde_json::Value> = serde_json::from_str(contents).ok()?;
+
+    let mut locales = HashSet::new();
+    for entry in &entries {
+        if let Some(values) = entry.get("values").and_then(|values| values.as_object()) {
+            locales.extend(values.keys().map(String::as_str));
+        }
+    }
+
+    let mut locales: Vec<_> = locales.into_iter().collect();
+    locales.sort();
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+
+    let mut headers = vec!["Key", "Source", "Context", "Example"];
+    headers.extend(locales.iter());
+    writer.write_record(&headers).ok()?;
+
+    for entry in &entries {
+        let field = |name: &str| entry.get(name).and_then(|value| value.as_str());
+        let values = entry.get("values");
+
+        let mut record = vec![
+            field("key").unwrap_or(""),
+            field("source").unwrap_or(""),
+            field("context").unwrap_or(""),
+            field("example").unwrap_or(""),
+        ];
+
+        for locale in &locales {
+            let value = values
+                .and_then(|values| values.get(*locale))
+                .and_then(|value| value.as_str());
+
+            record.push(value.unwrap_or(""));
+        }
+
+        writer.write_record(&record).ok()?;
+    }
+
+    let csv = writer.into_inner().ok()?;
+
+    match convert_localization_csv(&csv) {
+        Ok(ref converted) if converted == contents => Some(csv),
+        _ => None,
+    }
+}
+
+/// Returns the file suffix that the Lua middleware uses for the given script
+/// class, if it is a script class.
+pub fn script_suffix(class_name: &str) -> Option<&'static str> {
+    match class_name {
+        "Script" => Some(".server.lua"),
+        "LocalScript" => Some(".client.lua"),
+        "ModuleScript" => Some(".lua"),
+        _ => None,
+    }
+}
+
+/// Returns all properties of the instance that should be written out, skipping
+/// the given properties and any that match their default value. Ref properties
+/// are skipped too, since only model files can hold them.
+fn syncable_properties(instance: &RbxInstance, skip: &[&str]) -> HashMap<String, RbxValue> {
+    let descriptor = get_class_descriptor(&instance.class_name);
+
+    instance
+        .properties
+        .iter()
+        .filter(|(key, value)| {
+            if skip.contains(&key.as_str()) {
+                return false;
+            }
+
+            if let RbxValue::Ref { .. } = value {
+                return false;
+            }
+
+            let default = descriptor.and_then(|descriptor| descriptor.get_default_value(key));
+            default != Some(value)
+        })
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+fn unresolved(properties: HashMap<String, RbxValue>) -> HashMap<String, UnresolvedRbxValue> {
+    properties
+        .into_iter()
+        .map(|(key, value)| (key, value.into()))
+        .collect()
+}
+
+/// Tells whether the instance has any Ref properties that point to itself or
+/// one of its descendants. These can only be preserved inside of model files.
+/// Refs to instances anywhere else can't be kept and are left out.
+fn has_refs(tree: &RbxTree, instance: &RbxInstance) -> bool {
+    refs_into(tree, instance, instance.get_id())
+}
+
+/// Tells whether any instance in the subtree has a Ref property that points to
+/// another instance in the same subtree.
+fn subtree_has_refs(tree: &RbxTree, instance: &RbxInstance) -> bool {
+    let root_id = instance.get_id();
+
+    refs_into(tree, instance, root_id)
+        || tree
+            .descendants(root_id)
+            .any(|descendant| refs_into(tree, descendant, root_id))
+}
+
+/// Tells whether any Ref property of the instance points into the subtree
+/// starting at `root_id`.
+fn refs_into(tree: &RbxTree, instance: &RbxInstance, root_id: RbxId) -> bool {
+    instance.properties.values().any(|value| match value {
+        RbxValue::Ref {
+            value: Some(target),
+        } => is_in_subtree(tree, *target, root_id),
+        _ => false,
+    })
+}
+
+fn is_in_subtree(tree: &RbxTree, id: RbxId, root_id: RbxId) -> bool {
+    let mut current = tree.get_instance(id);
+
+    while let Some(instance) = current {
+        if instance.get_id() == root_id {
+            return true;
+        }
+
+        current = instance
+            .get_parent_id()
+            .and_then(|parent_id| tree.get_instance(parent_id));
+    }
+
+    false
+}
+
+/// Tells whether any descendants of the instance should be written as their own
+/// files, like scripts.
+fn subtree_needs_files(tree: &RbxTree, instance: &RbxInstance) -> bool {
+    tree.descendants(instance.get_id())
+        .any(|descendant| script_suffix(&descendant.class_name).is_some())
+}
+
+/// Tells whether an instance with the given name can be represented as a file
+/// or directory whose name the snapshot middleware will read back unchanged.
+pub fn is_valid_file_name(name: &str) -> bool {
+    const INVALID_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+    // These suffixes would be mistaken for part of the file's extension, and
+    // names starting with init would usurp their parent.
+    const RESERVED_SUFFIXES: &[&str] = &[".server", ".client", ".meta", ".model", ".project"];
+
+    let lowercase = name.to_lowercase();
+
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.ends_with('.')
+        && !name.ends_with(' ')
+        && !name.contains(|c: char| INVALID_CHARS.contains(&c) || c.is_control())
+        && lowercase != "init"
+        && !lowercase.starts_with("init.")
+        && !RESERVED_SUFFIXES
+            .iter()
+            .any(|suffix| lowercase.ends_with(suffix))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::{collections::BTreeSet, path::Path};
+
+    use maplit::hashmap;
+    use memofs::{InMemoryFs, Vfs};
+    use rbx_dom_weak::RbxInstanceProperties;
+
+    use crate::snapshot::{InstanceContext, InstanceSnapshot};
+    use crate::snapshot_middleware::snapshot_from_vfs;
+
+    /// A view of an instance that ignores properties set to their default
+    /// value, Ref properties, and the order of children.
+    #[derive(Debug, PartialEq)]
+    struct Normalized {
+        name: String,
+        class_name: String,
+        properties: BTreeMap<String, String>,
+        children: BTreeSet<String>,
+    }
+
+    fn normalize(
+        name: &str,
+        class_name: &str,
+        properties: &HashMap<String, RbxValue>,
+        children: Vec<String>,
+    ) -> String {
+        let descriptor = get_class_descriptor(class_name);
+
+        let properties = properties
+            .iter()
+            .filter(|(key, value)| {
+                if let RbxValue::Ref { .. } = value {
+                    return false;
+                }
+
+                let default = descriptor.and_then(|descriptor| descriptor.get_default_value(key));
+                default != Some(value)
+            })
+            .map(|(key, value)| (key.clone(), format!("{:?}", value)))
+            .collect();
+
+        format!(
+            "{:#?}",
+            Normalized {
+                name: name.to_owned(),
+                class_name: class_name.to_owned(),
+                properties,
+                children: children.into_iter().collect(),
+            }
+        )
+    }
+
+    fn normalize_tree(tree: &RbxTree, instance: &RbxInstance) -> String {
+        let children = instance
+            .get_children_ids()
+            .iter()
+            .map(|&id| normalize_tree(tree, tree.get_instance(id).unwrap()))
+            .collect();
+
+        normalize(
+            &instance.name,
+            &instance.class_name,
+            &instance.properties,
+            children,
+        )
+    }
+
+    fn normalize_snapshot(snapshot: &InstanceSnapshot) -> String {
+        let children = snapshot.children.iter().map(normalize_snapshot).collect();
+
+        normalize(
+            &snapshot.name,
+            &snapshot.class_name,
+            &snapshot.properties,
+            children,
+        )
+    }
+
+    fn new_place() -> RbxTree {
+        RbxTree::new(RbxInstanceProperties {
+            name: "DataModel".to_owned(),
+            class_name: "DataModel".to_owned(),
+            properties: HashMap::new(),
+        })
+    }
+
+    fn add(
+        tree: &mut RbxTree,
+        parent: rbx_dom_weak::RbxId,
+        name: &str,
+        class_name: &str,
+        properties: HashMap<String, RbxValue>,
+    ) -> rbx_dom_weak::RbxId {
+        tree.insert_instance(
+            RbxInstanceProperties {
+                name: name.to_owned(),
+                class_name: class_name.to_owned(),
+                properties,
+            },
+            parent,
+        )
+    }
+
+    fn string(value: &str) -> RbxValue {
+        RbxValue::String {
+            value: value.to_owned(),
+        }
+    }
+
+    /// Lists every file in the snapshot as a path relative to its root.
+    fn list_files(snapshot: &VfsSnapshot, prefix: &str, output: &mut Vec<String>) {
+        match snapshot {
+            VfsSnapshot::File { .. } => output.push(prefix.to_owned()),
+            VfsSnapshot::Dir { children } => {
+                if children.is_empty() {
+                    output.push(format!("{}/", prefix));
+                }
+
+                for (name, child) in children {
+                    let path = if prefix.is_empty() {
+                        name.clone()
+                    } else {
+                        format!("{}/{}", prefix, name)
+                    };
+
+                    list_files(child, &path, output);
+                }
+            }
+            _ => unimplemented!(),
+        }
+    }
+
+    /// Runs syncback on the tree, then reads the result back with the snapshot
+    /// middleware and checks that the same instances come out. Returns the
+    /// list of files that syncback created.
+    fn assert_round_trip(tree: &RbxTree) -> Vec<String> {
+        let output = syncback_place(tree, "test").unwrap();
+
+        let mut files = Vec::new();
+        list_files(&output, "", &mut files);
+
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/place", output).unwrap();
+
+        let vfs = Vfs::new(imfs);
+
+        let mut snapshot =
+            snapshot_from_vfs(&InstanceContext::default(), &vfs, Path::new("/place"))
+                .expect("snapshot error")
+                .expect("snapshot returned no instances");
+
+        // The root instance is named after the project, which doesn't matter.
+        let root = tree.get_instance(tree.get_root_id()).unwrap();
+        snapshot.name = root.name.clone().into();
+
+        assert_eq!(normalize_snapshot(&snapshot), normalize_tree(tree, root));
+
+        files
+    }
+
+    #[test]
+    fn scripts_and_folders() {
+        let mut tree = new_place();
+        let root_id = tree.get_root_id();
+
+        let storage = add(
+            &mut tree,
+            root_id,
+            "ReplicatedStorage",
+            "ReplicatedStorage",
+            HashMap::new(),
+        );
+        let shared = add(&mut tree, storage, "Shared", "Folder", HashMap::new());
+        add(
+            &mut tree,
+            shared,
+            "Util",
+            "ModuleScript",
+            hashmap! { "Source".to_owned() => string("return {}") },
+        );
+
+        let server = add(
+            &mut tree,
+            root_id,
+            "ServerScriptService",
+            "ServerScriptService",
+            HashMap::new(),
+        );
+        let main = add(
+            &mut tree,
+            server,
+            "Main",
+            "Script",
+            hashmap! {
+                "Source".to_owned() => string("print(\"hi\")"),
+                "Disabled".to_owned() => RbxValue::Bool { value: true },
+            },
+        );
+        add(
+            &mut tree,
+            main,
+            "Helper",
+            "LocalScript",
+            hashmap! { "Source".to_owned() => string("") },
+        );
+
+        add(&mut tree, root_id, "Lighting", "Lighting", HashMap::new());
+
+        let files = assert_round_trip(&tree);
+
+        assert_eq!(
+            files,
+            vec![
+                "default.project.json",
+                "src/ReplicatedStorage/Shared/Util.lua",
+                "src/ServerScriptService/Main/Helper.client.lua",
+                "src/ServerScriptService/Main/init.meta.json",
+                "src/ServerScriptService/Main/init.server.lua",
+            ]
+        );
+    }
+
+    #[test]
+    fn values_and_models() {
+        let mut tree = new_place();
+        let root_id = tree.get_root_id();
+
+        let workspace = add(
+            &mut tree,
+            root_id,
+            "ServerStorage",
+            "ServerStorage",
+            HashMap::new(),
+        );
+        add(
+            &mut tree,
+            workspace,
+            "Greeting",
+            "StringValue",
+            hashmap! { "Value".to_owned() => string("Hello, world!") },
+        );
+
+        let model = add(&mut tree, workspace, "Model", "Model", HashMap::new());
+        add(
+            &mut tree,
+            model,
+            "Count",
+            "IntValue",
+            hashmap! { "Value".to_owned() => RbxValue::Int32 { value: 5 } },
+        );
+
+        add(
+            &mut tree,
+            workspace,
+            "Strings",
+            "LocalizationTable",
+            hashmap! {
+                "Contents".to_owned() => string(
+                    r#"[{"key":"Ack","source":"Ack!","values":{"es":"¡Ay!"}}]"#,
+                ),
+            },
+        );
+
+        let files = assert_round_trip(&tree);
+
+        assert_eq!(
+            files,
+            vec![
+                "default.project.json",
+                "src/ServerStorage/Greeting.txt",
+                "src/ServerStorage/Model.model.json",
+                "src/ServerStorage/Strings.csv",
+            ]
+        );
+    }
+
+    #[test]
+    fn unrepresentable_names() {
+        let mut tree = new_place();
+        let root_id = tree.get_root_id();
+
+        let storage = add(
+            &mut tree,
+            root_id,
+            "ServerStorage",
+            "ServerStorage",
+            HashMap::new(),
+        );
+        let folder = add(&mut tree, storage, "Folder", "Folder", HashMap::new());
+        add(&mut tree, folder, "init", "Folder", HashMap::new());
+
+        let scripts = add(&mut tree, storage, "Scripts", "Folder", HashMap::new());
+        add(&mut tree, scripts, "Same", "ModuleScript", HashMap::new());
+        add(&mut tree, scripts, "same", "ModuleScript", HashMap::new());
+
+        let files = assert_round_trip(&tree);
+
+        assert_eq!(
+            files,
+            vec![
+                "default.project.json",
+                "src/ServerStorage/Folder.rbxmx",
+                "src/ServerStorage/Scripts.rbxmx",
+            ]
+        );
+    }
+
+    #[test]
+    fn refs_use_models() {
+        let mut tree = new_place();
+        let root_id = tree.get_root_id();
+
+        let storage = add(
+            &mut tree,
+            root_id,
+            "ServerStorage",
+            "ServerStorage",
+            HashMap::new(),
+        );
+        let model = add(&mut tree, storage, "Model", "Model", HashMap::new());
+        let part = add(&mut tree, model, "Part", "Part", HashMap::new());
+
+        tree.get_instance_mut(model).unwrap().properties.insert(
+            "PrimaryPart".to_owned(),
+            RbxValue::Ref { value: Some(part) },
+        );
+
+        let files = assert_round_trip(&tree);
+
+        assert_eq!(
+            files,
+            vec!["default.project.json", "src/ServerStorage/Model.rbxmx"]
+        );
+    }
+
+    #[test]
+    fn service_refs_are_left_out() {
+        let mut tree = new_place();
+        let root_id = tree.get_root_id();
+
+        let workspace = add(&mut tree, root_id, "Workspace", "Workspace", HashMap::new());
+        let camera = add(&mut tree, workspace, "Camera", "Camera", HashMap::new());
+        add(&mut tree, workspace, "Baseplate", "Part", HashMap::new());
+
+        tree.get_instance_mut(workspace).unwrap().properties.insert(
+            "CurrentCamera".to_owned(),
+            RbxValue::Ref {
+                value: Some(camera),
+            },
+        );
+
+        let files = assert_round_trip(&tree);
+
+        assert_eq!(
+            files,
+            vec![
+                "default.project.json",
+                "src/Workspace/Baseplate.model.json",
+                "src/Workspace/Camera.model.json",
+            ]
+        );
+    }
+
+    #[test]
+    fn refs_outside_subtree_are_left_out() {
+        let mut tree = new_place();
+        let root_id = tree.get_root_id();
+
+        let storage = add(
+            &mut tree,
+            root_id,
+            "ServerStorage",
+            "ServerStorage",
+            HashMap::new(),
+        );
+        let target = add(&mut tree, storage, "Target", "Part", HashMap::new());
+        let model = add(&mut tree, storage, "Model", "Model", HashMap::new());
+
+        tree.get_instance_mut(model).unwrap().properties.insert(
+            "PrimaryPart".to_owned(),
+            RbxValue::Ref {
+                value: Some(target),
+            },
+        );
+
+        let files = assert_round_trip(&tree);
+
+        assert_eq!(
+            files,
+            vec![
+                "default.project.json",
+                "src/ServerStorage/Model.model.json",
+                "src/ServerStorage/Target.model.json",
+            ]
+        );
+    }
+
+    #[test]
+    fn clashing_file_names() {
+        let mut tree = new_place();
+        let root_id = tree.get_root_id();
+
+        let storage = add(
+            &mut tree,
+            root_id,
+            "ServerStorage",
+            "ServerStorage",
+            HashMap::new(),
+        );
+        let values = add(&mut tree, storage, "Values", "Folder", HashMap::new());
+        add(
+            &mut tree,
+            values,
+            "A",
+            "StringValue",
+            hashmap! { "Value".to_owned() => string("Hello") },
+        );
+        add(&mut tree, values, "A.txt", "Folder", HashMap::new());
+
+        let files = assert_round_trip(&tree);
+
+        assert_eq!(
+            files,
+            vec!["default.project.json", "src/ServerStorage/Values.rbxmx"]
+        );
+    }
+
+    #[test]
+    fn file_names() {
+        assert!(is_valid_file_name("Hello"));
+        assert!(is_valid_file_name("Hello World"));
+        assert!(is_valid_file_name("init-scripts"));
+
+        assert!(!is_valid_file_name(""));
+        assert!(!is_valid_file_name(".."));
+        assert!(!is_valid_file_name("a/b"));
+        assert!(!is_valid_file_name("what?"));
+        assert!(!is_valid_file_name("trailing."));
+        assert!(!is_valid_file_name("Init"));
+        assert!(!is_valid_file_name("foo.server"));
+        assert!(!is_valid_file_name("foo.Meta"));
+    }
+}
@@ -6,17 +6,24 @@ use std::{collections::HashMap, fs, path::PathBuf, sync::Arc};
 use futures::{Future, Stream};
 
 use hyper::{service::Service, Body, Method, Request, StatusCode};
-use rbx_dom_weak::RbxId;
+use rbx_dom_weak::{RbxId, RbxValue};
 
 use crate::{
     serve_session::ServeSession,
-    snapshot::{InstanceWithMeta, PatchSet, PatchUpdate},
+    session_id::SessionId,
+    snapshot::{
+        find_conflicts, AppliedPatchSet, InstanceSnapshot, InstanceWithMeta, PatchAdd, PatchSet,
+        PatchUpdate, RojoTree,
+    },
+    snapshot_middleware::{ATTRIBUTES_PROPERTY, TAGS_PROPERTY},
     web::{
         interface::{
-            ErrorResponse, Instance, InstanceMetadata as WebInstanceMetadata, InstanceUpdate,
-            OpenResponse, ReadResponse, ServerInfoResponse, SubscribeMessage, SubscribeResponse,
-            WriteRequest, WriteResponse, PROTOCOL_VERSION, SERVER_VERSION,
+            ErrorResponse, ErrorsResponse, FileError, Instance,
+            InstanceMetadata as WebInstanceMetadata, InstanceUpdate, OpenResponse, ReadResponse,
+            ServerInfoResponse, SubscribeMessage, SubscribeResponse, WriteConflict,
+            WriteConflictResponse, WriteRequest, WriteResponse, PROTOCOL_VERSION, SERVER_VERSION,
         },
+        socket,
         util::{json, json_ok},
     },
 };
@@ -35,6 +42,7 @@ impl Service for ApiService {
     fn call(&mut self, request: hyper::Request<Self::ReqBody>) -> Self::Future {
         match (request.method(), request.uri().path()) {
             (&Method::GET, "/api/rojo") => self.handle_api_rojo(),
+            (&Method::GET, "/api/errors") => self.handle_api_errors(),
             (&Method::GET, path) if path.starts_with("/api/read/") => self.handle_api_read(request),
             (&Method::GET, path) if path.starts_with("/api/subscribe/") => {
                 self.handle_api_subscribe(request)
@@ -72,8 +80,21 @@ impl ApiService {
         })
     }
 
+    /// Get every file that currently can't be turned into instances.
+    fn handle_api_errors(&self) -> <Self as Service>::Future {
+        json_ok(&ErrorsResponse {
+            session_id: self.serve_session.session_id(),
+            errors: file_errors(&self.serve_session),
+        })
+    }
+
     /// Retrieve any messages past the given cursor index, and if
-    /// there weren't any, subscribe to receive any new messages.
+    /// there weren't any, subscribe to receive any new messages. If the
+    /// messages past the cursor aren't kept anymore, the client is told to read
+    /// the whole tree again.
+    ///
+    /// Clients that ask to upgrade the connection to a WebSocket instead get
+    /// every message past the cursor streamed to them as it happens.
     fn handle_api_subscribe(&self, request: Request<Body>) -> <Self as Service>::Future {
         let argument = &request.uri().path()["/api/subscribe/".len()..];
         let input_cursor: u32 = match argument.parse() {
@@ -86,63 +107,36 @@ impl ApiService {
             }
         };
 
-        let session_id = self.serve_session.session_id();
+        let receiver = match self.serve_session.message_queue().subscribe(input_cursor) {
+            Ok(receiver) => receiver,
+            Err(err) => {
+                return json(
+                    ErrorResponse::cursor_too_old(err.to_string()),
+                    StatusCode::GONE,
+                );
+            }
+        };
 
-        let receiver = self.serve_session.message_queue().subscribe(input_cursor);
+        if socket::is_upgrade_request(&request) {
+            return socket::handle_upgrade(Arc::clone(&self.serve_session), request, receiver);
+        }
 
-        let tree_handle = self.serve_session.tree_handle();
+        let session_id = self.serve_session.session_id();
+
+        let serve_session = Arc::clone(&self.serve_session);
 
         Box::new(receiver.then(move |result| match result {
             Ok((message_cursor, messages)) => {
-                let tree = tree_handle.lock().unwrap();
-
-                let api_messages = messages
-                    .into_iter()
-                    .map(|message| {
-                        let removed = message.removed;
-
-                        let mut added = HashMap::new();
-                        for id in message.added {
-                            let instance = tree.get_instance(id).unwrap();
-                            added.insert(id, Instance::from_rojo_instance(instance));
-
-                            for instance in tree.descendants(id) {
-                                added.insert(instance.id(), Instance::from_rojo_instance(instance));
-                            }
-                        }
-
-                        let updated = message
-                            .updated
-                            .into_iter()
-                            .map(|update| {
-                                let changed_metadata = update
-                                    .changed_metadata
-                                    .as_ref()
-                                    .map(WebInstanceMetadata::from_rojo_metadata);
-
-                                InstanceUpdate {
-                                    id: update.id,
-                                    changed_name: update.changed_name,
-                                    changed_class_name: update.changed_class_name,
-                                    changed_properties: update.changed_properties,
-                                    changed_metadata,
-                                }
-                            })
-                            .collect();
-
-                        SubscribeMessage {
-                            removed,
-                            added,
-                            updated,
-                        }
-                    })
-                    .collect();
-
-                json_ok(SubscribeResponse {
+                let errors = file_errors(&serve_session);
+                let tree = serve_session.tree();
+
+                json_ok(subscribe_response(
+                    &tree,
                     session_id,
                     message_cursor,
-                    messages: api_messages,
-                })
+                    messages,
+                    errors,
+                ))
             }
             Err(_) => json(
                 ErrorResponse::internal_error("Message queue disconnected sender"),
@@ -152,8 +146,7 @@ impl ApiService {
     }
 
     fn handle_api_write(&self, request: Request<Body>) -> <Self as Service>::Future {
-        let session_id = self.serve_session.session_id();
-        let tree_mutation_sender = self.serve_session.tree_mutation_sender();
+        let serve_session = Arc::clone(&self.serve_session);
 
         Box::new(request.into_body().concat2().and_then(move |body| {
             let request: WriteRequest = match serde_json::from_slice(&body) {
@@ -166,34 +159,14 @@ impl ApiService {
                 }
             };
 
-            if request.session_id != session_id {
-                return json(
+            match apply_write_request(&serve_session, request) {
+                Ok(response) => json_ok(&response),
+                Err(WriteRejection::WrongSession) => json(
                     ErrorResponse::bad_request("Wrong session ID"),
                     StatusCode::BAD_REQUEST,
-                );
+                ),
+                Err(WriteRejection::Conflicts(response)) => json(response, StatusCode::CONFLICT),
             }
-
-            let updated_instances = request
-                .updated
-                .into_iter()
-                .map(|update| PatchUpdate {
-                    id: update.id,
-                    changed_class_name: update.changed_class_name,
-                    changed_name: update.changed_name,
-                    changed_properties: update.changed_properties,
-                    changed_metadata: None,
-                })
-                .collect();
-
-            tree_mutation_sender
-                .send(PatchSet {
-                    removed_instances: Vec::new(),
-                    added_instances: Vec::new(),
-                    updated_instances,
-                })
-                .unwrap();
-
-            json_ok(&WriteResponse { session_id })
         }))
     }
 
@@ -280,6 +253,152 @@ impl ApiService {
     }
 }
 
+/// Turns messages from the message queue into the form they're sent to clients
+/// in.
+pub(super) fn subscribe_response(
+    tree: &RojoTree,
+    session_id: SessionId,
+    message_cursor: u32,
+    messages: Vec<AppliedPatchSet>,
+    errors: Vec<FileError>,
+) -> SubscribeResponse<'_> {
+    let messages = messages
+        .into_iter()
+        .map(|message| {
+            let removed = message.removed;
+
+            let mut added = HashMap::new();
+            for id in message.added {
+                let instance = tree.get_instance(id).unwrap();
+                added.insert(id, Instance::from_rojo_instance(instance));
+
+                for instance in tree.descendants(id) {
+                    added.insert(instance.id(), Instance::from_rojo_instance(instance));
+                }
+            }
+
+            let updated = message
+                .updated
+                .into_iter()
+                .map(|update| {
+                    let changed_metadata = update
+                        .changed_metadata
+                        .as_ref()
+                        .map(WebInstanceMetadata::from_rojo_metadata);
+
+                    InstanceUpdate {
+                        id: update.id,
+                        changed_name: update.changed_name,
+                        changed_class_name: update.changed_class_name,
+                        changed_properties: send_removed_as_empty(update.changed_properties),
+                        changed_metadata,
+                        previous_name: update.previous_name,
+                        previous_class_name: update.previous_class_name,
+                        previous_properties: update.previous_properties,
+                    }
+                })
+                .collect();
+
+            SubscribeMessage {
+                removed,
+                added,
+                updated,
+                client_ids: message.client_ids,
+            }
+        })
+        .collect();
+
+    SubscribeResponse {
+        session_id,
+        message_cursor,
+        messages,
+        errors,
+    }
+}
+
+/// Lists the serve session's current errors, with paths relative to the
+/// project when possible.
+pub(super) fn file_errors(serve_session: &ServeSession) -> Vec<FileError> {
+    let root_dir = serve_session.root_dir();
+
+    serve_session
+        .errors()
+        .errors()
+        .into_iter()
+        .map(|(path, message)| FileError {
+            path: path
+                .strip_prefix(root_dir)
+                .map(|path| path.to_path_buf())
+                .unwrap_or(path),
+            message,
+        })
+        .collect()
+}
+
+/// Reasons that a write from a client can be turned down.
+pub(super) enum WriteRejection {
+    WrongSession,
+    Conflicts(WriteConflictResponse),
+}
+
+/// Applies the changes a client asked for, shared by /api/write and WebSocket
+/// connections.
+pub(super) fn apply_write_request(
+    serve_session: &ServeSession,
+    request: WriteRequest,
+) -> Result<WriteResponse, WriteRejection> {
+    let session_id = serve_session.session_id();
+
+    if request.session_id != session_id {
+        return Err(WriteRejection::WrongSession);
+    }
+
+    let updated_instances = request
+        .updated
+        .into_iter()
+        .map(|update| PatchUpdate {
+            id: update.id,
+            changed_class_name: update.changed_class_name,
+            changed_name: update.changed_name,
+            changed_properties: update.changed_properties,
+            changed_metadata: None,
+            previous_name: update.previous_name,
+            previous_class_name: update.previous_class_name,
+            previous_properties: update.previous_properties,
+        })
+        .collect();
+
+    let added_instances = added_instances_to_patches(request.added);
+
+    let patch_set = PatchSet {
+        removed_instances: Vec::new(),
+        added_instances,
+        updated_instances,
+    };
+
+    // Writes based on values that someone else has changed since are rejected
+    // so that the client can decide what to do about them, instead of
+    // overwriting the other change.
+    let conflicts = find_conflicts(&serve_session.tree(), &patch_set);
+
+    if !conflicts.is_empty() {
+        return Err(WriteRejection::Conflicts(WriteConflictResponse {
+            session_id,
+            conflicts: conflicts
+                .into_iter()
+                .map(WriteConflict::from_patch_conflict)
+                .collect(),
+        }));
+    }
+
+    serve_session
+        .tree_mutation_sender()
+        .send(patch_set)
+        .unwrap();
+
+    Ok(WriteResponse { session_id })
+}
+
 /// If this instance is represented by a script, try to find the correct .lua
 /// file to open to edit it.
 fn pick_script_path(instance: InstanceWithMeta<'_>) -> Option<PathBuf> {
@@ -307,3 +426,76 @@ fn pick_script_path(instance: InstanceWithMeta<'_>) -> Option<PathBuf> {
         })
         .map(|path| path.to_owned())
 }
+
+/// Turns the instances added by a client in a write request into patches.
+///
+/// Instances whose parent is also part of the request are nested inside of
+/// their parent's snapshot, so each patch describes a whole new subtree. The
+/// IDs the client picked are kept as snapshot IDs so that Ref properties
+/// between new instances can be resolved.
+fn added_instances_to_patches(mut added: HashMap<RbxId, Instance<'static>>) -> Vec<PatchAdd> {
+    let mut root_ids: Vec<_> = added
+        .iter()
+        .filter_map(|(&id, instance)| match instance.parent {
+            Some(parent) if !added.contains_key(&parent) => Some((id, parent)),
+            _ => None,
+        })
+        .collect();
+
+    // HashMap iteration order is random, but files should be created in a
+    // predictable order.
+    root_ids.sort_by_key(|&(id, _)| id.to_string());
+
+    root_ids
+        .into_iter()
+        .filter_map(|(id, parent_id)| {
+            let instance = added_instance_to_snapshot(&mut added, id)?;
+
+            Some(PatchAdd {
+                parent_id,
+                instance,
+            })
+        })
+        .collect()
+}
+
+fn added_instance_to_snapshot(
+    added: &mut HashMap<RbxId, Instance<'static>>,
+    id: RbxId,
+) -> Option<InstanceSnapshot> {
+    // Removing each instance as it's visited makes sure that malformed
+    // requests with cycles can't recurse forever.
+    let instance = added.remove(&id)?;
+
+    let children: Vec<_> = instance
+        .children
+        .iter()
+        .filter_map(|&child_id| added_instance_to_snapshot(added, child_id))
+        .collect();
+
+    let mut snapshot = InstanceSnapshot::new()
+        .name(instance.name.into_owned())
+        .class_name(instance.class_name.into_owned())
+        .properties(instance.properties.into_owned())
+        .children(children);
+
+    snapshot.snapshot_id = Some(id);
+
+    Some(snapshot)
+}
+
+/// Removed properties are sent as nulls, which the plugin loses when it decodes
+/// messages. The plugin applies tags and attributes with its own code, which
+/// treats empty values as having none, so removing them is sent that way
+/// instead.
+fn send_removed_as_empty(
+    mut properties: HashMap<String, Option<RbxValue>>,
+) -> HashMap<String, Option<RbxValue>> {
+    for name in &[TAGS_PROPERTY, ATTRIBUTES_PROPERTY] {
+        if let Some(value @ None) = properties.get_mut(*name) {
+            *value = Some(RbxValue::BinaryString { value: Vec::new() });
+        }
+    }
+
+    properties
+}
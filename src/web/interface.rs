@@ -3,6 +3,7 @@
 use std::{
     borrow::Cow,
     collections::{HashMap, HashSet},
+    path::PathBuf,
 };
 
 use rbx_dom_weak::{RbxId, RbxValue};
@@ -10,7 +11,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     session_id::SessionId,
-    snapshot::{InstanceMetadata as RojoInstanceMetadata, InstanceWithMeta},
+    snapshot::{InstanceMetadata as RojoInstanceMetadata, InstanceWithMeta, PatchConflict},
 };
 
 /// Server version to report over the API, not exposed outside this crate.
@@ -26,6 +27,11 @@ pub struct SubscribeMessage<'a> {
     pub removed: Vec<RbxId>,
     pub added: HashMap<RbxId, Instance<'a>>,
     pub updated: Vec<InstanceUpdate>,
+
+    /// Maps the IDs of added instances that a client asked for to the IDs that
+    /// client picked, so that it can reuse the instances it already has.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub client_ids: HashMap<RbxId, RbxId>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -40,6 +46,17 @@ pub struct InstanceUpdate {
     #[serde(default)]
     pub changed_properties: HashMap<String, Option<RbxValue>>,
     pub changed_metadata: Option<InstanceMetadata>,
+
+    /// The values that changed fields had before the change. Messages from the
+    /// server fill these in so that clients can tell whether they were up to
+    /// date. Clients can fill them in on writes so that the server rejects the
+    /// write if someone else changed those fields first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_class_name: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub previous_properties: HashMap<String, Option<RbxValue>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -108,8 +125,11 @@ pub struct WriteRequest {
     pub session_id: SessionId,
     pub removed: Vec<RbxId>,
 
+    /// Instances added by the client, keyed by IDs picked by the client. An
+    /// instance's parent is either an instance that already exists on the
+    /// server or another instance in this map.
     #[serde(default)]
-    pub added: HashMap<RbxId, ()>,
+    pub added: HashMap<RbxId, Instance<'static>>,
     pub updated: Vec<InstanceUpdate>,
 }
 
@@ -119,6 +139,37 @@ pub struct WriteResponse {
     pub session_id: SessionId,
 }
 
+/// Response body from /api/write when some of the updates in the request were
+/// based on values that have changed since. None of the request is applied.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteConflictResponse {
+    pub session_id: SessionId,
+    pub conflicts: Vec<WriteConflict>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WriteConflict {
+    pub id: RbxId,
+
+    /// Either `Name`, `ClassName`, or the name of a property.
+    pub field: String,
+    pub expected: Option<RbxValue>,
+    pub current: Option<RbxValue>,
+}
+
+impl WriteConflict {
+    pub(crate) fn from_patch_conflict(conflict: PatchConflict) -> Self {
+        Self {
+            id: conflict.id,
+            field: conflict.field,
+            expected: conflict.expected,
+            current: conflict.current,
+        }
+    }
+}
+
 /// Response body from /api/subscribe/{cursor}
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -126,6 +177,29 @@ pub struct SubscribeResponse<'a> {
     pub session_id: SessionId,
     pub message_cursor: u32,
     pub messages: Vec<SubscribeMessage<'a>>,
+
+    /// Every file that currently can't be turned into instances, so that
+    /// clients can show them without polling /api/errors.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FileError>,
+}
+
+/// Response body from /api/errors
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorsResponse {
+    pub session_id: SessionId,
+    pub errors: Vec<FileError>,
+}
+
+/// A file that Rojo couldn't turn into instances the last time it changed.
+/// Instances from the file keep the state they had before the error.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileError {
+    /// The path of the file, relative to the project's folder when possible.
+    pub path: PathBuf,
+    pub message: String,
 }
 
 /// Response body from /api/open/{id}
@@ -135,6 +209,20 @@ pub struct OpenResponse {
     pub session_id: SessionId,
 }
 
+/// Message sent by the server over a WebSocket connection opened on
+/// /api/subscribe/{cursor}.
+///
+/// Clients send `WriteRequest` messages over the same connection, which are
+/// answered with a `Write`, `WriteConflict`, or `Error` message.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum SocketMessage<'a> {
+    Subscribe(SubscribeResponse<'a>),
+    Write(WriteResponse),
+    WriteConflict(WriteConflictResponse),
+    Error(ErrorResponse),
+}
+
 /// General response type returned from all Rojo routes
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -158,6 +246,13 @@ impl ErrorResponse {
         }
     }
 
+    pub fn cursor_too_old<S: Into<String>>(details: S) -> Self {
+        Self {
+            kind: ErrorResponseKind::CursorTooOld,
+            details: details.into(),
+        }
+    }
+
     pub fn internal_error<S: Into<String>>(details: S) -> Self {
         Self {
             kind: ErrorResponseKind::InternalError,
@@ -170,5 +265,10 @@ impl ErrorResponse {
 pub enum ErrorResponseKind {
     NotFound,
     BadRequest,
+
+    /// The messages past the requested cursor aren't kept anymore. Clients
+    /// need to read the whole tree again with /api/read and subscribe with the
+    /// cursor it returns.
+    CursorTooOld,
     InternalError,
 }
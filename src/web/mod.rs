@@ -1,6 +1,7 @@
 mod api;
 mod assets;
 pub mod interface;
+mod socket;
 mod ui;
 mod util;
 
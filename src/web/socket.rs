@@ -0,0 +1,211 @@
+//! Implements the WebSocket transport for /api/subscribe. Instead of polling
+//! for each batch of changes, clients that open a WebSocket get every change
+//! pushed to them as it happens, and can send writes over the same connection.
+
+use std::sync::Arc;
+
+use futures::{
+    future::{self, Loop},
+    sync::{mpsc, oneshot},
+    Future, Sink, Stream,
+};
+use hyper::{
+    header::{CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, UPGRADE},
+    upgrade::Upgraded,
+    Body, Request, Response, StatusCode,
+};
+use log::{debug, warn};
+use tokio::codec::Framed;
+use websocket_base::{
+    codec::ws::{Context, MessageCodec},
+    header::{WebSocketAccept, WebSocketKey},
+    OwnedMessage,
+};
+
+use crate::{
+    serve_session::ServeSession,
+    snapshot::AppliedPatchSet,
+    web::{
+        api::{apply_write_request, file_errors, subscribe_response, WriteRejection},
+        interface::{ErrorResponse, SocketMessage, WriteRequest},
+        util::json,
+    },
+};
+
+type ResponseFuture = Box<dyn Future<Item = Response<Body>, Error = hyper::Error> + Send>;
+type MessageReceiver = oneshot::Receiver<(u32, Vec<AppliedPatchSet>)>;
+
+/// Tells whether the client asked to turn this request into a WebSocket.
+pub fn is_upgrade_request(request: &Request<Body>) -> bool {
+    request
+        .headers()
+        .get(UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.eq_ignore_ascii_case("websocket"))
+        .unwrap_or(false)
+}
+
+/// Accepts the WebSocket handshake and starts serving messages from the given
+/// subscription once the connection has been upgraded.
+pub fn handle_upgrade(
+    serve_session: Arc<ServeSession>,
+    request: Request<Body>,
+    receiver: MessageReceiver,
+) -> ResponseFuture {
+    let key = request
+        .headers()
+        .get(SEC_WEBSOCKET_KEY)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<WebSocketKey>().ok());
+
+    let key = match key {
+        Some(key) => key,
+        None => {
+            return json(
+                ErrorResponse::bad_request("Missing or malformed Sec-WebSocket-Key header"),
+                StatusCode::BAD_REQUEST,
+            );
+        }
+    };
+
+    let upgrade = request
+        .into_body()
+        .on_upgrade()
+        .map_err(|err| warn!("Could not upgrade connection to a WebSocket: {}", err))
+        .and_then(move |upgraded| serve_socket(serve_session, upgraded, receiver));
+
+    hyper::rt::spawn(upgrade);
+
+    let response = Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(UPGRADE, "websocket")
+        .header(CONNECTION, "Upgrade")
+        .header(SEC_WEBSOCKET_ACCEPT, WebSocketAccept::new(&key).serialize())
+        .body(Body::empty())
+        .unwrap();
+
+    Box::new(future::ok(response))
+}
+
+/// Runs a single WebSocket connection until either side closes it.
+///
+/// Everything sent to the client goes through a channel so that change
+/// notifications and answers to writes can't interleave halfway through a
+/// message.
+fn serve_socket(
+    serve_session: Arc<ServeSession>,
+    upgraded: Upgraded,
+    receiver: MessageReceiver,
+) -> impl Future<Item = (), Error = ()> {
+    let (sink, stream) = Framed::new(upgraded, MessageCodec::default(Context::Server)).split();
+    let (sender, outgoing) = mpsc::unbounded();
+
+    let sink = sink.sink_map_err(|err| debug!("Could not send WebSocket message: {}", err));
+    hyper::rt::spawn(outgoing.forward(sink).map(|_| ()));
+
+    let subscription = forward_messages(Arc::clone(&serve_session), sender.clone(), receiver);
+    let requests = handle_requests(serve_session, sender, stream);
+
+    // When the client goes away, the subscription is dropped along with its
+    // sender, which lets the forwarding task above finish up.
+    requests.select(subscription).then(|_| Ok(()))
+}
+
+/// Sends every batch of messages to the client, resubscribing after each one.
+///
+/// If the client is so far behind that messages it hasn't gotten yet were
+/// dropped, it's told to read the whole tree again and the connection closes.
+fn forward_messages(
+    serve_session: Arc<ServeSession>,
+    sender: mpsc::UnboundedSender<OwnedMessage>,
+    receiver: MessageReceiver,
+) -> impl Future<Item = (), Error = ()> {
+    future::loop_fn(receiver, move |receiver| {
+        let serve_session = Arc::clone(&serve_session);
+        let sender = sender.clone();
+
+        receiver
+            .map_err(|_| warn!("Message queue disconnected sender"))
+            .and_then(move |(message_cursor, messages)| {
+                let message = {
+                    let errors = file_errors(&serve_session);
+                    let tree = serve_session.tree();
+
+                    to_text(&SocketMessage::Subscribe(subscribe_response(
+                        &tree,
+                        serve_session.session_id(),
+                        message_cursor,
+                        messages,
+                        errors,
+                    )))
+                };
+
+                sender.unbounded_send(message).map_err(|_| ())?;
+
+                match serve_session.message_queue().subscribe(message_cursor) {
+                    Ok(receiver) => Ok(Loop::Continue(receiver)),
+                    Err(err) => {
+                        let error = ErrorResponse::cursor_too_old(err.to_string());
+                        let _ = sender.unbounded_send(to_text(&SocketMessage::Error(error)));
+                        let _ = sender.unbounded_send(OwnedMessage::Close(None));
+
+                        Ok(Loop::Break(()))
+                    }
+                }
+            })
+    })
+}
+
+/// Answers messages sent by the client until it closes the connection.
+fn handle_requests<S>(
+    serve_session: Arc<ServeSession>,
+    sender: mpsc::UnboundedSender<OwnedMessage>,
+    stream: S,
+) -> impl Future<Item = (), Error = ()>
+where
+    S: Stream<Item = OwnedMessage>,
+    S::Error: std::fmt::Display,
+{
+    stream
+        .map_err(|err| debug!("Could not read WebSocket message: {}", err))
+        .take_while(|message| Ok(!message.is_close()))
+        .for_each(move |message| {
+            let response = match message {
+                OwnedMessage::Text(text) => Some(to_text(&handle_write(&serve_session, &text))),
+                OwnedMessage::Ping(data) => Some(OwnedMessage::Pong(data)),
+                OwnedMessage::Binary(_) => Some(to_text(&SocketMessage::Error(
+                    ErrorResponse::bad_request("Binary messages are not supported"),
+                ))),
+                OwnedMessage::Pong(_) | OwnedMessage::Close(_) => None,
+            };
+
+            match response {
+                Some(response) => sender.unbounded_send(response).map_err(|_| ()),
+                None => Ok(()),
+            }
+        })
+}
+
+fn handle_write(serve_session: &ServeSession, text: &str) -> SocketMessage<'static> {
+    let request: WriteRequest = match serde_json::from_str(text) {
+        Ok(request) => request,
+        Err(err) => {
+            return SocketMessage::Error(ErrorResponse::bad_request(format!(
+                "Invalid message: {}",
+                err
+            )));
+        }
+    };
+
+    match apply_write_request(serve_session, request) {
+        Ok(response) => SocketMessage::Write(response),
+        Err(WriteRejection::WrongSession) => {
+            SocketMessage::Error(ErrorResponse::bad_request("Wrong session ID"))
+        }
+        Err(WriteRejection::Conflicts(response)) => SocketMessage::WriteConflict(response),
+    }
+}
+
+fn to_text(message: &SocketMessage<'_>) -> OwnedMessage {
+    OwnedMessage::Text(serde_json::to_string(message).expect("Could not serialize message"))
+}
@@ -12,8 +12,9 @@ use crate::{
     serve_session::ServeSession,
     snapshot::RojoTree,
     web::{
+        api::file_errors,
         assets,
-        interface::{ErrorResponse, SERVER_VERSION},
+        interface::{ErrorResponse, FileError, SERVER_VERSION},
         util::json,
     },
 };
@@ -66,11 +67,16 @@ impl UiService {
     }
 
     fn handle_home(&self) -> Response<Body> {
+        let errors = file_errors(&self.serve_session);
+
         let page = self.normal_page(html! {
-            <div class="button-list">
-                { Self::button("Rojo Documentation", "https://rojo.space/docs") }
-                { Self::button("View instance tree state", "/show-instances") }
-            </div>
+            <>
+                { Self::error_list(&errors) }
+                <div class="button-list">
+                    { Self::button("Rojo Documentation", "https://rojo.space/docs") }
+                    { Self::button("View instance tree state", "/show-instances") }
+                </div>
+            </>
         });
 
         Response::builder()
@@ -209,6 +215,29 @@ impl UiService {
         }
     }
 
+    /// Lists files that couldn't be turned into instances, if there are any.
+    fn error_list(errors: &[FileError]) -> HtmlContent<'_> {
+        if errors.is_empty() {
+            return HtmlContent::None;
+        }
+
+        let list = errors.iter().map(|error| {
+            html! {
+                <li class="error">
+                    <span class="error-path">{ format!("{}", error.path.display()) }</span>
+                    ": " { error.message.as_str() }
+                </li>
+            }
+        });
+
+        html! {
+            <section class="main-section">
+                <h1 class="section-title">"Errors"</h1>
+                <ul class="error-list">{ Fragment::new(list) }</ul>
+            </section>
+        }
+    }
+
     fn display_value(value: &RbxValue) -> String {
         match value {
             RbxValue::String { value } => value.clone(),
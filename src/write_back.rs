@@ -0,0 +1,870 @@
+//! Persists property changes made by a live sync client back into the files
+//! that describe those properties.
+
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use memofs::{IoResultExt, Vfs};
+use rbx_dom_weak::{RbxId, RbxValue, UnresolvedRbxValue};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::{
+    project::Project,
+    snapshot::{InstanceWithMeta, InstigatingSource, RojoTree},
+    snapshot_middleware::{AdjacentMetadata, DirectoryMetadata, JsonModel},
+};
+
+#[derive(Debug, Error)]
+pub enum WriteBackError {
+    #[error("it does not come from a file or project")]
+    NoSource,
+
+    #[error("the file it comes from, {}, can't hold properties", .path.display())]
+    UnsupportedFile { path: PathBuf },
+
+    #[error("it could not be found in {}", .path.display())]
+    NotFound { path: PathBuf },
+
+    #[error("malformed JSON file at path {}", .path.display())]
+    Json {
+        source: serde_json::Error,
+        path: PathBuf,
+    },
+
+    #[error(transparent)]
+    Io {
+        #[from]
+        source: io::Error,
+    },
+}
+
+impl WriteBackError {
+    fn json(source: serde_json::Error, path: &Path) -> Self {
+        Self::Json {
+            source,
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+/// Where the properties of an instance are stored.
+#[derive(Debug)]
+enum PropertyFile {
+    /// A `.meta.json` file next to the file an instance came from.
+    Adjacent(PathBuf),
+
+    /// An `init.meta.json` file inside the directory an instance came from.
+    Directory(PathBuf),
+
+    /// A `.model.json` file, along with the names of the children that lead
+    /// from its root to the instance.
+    JsonModel(PathBuf, Vec<String>),
+
+    /// A project file, along with the keys that lead from its tree to the
+    /// instance's node.
+    Project(PathBuf, Vec<String>),
+}
+
+/// Writes changed properties of an instance into the file that they should be
+/// stored in. A value of `None` removes the property from that file.
+///
+/// Files are edited as plain JSON instead of through the types that they're
+/// read into, so that keys keep their order and nothing besides the changed
+/// properties is touched, like relative paths in projects.
+pub fn write_properties(
+    vfs: &Vfs,
+    tree: &RojoTree,
+    id: RbxId,
+    changed_properties: &HashMap<String, Option<RbxValue>>,
+) -> Result<(), WriteBackError> {
+    let instance = tree.get_instance(id).ok_or(WriteBackError::NoSource)?;
+
+    match property_file(vfs, tree, instance)? {
+        PropertyFile::Adjacent(path) => {
+            let mut document = read_json_or_empty(vfs, &path)?;
+            check_format::<AdjacentMetadata>(&document, &path)?;
+
+            let object = as_object(&mut document, &path)?;
+            apply_changes(object, "properties", changed_properties, &path)?;
+
+            write_json(vfs, &path, &document)
+        }
+        PropertyFile::Directory(path) => {
+            let mut document = read_json_or_empty(vfs, &path)?;
+            check_format::<DirectoryMetadata>(&document, &path)?;
+
+            let object = as_object(&mut document, &path)?;
+            apply_changes(object, "properties", changed_properties, &path)?;
+
+            write_json(vfs, &path, &document)
+        }
+        PropertyFile::JsonModel(path, names) => {
+            let mut document = read_json(vfs, &path)?;
+            check_format::<JsonModel>(&document, &path)?;
+
+            let mut object = as_object(&mut document, &path)?;
+            for name in &names {
+                object = object
+                    .get_mut("Children")
+                    .and_then(|children| children.as_array_mut())
+                    .and_then(|children| {
+                        children.iter_mut().find(|child| {
+                            child.get("Name").and_then(|name| name.as_str()) == Some(name)
+                        })
+                    })
+                    .and_then(|child| child.as_object_mut())
+                    .ok_or_else(|| WriteBackError::NotFound { path: path.clone() })?;
+            }
+
+            apply_changes(object, "Properties", changed_properties, &path)?;
+
+            write_json(vfs, &path, &document)
+        }
+        PropertyFile::Project(path, keys) => {
+            let mut document = read_json(vfs, &path)?;
+
+            let mut node = document
+                .get_mut("tree")
+                .ok_or_else(|| WriteBackError::NotFound { path: path.clone() })?;
+
+            for key in &keys {
+                node = node
+                    .get_mut(key)
+                    .ok_or_else(|| WriteBackError::NotFound { path: path.clone() })?;
+            }
+
+            let node = node
+                .as_object_mut()
+                .ok_or_else(|| WriteBackError::NotFound { path: path.clone() })?;
+
+            apply_changes(node, "$properties", changed_properties, &path)?;
+
+            write_json(vfs, &path, &document)
+        }
+    }
+}
+
+fn read_json(vfs: &Vfs, path: &Path) -> Result<Value, WriteBackError> {
+    let contents = vfs.read(path)?;
+    serde_json::from_slice(&contents).map_err(|source| WriteBackError::json(source, path))
+}
+
+/// Reads a JSON file, treating a missing file as an empty object so that it
+/// can be created.
+fn read_json_or_empty(vfs: &Vfs, path: &Path) -> Result<Value, WriteBackError> {
+    match vfs.read(path).with_not_found()? {
+        Some(contents) => {
+            serde_json::from_slice(&contents).map_err(|source| WriteBackError::json(source, path))
+        }
+        None => Ok(Value::Object(Map::new())),
+    }
+}
+
+/// Makes sure that a file can be read as the given type before it's edited,
+/// so that we never write into a file that Rojo couldn't read anyways.
+fn check_format<T: DeserializeOwned>(document: &Value, path: &Path) -> Result<(), WriteBackError> {
+    serde_json::from_value::<T>(document.clone())
+        .map(|_| ())
+        .map_err(|source| WriteBackError::json(source, path))
+}
+
+fn as_object<'a>(
+    document: &'a mut Value,
+    path: &Path,
+) -> Result<&'a mut Map<String, Value>, WriteBackError> {
+    document
+        .as_object_mut()
+        .ok_or_else(|| WriteBackError::NotFound {
+            path: path.to_path_buf(),
+        })
+}
+
+/// Finds the file that the properties of the given instance are stored in.
+fn property_file(
+    vfs: &Vfs,
+    tree: &RojoTree,
+    instance: InstanceWithMeta<'_>,
+) -> Result<PropertyFile, WriteBackError> {
+    // Instances inside of model files don't have an instigating source, so we
+    // look for the nearest ancestor that does.
+    let mut names = Vec::new();
+    let mut current = instance;
+
+    let source = loop {
+        if let Some(source) = &current.metadata().instigating_source {
+            break source;
+        }
+
+        names.push(current.name().to_owned());
+        current = current
+            .parent()
+            .and_then(|parent| tree.get_instance(parent))
+            .ok_or(WriteBackError::NoSource)?;
+    };
+
+    names.reverse();
+
+    match source {
+        InstigatingSource::Path(path) if path_is_json_model(path) => {
+            Ok(PropertyFile::JsonModel(path.clone(), names))
+        }
+        InstigatingSource::Path(path) if path_is_other_model(path) || !names.is_empty() => {
+            Err(WriteBackError::UnsupportedFile { path: path.clone() })
+        }
+        _ if !names.is_empty() => Err(WriteBackError::NoSource),
+        InstigatingSource::Path(path) if Project::is_project_file(path) => {
+            Ok(PropertyFile::Project(path.clone(), Vec::new()))
+        }
+        InstigatingSource::Path(path) => {
+            if vfs.metadata(path)?.is_dir() {
+                return Ok(PropertyFile::Directory(path.join("init.meta.json")));
+            }
+
+            let context = &current.metadata().context;
+
+            if !context.middleware.reads_adjacent_metadata(context, path) {
+                return Err(WriteBackError::UnsupportedFile { path: path.clone() });
+            }
+
+            Ok(PropertyFile::Adjacent(
+                path.with_file_name(format!("{}.meta.json", current.name())),
+            ))
+        }
+        InstigatingSource::ProjectNode(project_path, _, _, _) => {
+            project_node_keys(vfs, tree, current, project_path)
+        }
+    }
+}
+
+/// Walks up the tree from an instance that came from a project node until the
+/// instance created from the root of that project is found, collecting the
+/// keys of each node along the way.
+fn project_node_keys(
+    vfs: &Vfs,
+    tree: &RojoTree,
+    instance: InstanceWithMeta<'_>,
+    node_project_path: &Path,
+) -> Result<PropertyFile, WriteBackError> {
+    let mut keys = vec![instance.name().to_owned()];
+    let mut current = instance;
+
+    loop {
+        current = current
+            .parent()
+            .and_then(|parent| tree.get_instance(parent))
+            .ok_or(WriteBackError::NoSource)?;
+
+        if let Some(project_path) = project_file_for(vfs, current)? {
+            if project_path == node_project_path {
+                keys.reverse();
+                return Ok(PropertyFile::Project(project_path, keys));
+            }
+        }
+
+        match &current.metadata().instigating_source {
+            Some(InstigatingSource::ProjectNode(path, _, _, _)) if path == node_project_path => {
+                keys.push(current.name().to_owned());
+            }
+            _ => return Err(WriteBackError::NoSource),
+        }
+    }
+}
+
+/// If the given instance is the root of a project, returns the path to that
+/// project's file.
+fn project_file_for(
+    vfs: &Vfs,
+    instance: InstanceWithMeta<'_>,
+) -> Result<Option<PathBuf>, WriteBackError> {
+    let path = match &instance.metadata().instigating_source {
+        Some(InstigatingSource::Path(path)) => path.clone(),
+
+        // Projects nested inside of other projects are created from a node of
+        // the outer project that points to them.
+        Some(InstigatingSource::ProjectNode(project_path, _, node, _)) => match &node.path {
+            Some(path) => project_path.parent().unwrap().join(path),
+            None => return Ok(None),
+        },
+        None => return Ok(None),
+    };
+
+    if Project::is_project_file(&path) {
+        return Ok(Some(path));
+    }
+
+    let default_path = path.join("default.project.json");
+
+    if vfs.metadata(&default_path).with_not_found()?.is_some() {
+        Ok(Some(default_path))
+    } else {
+        Ok(None)
+    }
+}
+
+fn path_is_json_model(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(".model.json")
+}
+
+/// Models written as YAML or TOML can't be edited without losing their
+/// formatting and comments, so properties are never written into them.
+fn path_is_other_model(path: &Path) -> bool {
+    let path = path.to_string_lossy();
+
+    [".model.yaml", ".model.yml", ".model.toml"]
+        .iter()
+        .any(|suffix| path.ends_with(suffix))
+}
+
+/// Applies changed properties to the object stored under `key` in the given
+/// JSON object. Properties that already exist keep their place, new ones are
+/// added at the end in alphabetical order, and the object is removed if it
+/// ends up empty.
+fn apply_changes(
+    object: &mut Map<String, Value>,
+    key: &str,
+    changed_properties: &HashMap<String, Option<RbxValue>>,
+    path: &Path,
+) -> Result<(), WriteBackError> {
+    let properties = object
+        .entry(key)
+        .or_insert_with(|| Value::Object(Map::new()))
+        .as_object_mut()
+        .ok_or_else(|| WriteBackError::NotFound {
+            path: path.to_path_buf(),
+        })?;
+
+    let mut changes: Vec<_> = changed_properties.iter().collect();
+    changes.sort_by_key(|(name, _)| name.as_str());
+
+    for (name, value) in changes {
+        match value {
+            Some(value) => {
+                let value = serde_json::to_value(UnresolvedRbxValue::from(value.clone()))
+                    .map_err(|source| WriteBackError::json(source, path))?;
+
+                properties.insert(name.clone(), value);
+            }
+            None => {
+                properties.shift_remove(name);
+            }
+        }
+    }
+
+    if properties.is_empty() {
+        object.shift_remove(key);
+    }
+
+    Ok(())
+}
+
+/// Writes a JSON document as pretty-printed JSON.
+fn write_json(vfs: &Vfs, path: &Path, document: &Value) -> Result<(), WriteBackError> {
+    let mut contents = serde_json::to_string_pretty(document)
+        .map_err(|source| WriteBackError::json(source, path))?;
+    contents.push('\n');
+
+    vfs.write(path, contents)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use maplit::hashmap;
+    use memofs::{InMemoryFs, VfsSnapshot};
+    use rbx_dom_weak::RbxInstanceProperties;
+
+    use crate::{
+        snapshot::{
+            apply_patch_set, compute_patch_set, InstanceContext, InstancePropertiesWithMeta,
+        },
+        snapshot_middleware::{snapshot_from_vfs, FileRule},
+    };
+
+    fn tree_from_vfs(vfs: &Vfs, path: &str) -> RojoTree {
+        tree_with_context(&InstanceContext::default(), vfs, path)
+    }
+
+    fn tree_with_context(context: &InstanceContext, vfs: &Vfs, path: &str) -> RojoTree {
+        let snapshot = snapshot_from_vfs(context, vfs, Path::new(path))
+            .unwrap()
+            .unwrap();
+
+        let mut tree = RojoTree::new(InstancePropertiesWithMeta {
+            properties: RbxInstanceProperties {
+                name: "ROOT".to_owned(),
+                class_name: "Folder".to_owned(),
+                properties: Default::default(),
+            },
+            metadata: Default::default(),
+        });
+
+        let root_id = tree.get_root_id();
+        let patch_set = compute_patch_set(&snapshot, &tree, root_id);
+        apply_patch_set(&mut tree, patch_set);
+
+        tree
+    }
+
+    fn find_id(tree: &RojoTree, name: &str) -> RbxId {
+        let root_id = tree.get_root_id();
+
+        std::iter::once(tree.get_instance(root_id).unwrap())
+            .chain(tree.descendants(root_id))
+            .find(|instance| instance.name() == name)
+            .unwrap()
+            .id()
+    }
+
+    fn read_string(vfs: &Vfs, path: &str) -> String {
+        String::from_utf8(vfs.read(path).unwrap().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn adjacent_meta_file() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "Hello.server.lua" => VfsSnapshot::file("print(\"Hello\")"),
+                "Other.lua" => VfsSnapshot::file("return nil"),
+                "Other.meta.json" => VfsSnapshot::file(r#"{
+                    "ignoreUnknownInstances": true,
+                    "properties": { "Disabled": true }
+                }"#),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+        let tree = tree_from_vfs(&vfs, "/foo");
+
+        write_properties(
+            &vfs,
+            &tree,
+            find_id(&tree, "Hello"),
+            &hashmap! {
+                "Disabled".to_owned() => Some(RbxValue::Bool { value: true }),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_string(&vfs, "/foo/Hello.meta.json"),
+            concat!(
+                "{\n",
+                "  \"properties\": {\n",
+                "    \"Disabled\": {\n",
+                "      \"Type\": \"Bool\",\n",
+                "      \"Value\": true\n",
+                "    }\n",
+                "  }\n",
+                "}\n",
+            )
+        );
+
+        write_properties(
+            &vfs,
+            &tree,
+            find_id(&tree, "Other"),
+            &hashmap! {
+                "Disabled".to_owned() => None,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_string(&vfs, "/foo/Other.meta.json"),
+            "{\n  \"ignoreUnknownInstances\": true\n}\n"
+        );
+    }
+
+    #[test]
+    fn file_rule_meta_file() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "shader.vert" => VfsSnapshot::file("void main() {}"),
+            }),
+        )
+        .unwrap();
+
+        let mut context = InstanceContext::default();
+        context.add_file_rules(vec![FileRule::from_project(
+            "*.vert",
+            "StringValue",
+            Path::new("/foo/default.project.json"),
+        )
+        .unwrap()]);
+
+        let vfs = Vfs::new(imfs);
+        let tree = tree_with_context(&context, &vfs, "/foo");
+
+        write_properties(
+            &vfs,
+            &tree,
+            find_id(&tree, "shader"),
+            &hashmap! {
+                "Archivable".to_owned() => Some(RbxValue::Bool { value: false }),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_string(&vfs, "/foo/shader.meta.json"),
+            concat!(
+                "{\n",
+                "  \"properties\": {\n",
+                "    \"Archivable\": {\n",
+                "      \"Type\": \"Bool\",\n",
+                "      \"Value\": false\n",
+                "    }\n",
+                "  }\n",
+                "}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn model_file_meta_file() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "Value.rbxmx" => VfsSnapshot::file(r#"
+                    <roblox version="4">
+                        <Item class="StringValue" referent="0" />
+                    </roblox>
+                "#),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+        let tree = tree_from_vfs(&vfs, "/foo");
+
+        write_properties(
+            &vfs,
+            &tree,
+            find_id(&tree, "Value"),
+            &hashmap! {
+                "Value".to_owned() => Some(RbxValue::String { value: "Hi".to_owned() }),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_string(&vfs, "/foo/Value.meta.json"),
+            concat!(
+                "{\n",
+                "  \"properties\": {\n",
+                "    \"Value\": {\n",
+                "      \"Type\": \"String\",\n",
+                "      \"Value\": \"Hi\"\n",
+                "    }\n",
+                "  }\n",
+                "}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn directory_meta_file() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "init.meta.json" => VfsSnapshot::file(r#"{ "className": "Configuration" }"#),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+        let tree = tree_from_vfs(&vfs, "/foo");
+
+        write_properties(
+            &vfs,
+            &tree,
+            find_id(&tree, "foo"),
+            &hashmap! {
+                "Archivable".to_owned() => Some(RbxValue::Bool { value: false }),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_string(&vfs, "/foo/init.meta.json"),
+            concat!(
+                "{\n",
+                "  \"className\": \"Configuration\",\n",
+                "  \"properties\": {\n",
+                "    \"Archivable\": {\n",
+                "      \"Type\": \"Bool\",\n",
+                "      \"Value\": false\n",
+                "    }\n",
+                "  }\n",
+                "}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn json_model_child() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo.model.json",
+            VfsSnapshot::file(
+                r#"{
+                    "ClassName": "Folder",
+                    "Children": [
+                        { "Name": "Count", "ClassName": "IntValue" }
+                    ]
+                }"#,
+            ),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+        let tree = tree_from_vfs(&vfs, "/foo.model.json");
+
+        write_properties(
+            &vfs,
+            &tree,
+            find_id(&tree, "Count"),
+            &hashmap! {
+                "Value".to_owned() => Some(RbxValue::Int32 { value: 5 }),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_string(&vfs, "/foo.model.json"),
+            concat!(
+                "{\n",
+                "  \"ClassName\": \"Folder\",\n",
+                "  \"Children\": [\n",
+                "    {\n",
+                "      \"Name\": \"Count\",\n",
+                "      \"ClassName\": \"IntValue\",\n",
+                "      \"Properties\": {\n",
+                "        \"Value\": {\n",
+                "          \"Type\": \"Int32\",\n",
+                "          \"Value\": 5\n",
+                "        }\n",
+                "      }\n",
+                "    }\n",
+                "  ]\n",
+                "}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn project_node() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "default.project.json" => VfsSnapshot::file(r#"{
+                    "name": "foo",
+                    "tree": {
+                        "$className": "Folder",
+                        "Config": {
+                            "$className": "Configuration",
+                            "Count": {
+                                "$className": "IntValue",
+                                "$properties": { "Value": 1 }
+                            }
+                        },
+                        "Scripts": { "$path": "src" }
+                    }
+                }"#),
+                "src" => VfsSnapshot::empty_dir(),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+        let tree = tree_from_vfs(&vfs, "/foo");
+
+        write_properties(
+            &vfs,
+            &tree,
+            find_id(&tree, "Count"),
+            &hashmap! {
+                "Value".to_owned() => None,
+            },
+        )
+        .unwrap();
+
+        write_properties(
+            &vfs,
+            &tree,
+            find_id(&tree, "Scripts"),
+            &hashmap! {
+                "Archivable".to_owned() => Some(RbxValue::Bool { value: false }),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_string(&vfs, "/foo/default.project.json"),
+            concat!(
+                "{\n",
+                "  \"name\": \"foo\",\n",
+                "  \"tree\": {\n",
+                "    \"$className\": \"Folder\",\n",
+                "    \"Config\": {\n",
+                "      \"$className\": \"Configuration\",\n",
+                "      \"Count\": {\n",
+                "        \"$className\": \"IntValue\"\n",
+                "      }\n",
+                "    },\n",
+                "    \"Scripts\": {\n",
+                "      \"$path\": \"src\",\n",
+                "      \"$properties\": {\n",
+                "        \"Archivable\": {\n",
+                "          \"Type\": \"Bool\",\n",
+                "          \"Value\": false\n",
+                "        }\n",
+                "      }\n",
+                "    }\n",
+                "  }\n",
+                "}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn keeps_key_order() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "Value.txt" => VfsSnapshot::file("Hello"),
+                "Value.meta.json" => VfsSnapshot::file(r#"{
+                    "properties": {
+                        "Value": "Hi",
+                        "Archivable": true
+                    },
+                    "ignoreUnknownInstances": true
+                }"#),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+        let tree = tree_from_vfs(&vfs, "/foo");
+
+        write_properties(
+            &vfs,
+            &tree,
+            find_id(&tree, "Value"),
+            &hashmap! {
+                "Value".to_owned() => Some(RbxValue::String { value: "Bye".to_owned() }),
+                "Name".to_owned() => Some(RbxValue::String { value: "Value".to_owned() }),
+                "Archivable".to_owned() => Some(RbxValue::Bool { value: false }),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            read_string(&vfs, "/foo/Value.meta.json"),
+            concat!(
+                "{\n",
+                "  \"properties\": {\n",
+                "    \"Value\": {\n",
+                "      \"Type\": \"String\",\n",
+                "      \"Value\": \"Bye\"\n",
+                "    },\n",
+                "    \"Archivable\": {\n",
+                "      \"Type\": \"Bool\",\n",
+                "      \"Value\": false\n",
+                "    },\n",
+                "    \"Name\": {\n",
+                "      \"Type\": \"String\",\n",
+                "      \"Value\": \"Value\"\n",
+                "    }\n",
+                "  },\n",
+                "  \"ignoreUnknownInstances\": true\n",
+                "}\n",
+            )
+        );
+    }
+
+    #[test]
+    fn yaml_model_unsupported() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot(
+            "/foo",
+            VfsSnapshot::dir(hashmap! {
+                "Config.model.yaml" => VfsSnapshot::file(concat!(
+                    "ClassName: Configuration\n",
+                    "Children:\n",
+                    "  - Name: Count\n",
+                    "    ClassName: IntValue\n",
+                )),
+            }),
+        )
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+        let tree = tree_from_vfs(&vfs, "/foo");
+
+        for name in &["Config", "Count"] {
+            let result = write_properties(
+                &vfs,
+                &tree,
+                find_id(&tree, name),
+                &hashmap! {
+                    "Archivable".to_owned() => Some(RbxValue::Bool { value: false }),
+                },
+            );
+
+            match result {
+                Err(WriteBackError::UnsupportedFile { path }) => {
+                    assert_eq!(path, Path::new("/foo/Config.model.yaml"));
+                }
+                other => panic!("expected unsupported file error, got {:?}", other),
+            }
+        }
+
+        assert!(vfs.metadata("/foo/Config.meta.json").is_err());
+    }
+
+    #[test]
+    fn unsupported_file() {
+        let mut imfs = InMemoryFs::new();
+        imfs.load_snapshot("/foo.rbxmx", VfsSnapshot::file(
+            r#"<roblox version="4"><Item class="Folder" referent="0"><Item class="Folder" referent="1"><Properties><string name="Name">Inner</string></Properties></Item></Item></roblox>"#,
+        ))
+        .unwrap();
+
+        let vfs = Vfs::new(imfs);
+        let tree = tree_from_vfs(&vfs, "/foo.rbxmx");
+
+        // Instances inside of model files can't be given properties from
+        // anywhere but the model file itself.
+        let result = write_properties(
+            &vfs,
+            &tree,
+            find_id(&tree, "Inner"),
+            &hashmap! {
+                "Archivable".to_owned() => Some(RbxValue::Bool { value: false }),
+            },
+        );
+
+        match result {
+            Err(WriteBackError::UnsupportedFile { .. }) => {}
+            other => panic!("expected unsupported file error, got {:?}", other),
+        }
+    }
+}